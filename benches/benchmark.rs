@@ -30,6 +30,11 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("World", |b| {
         b.iter_batched(|| raytracer.clone(), |rt| rt.render(), SmallInput);
     });
+
+    let sah_raytracer = raytracer.clone().with_sah_bvh();
+    c.bench_function("World (SAH BVH)", |b| {
+        b.iter_batched(|| sah_raytracer.clone(), |rt| rt.render(), SmallInput);
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -218,7 +218,7 @@ fn image(
     .with_progressbar();
 
     let world = &mut raytracer.world;
-    let image_texture = ImageTexture::open(Path::new("link.png")).unwrap();
+    let image_texture = ImageTexture::open_cached(Path::new("link.png")).unwrap();
     let image_material = Metal::new(image_texture, 1.);
     let sphere = Sphere::new(vector![0., 0., 0.], 2., image_material);
     world.push(sphere);
@@ -332,7 +332,13 @@ fn cornell(
     let back_wall = Rectangle::xy(vector![0., 0., -200.], 400., 400., white.clone());
     let left_wall = Rectangle::yz(vector![-200., 0., 0.], 400., 400., green);
     let right_wall = Rectangle::yz(vector![200., 0., 0.], 400., 400., red);
-    let light_rect = Rectangle::xz(vector![0., 200., 0.], 200., 200., light);
+    // Facing down (x × z = -y) just below the roof, so the one-sided light shines into the box.
+    let light_rect = Quad::new(
+        vector![-100., 199.5, -100.],
+        vector![200., 0., 0.],
+        vector![0., 0., 200.],
+        light,
+    );
 
     let box1 = Cuboid::new(vector![30., -75., -50.], 100., 150., 100., white.clone())
         .with_rotation(Rotation3::new((15f32).to_radians() * Vector3::y()));
@@ -412,11 +418,12 @@ fn final_scene(
         }
     }
 
+    // Facing down (x × z = -y), so the one-sided light shines onto the scene.
     let light = DiffuseLight::solid_color(7. * WHITE);
-    world.push(Rectangle::xz(
-        vector![273., 554., 279.5],
-        150.,
-        132.5,
+    world.push(Quad::new(
+        vector![198., 554., 213.25],
+        vector![150., 0., 0.],
+        vector![0., 0., 132.5],
         light,
     ));
 
@@ -446,7 +453,7 @@ fn final_scene(
     let boundary = Sphere::new(vector![0., 0., 0.], 5000., Dielectric::new(1.5));
     world.push(ConstantMedium::solid_color(boundary, BLACK, 0.0001));
 
-    let link = Lambertian::new(ImageTexture::open("link.png").unwrap());
+    let link = Lambertian::new(ImageTexture::open_cached("link.png").unwrap());
     world.push(Sphere::new(vector![400., 200., 400.], 100., link));
     let pertext = PerlinNoiseTexture::new(0.1);
     world.push(Sphere::new(
@@ -1,5 +1,8 @@
 //! A camera that receives [`Ray`]s.
 
+use std::f32::consts::PI;
+
+use nalgebra::{Matrix3, Matrix4, Rotation3, UnitQuaternion};
 use rand::Rng;
 
 use crate::ray::Ray;
@@ -19,7 +22,10 @@ use crate::*;
 /// - `v`: Unit upwards direction.
 /// - `w`: Unit direction the camera is facing.
 /// - `lens_radius` Radius of the lense for the purpose of depth-of-field (half the aperture).
+/// - `focus_distance`: Distance at which objects appear in focus.
 /// - `time`: Optional exposure time.
+/// - `shutter_curve`: How the sampled times are distributed over the exposure time.
+/// - `projection`: How [`Ray`]s are distributed over the viewport (perspective, orthographic, or equirectangular).
 #[derive(Clone, Debug)]
 pub struct Camera {
     origin: Vector3<f32>,
@@ -28,9 +34,12 @@ pub struct Camera {
     vertical: Vector3<f32>,
     u: Vector3<f32>,
     v: Vector3<f32>,
-    _w: Vector3<f32>,
+    w: Vector3<f32>,
     lens_radius: f32,
+    focus_distance: f32,
     time: Option<(f32, f32)>,
+    shutter_curve: ShutterCurve,
+    projection: Projection,
 }
 
 impl Camera {
@@ -72,39 +81,358 @@ impl Camera {
             vertical,
             u,
             v,
-            _w: w,
+            w,
             lens_radius: aperture / 2.,
+            focus_distance,
+            time: None,
+            shutter_curve: ShutterCurve::default(),
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// Create a new camera with an orthographic projection, i.e. all [`Ray`]s are parallel, e.g. for technical drawings.
+    ///
+    /// There is no depth-of-field.
+    ///
+    /// # Parameters
+    /// - `lookfrom`: Center of the viewport.
+    /// - `viewport_height`: Height of the viewport in world units.
+    /// - The other parameters are the same as for [`new`](Camera::new).
+    pub fn orthographic(
+        lookfrom: Vector3<f32>,
+        lookat: Vector3<f32>,
+        vup: Vector3<f32>,
+        viewport_height: f32,
+        aspect_ratio: f32,
+    ) -> Self {
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = (lookfrom - lookat).normalize();
+        let u = vup.cross(&w).normalize();
+        let v = w.cross(&u);
+
+        let horizontal = viewport_width * u;
+        let vertical = viewport_height * v;
+        let lower_left_corner = lookfrom - horizontal / 2. - vertical / 2.;
+
+        Self {
+            origin: lookfrom,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius: 0.,
+            focus_distance: 1.,
+            time: None,
+            shutter_curve: ShutterCurve::default(),
+            projection: Projection::Orthographic,
+        }
+    }
+
+    /// Create a new camera rendering a 360° panorama in the equirectangular projection, e.g. for environment maps.
+    ///
+    /// The horizontal image axis covers the longitude from -180° to 180° (with the direction of `lookat` in the middle) and the vertical one the latitude from -90° to 90°.
+    /// The image therefore wraps around seamlessly at its left and right edges. Use an aspect ratio of 2 to avoid distortion.
+    /// There is no depth-of-field.
+    ///
+    /// # Parameters
+    /// The parameters are the same as for [`new`](Camera::new).
+    pub fn equirectangular(
+        lookfrom: Vector3<f32>,
+        lookat: Vector3<f32>,
+        vup: Vector3<f32>,
+    ) -> Self {
+        let w = (lookfrom - lookat).normalize();
+        let u = vup.cross(&w).normalize();
+        let v = w.cross(&u);
+
+        Self {
+            origin: lookfrom,
+            lower_left_corner: lookfrom,
+            horizontal: Vector3::zeros(),
+            vertical: Vector3::zeros(),
+            u,
+            v,
+            w,
+            lens_radius: 0.,
+            focus_distance: 1.,
             time: None,
+            shutter_curve: ShutterCurve::default(),
+            projection: Projection::Equirectangular,
         }
     }
 
+    /// Create a new camera from a view matrix (world to camera space), e.g. as provided by other engines.
+    ///
+    /// The camera looks along its negative z axis with y pointing up. Returns [`None`] if `view` is not invertible.
+    ///
+    /// # Parameters
+    /// - `view`: View matrix of the camera.
+    /// - The other parameters are the same as for [`new`](Camera::new).
+    pub fn from_view_matrix(
+        view: Matrix4<f32>,
+        vertical_fov: f32,
+        aspect_ratio: f32,
+        aperture: f32,
+        focus_distance: f32,
+    ) -> Option<Self> {
+        let camera_to_world = view.try_inverse()?;
+        let origin = camera_to_world.column(3).xyz();
+        let up = camera_to_world.column(1).xyz();
+        let backwards = camera_to_world.column(2).xyz();
+
+        Some(Self::new(
+            origin,
+            origin - backwards,
+            up,
+            vertical_fov,
+            aspect_ratio,
+            aperture,
+            focus_distance,
+        ))
+    }
+
     /// Consume `self` and create a [`Camera`] with a non-zero exposure.
     pub fn with_time(mut self, time_start: f32, time_end: f32) -> Self {
         self.time = Some((time_start, time_end));
         self
     }
 
+    /// Consume `self` and distribute the sampled times over the exposure time according to `shutter_curve`.
+    pub fn with_shutter_curve(mut self, shutter_curve: ShutterCurve) -> Self {
+        self.shutter_curve = shutter_curve;
+        self
+    }
+
     /// Emit a [`Ray`] from the camera.
     pub fn get_ray(&self, u: f32, v: f32) -> Ray {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
-        let random_disk = self.lens_radius * random_vector_in_unit_disk();
-        let offset = self.u * random_disk.x + self.v * random_disk.y;
+        let ray = match self.projection {
+            Projection::Perspective => {
+                let random_disk = self.lens_radius * random_vector_in_unit_disk();
+                let offset = self.u * random_disk.x + self.v * random_disk.y;
 
-        let ray = Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
-        );
+                Ray::new(
+                    self.origin + offset,
+                    self.lower_left_corner + u * self.horizontal + v * self.vertical
+                        - self.origin
+                        - offset,
+                )
+            }
+            Projection::Orthographic => Ray::new(
+                self.lower_left_corner + u * self.horizontal + v * self.vertical,
+                -self.w,
+            ),
+            Projection::Equirectangular => {
+                let longitude = (2. * u - 1.) * PI;
+                let latitude = (v - 0.5) * PI;
+                let direction = latitude.cos()
+                    * (longitude.sin() * self.u - longitude.cos() * self.w)
+                    + latitude.sin() * self.v;
+                Ray::new(self.origin, direction)
+            }
+        };
         if let Some((time1, time2)) = self.time {
-            ray.with_time(time1 + rng.gen::<f32>() * (time2 - time1))
+            let fraction = self.shutter_curve.sample(rng.gen());
+            ray.with_time(time1 + fraction * (time2 - time1))
         } else {
             ray
         }
     }
 
+    /// Whether the viewport could not be constructed, e.g. because `vup` is parallel to the viewing direction.
+    pub(crate) fn is_degenerate(&self) -> bool {
+        [self.u, self.v, self.w]
+            .iter()
+            .any(|axis| axis.iter().any(|component| !component.is_finite()))
+    }
+
     pub fn time(&self) -> Option<(f32, f32)> {
         self.time
     }
+
+    /// Whether the viewport wraps around horizontally, i.e. the left and right edges of the image are adjacent ([equirectangular](Camera::equirectangular) cameras).
+    pub(crate) fn wraps_around(&self) -> bool {
+        self.projection == Projection::Equirectangular
+    }
+
+    pub fn focus_distance(&self) -> f32 {
+        self.focus_distance
+    }
+
+    /// Change the distance at which objects appear in focus while keeping the field of view.
+    ///
+    /// This has no visible effect for [orthographic](Camera::orthographic) and [equirectangular](Camera::equirectangular) cameras.
+    pub fn set_focus_distance(&mut self, focus_distance: f32) {
+        if self.projection != Projection::Perspective {
+            self.focus_distance = focus_distance;
+            return;
+        }
+
+        let scale = focus_distance / self.focus_distance;
+        self.horizontal *= scale;
+        self.vertical *= scale;
+        self.lower_left_corner =
+            self.origin - self.horizontal / 2. - self.vertical / 2. - focus_distance * self.w;
+        self.focus_distance = focus_distance;
+    }
+
+    /// Focus on the scene by its bounding sphere.
+    ///
+    /// The `focus_distance` is set to the distance from the camera to the point on the view axis closest to the center of the sphere enclosing the [`Aabb`](crate::hittable::Aabb) of `world`.
+    /// If `world` has no bounding box (e.g. it is empty) or lies behind the camera, the focus is left unchanged.
+    pub fn autofocus(&mut self, world: &HittableList) {
+        let Some(aabb) = world.bounding_box(0., 0.) else {
+            return;
+        };
+        let center = (aabb.minimum() + aabb.maximum()) / 2.;
+
+        let focus_distance = (center - self.origin).dot(&-self.w);
+        if focus_distance > 0. {
+            self.set_focus_distance(focus_distance);
+        }
+    }
+}
+
+/// A keyframed path of a [`Camera`] for fly-throughs (see [`Raytracer::render_camera_path`]).
+///
+/// Between two keyframes, the position, the size of the viewport, the focus distance, and the aperture are interpolated linearly and the orientation spherically.
+///
+/// # Fields
+/// - `keyframes`: [`Camera`]s sorted by their time.
+#[derive(Clone, Debug, Default)]
+pub struct CameraPath {
+    keyframes: Vec<(f32, Camera)>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Consume `self` and add a keyframe with `camera` at `time`.
+    pub fn with_keyframe(mut self, time: f32, camera: Camera) -> Self {
+        let index = self.keyframes.partition_point(|(t, _)| *t <= time);
+        self.keyframes.insert(index, (time, camera));
+        self
+    }
+
+    /// Time of the first and the last keyframe.
+    ///
+    /// Returns [`None`] if there are no keyframes.
+    pub fn time_range(&self) -> Option<(f32, f32)> {
+        Some((self.keyframes.first()?.0, self.keyframes.last()?.0))
+    }
+
+    /// The [`Camera`] at `time`, clamped to the first and the last keyframe.
+    ///
+    /// Returns [`None`] if there are no keyframes.
+    pub fn camera_at(&self, time: f32) -> Option<Camera> {
+        let index = self.keyframes.partition_point(|(t, _)| *t <= time);
+        if index == 0 {
+            return self.keyframes.first().map(|(_, camera)| camera.clone());
+        }
+        if index == self.keyframes.len() {
+            return self.keyframes.last().map(|(_, camera)| camera.clone());
+        }
+
+        let (time0, camera0) = &self.keyframes[index - 1];
+        let (time1, camera1) = &self.keyframes[index];
+        let s = (time - time0) / (time1 - time0);
+        let lerp = |a: f32, b: f32| a + s * (b - a);
+
+        let orientation = |camera: &Camera| {
+            UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(
+                Matrix3::from_columns(&[camera.u, camera.v, camera.w]),
+            ))
+        };
+        let rotation = orientation(camera0)
+            .try_slerp(&orientation(camera1), s, 1e-6)
+            .unwrap_or_else(|| orientation(camera0))
+            .to_rotation_matrix();
+        let (u, v, w) = (
+            rotation.matrix().column(0).into_owned(),
+            rotation.matrix().column(1).into_owned(),
+            rotation.matrix().column(2).into_owned(),
+        );
+
+        let focus_distance = lerp(camera0.focus_distance, camera1.focus_distance);
+        let viewport_width = lerp(
+            camera0.horizontal.norm() / camera0.focus_distance,
+            camera1.horizontal.norm() / camera1.focus_distance,
+        );
+        let viewport_height = lerp(
+            camera0.vertical.norm() / camera0.focus_distance,
+            camera1.vertical.norm() / camera1.focus_distance,
+        );
+
+        let origin = camera0.origin.lerp(&camera1.origin, s);
+        let horizontal = focus_distance * viewport_width * u;
+        let vertical = focus_distance * viewport_height * v;
+        let lower_left_corner = match camera0.projection {
+            Projection::Perspective => {
+                origin - horizontal / 2. - vertical / 2. - focus_distance * w
+            }
+            Projection::Orthographic | Projection::Equirectangular => {
+                origin - horizontal / 2. - vertical / 2.
+            }
+        };
+
+        Some(Camera {
+            origin,
+            lower_left_corner,
+            horizontal,
+            vertical,
+            u,
+            v,
+            w,
+            lens_radius: lerp(camera0.lens_radius, camera1.lens_radius),
+            focus_distance,
+            time: camera0.time,
+            shutter_curve: camera0.shutter_curve,
+            projection: camera0.projection,
+        })
+    }
+}
+
+/// How a [`Camera`] projects the scene onto its viewport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Projection {
+    /// All [`Ray`]s start at the `origin` of the [`Camera`] (apart from depth-of-field).
+    Perspective,
+    /// All [`Ray`]s are parallel and start on the viewport.
+    Orthographic,
+    /// All [`Ray`]s start at the `origin` of the [`Camera`] and cover the full sphere of directions.
+    Equirectangular,
+}
+
+/// How much light a shutter lets through over the exposure time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShutterCurve {
+    /// The shutter is fully open during the whole exposure time.
+    #[default]
+    Uniform,
+    /// The shutter opens linearly until the middle of the exposure time and then closes linearly.
+    Triangular,
+}
+
+impl ShutterCurve {
+    /// Map a uniformly distributed number in \[0,1\] to a fraction of the exposure time distributed according to the curve.
+    fn sample(self, uniform: f32) -> f32 {
+        match self {
+            ShutterCurve::Uniform => uniform,
+            ShutterCurve::Triangular => {
+                if uniform < 0.5 {
+                    (uniform / 2.).sqrt()
+                } else {
+                    1. - ((1. - uniform) / 2.).sqrt()
+                }
+            }
+        }
+    }
 }
 
 impl Default for Camera {
@@ -120,3 +448,166 @@ impl Default for Camera {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Lambertian;
+    use crate::shapes::Sphere;
+
+    #[test]
+    fn autofocus() {
+        let mut world = HittableList::default();
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        world.push(Sphere::new(vector![-1., 0., -6.], 0.5, material.clone()));
+        world.push(Sphere::new(vector![1., 0., -4.], 0.5, material));
+
+        let mut camera = Camera::new(
+            vector![0., 0., 0.],
+            vector![0., 0., -1.],
+            vector![0., 1., 0.],
+            std::f32::consts::FRAC_PI_6,
+            16. / 9.,
+            // Without an aperture, the rays do not depend on the random lens sample.
+            0.,
+            1.,
+        );
+        let direction = camera.get_ray(0.5, 0.5).direction().normalize();
+
+        camera.autofocus(&world);
+
+        assert!((camera.focus_distance() - 5.).abs() < 1e-4);
+        let refocused_direction = camera.get_ray(0.5, 0.5).direction();
+        assert!((refocused_direction.norm() - 5.).abs() < 0.1);
+        assert!((refocused_direction.normalize() - direction).norm() < 0.05);
+    }
+
+    #[test]
+    fn triangular_shutter() {
+        let fraction_in_middle = |camera: &Camera| {
+            (0..10_000)
+                .filter(|_| (camera.get_ray(0.5, 0.5).time() - 0.5).abs() < 0.25)
+                .count() as f32
+                / 10_000.
+        };
+
+        let uniform = Camera::default().with_time(0., 1.);
+        let triangular = uniform.clone().with_shutter_curve(ShutterCurve::Triangular);
+
+        assert!((fraction_in_middle(&uniform) - 0.5).abs() < 0.05);
+        assert!((fraction_in_middle(&triangular) - 0.75).abs() < 0.05);
+    }
+
+    #[test]
+    fn degenerate() {
+        assert!(!Camera::default().is_degenerate());
+
+        let camera = Camera::new(
+            vector![0., 0., 0.],
+            vector![0., 1., 0.],
+            vector![0., 1., 0.],
+            std::f32::consts::FRAC_PI_6,
+            16. / 9.,
+            0.,
+            1.,
+        );
+        assert!(camera.is_degenerate());
+    }
+
+    #[test]
+    fn from_view_matrix() {
+        let lookfrom = vector![3., 2., 1.];
+        let lookat = vector![0., 0.5, -2.];
+        let vup = vector![0., 1., 0.];
+        let camera = Camera::new(lookfrom, lookat, vup, 0.8, 1.5, 0., 2.);
+
+        let view = Matrix4::look_at_rh(&lookfrom.into(), &lookat.into(), &vup);
+        let from_view = Camera::from_view_matrix(view, 0.8, 1.5, 0., 2.).unwrap();
+
+        for (u, v) in [(0., 0.), (0.5, 0.5), (1., 0.25)] {
+            let expected = camera.get_ray(u, v);
+            let ray = from_view.get_ray(u, v);
+            assert!((ray.origin() - expected.origin()).norm() < 1e-4);
+            assert!((ray.direction() - expected.direction()).norm() < 1e-4);
+        }
+
+        assert!(Camera::from_view_matrix(Matrix4::zeros(), 0.8, 1.5, 0., 2.).is_none());
+    }
+
+    #[test]
+    fn camera_path_midpoint() {
+        let start = Camera::new(
+            vector![0., 0., 0.],
+            vector![0., 0., -1.],
+            vector![0., 1., 0.],
+            0.8,
+            1.5,
+            0.,
+            1.,
+        );
+        let end = Camera::new(
+            vector![4., 2., 0.],
+            vector![5., 2., 0.],
+            vector![0., 1., 0.],
+            0.8,
+            1.5,
+            0.,
+            1.,
+        );
+        let path = CameraPath::new()
+            .with_keyframe(2., end)
+            .with_keyframe(0., start.clone());
+
+        assert_eq!(path.time_range(), Some((0., 2.)));
+        let middle = path.camera_at(1.).unwrap();
+        assert!((middle.origin - vector![2., 1., 0.]).norm() < 1e-5);
+        let expected_w = vector![-1., 0., 1.].normalize();
+        assert!((middle.w - expected_w).norm() < 1e-5);
+
+        let before = path.camera_at(-1.).unwrap();
+        assert!(
+            (before.get_ray(0.3, 0.6).direction() - start.get_ray(0.3, 0.6).direction()).norm()
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn orthographic() {
+        let camera = Camera::orthographic(
+            vector![0., 0., 5.],
+            vector![0., 0., 0.],
+            vector![0., 1., 0.],
+            2.,
+            2.,
+        );
+
+        let lower_left = camera.get_ray(0., 0.);
+        let upper_right = camera.get_ray(1., 1.);
+        assert_eq!(lower_left.direction(), upper_right.direction());
+        assert!((lower_left.direction() - vector![0., 0., -1.]).norm() < 1e-6);
+        assert!((lower_left.origin() - vector![-2., -1., 5.]).norm() < 1e-6);
+        assert!((upper_right.origin() - vector![2., 1., 5.]).norm() < 1e-6);
+    }
+
+    #[test]
+    fn equirectangular() {
+        let camera = Camera::equirectangular(
+            vector![1., 2., 3.],
+            vector![1., 2., 0.],
+            vector![0., 1., 0.],
+        );
+        let direction = |u, v| {
+            let ray = camera.get_ray(u, v);
+            assert_eq!(ray.origin(), vector![1., 2., 3.]);
+            ray.direction()
+        };
+
+        assert!((direction(0.5, 0.5) - vector![0., 0., -1.]).norm() < 1e-6);
+        assert!((direction(0.75, 0.5) - vector![1., 0., 0.]).norm() < 1e-6);
+        assert!((direction(0.3, 1.) - vector![0., 1., 0.]).norm() < 1e-6);
+        assert!((direction(0.3, 0.) - vector![0., -1., 0.]).norm() < 1e-6);
+        for v in [0.1, 0.5, 0.9] {
+            assert!((direction(0., v) - direction(1., v)).norm() < 1e-5);
+        }
+    }
+}
@@ -3,8 +3,6 @@ use std::ops;
 use image::Rgb;
 use rand::Rng;
 
-use crate::*;
-
 /// Macro for [`Color::new`]
 #[macro_export]
 macro_rules! color {
@@ -42,13 +40,13 @@ impl Color {
 
     /// Creates a random vector with each element between 0 and 1.
     pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         Color::new(rng.gen(), rng.gen(), rng.gen())
     }
 
     /// Creates a random vector with each element in a range.
     pub fn random_in_range(min: f32, max: f32) -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         Color::new(
             min + rng.gen::<f32>() * (max - min),
             min + rng.gen::<f32>() * (max - min),
@@ -56,6 +54,28 @@ impl Color {
         )
     }
 
+    /// Relative luminance of the (linear) [`Color`] with the Rec. 709 weights.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// Fit the [`Color`] into the displayable range by desaturating it towards white instead of clamping each channel.
+    ///
+    /// Clamping the channels independently shifts the hue of bright saturated colors (e.g. bright orange becomes yellow).
+    /// Instead, the color is scaled down until its brightest channel is 1, and the excess brightness is used to blend it towards white, which keeps the hue.
+    /// Colors that already fit are returned unchanged (apart from clamping negative channels to 0).
+    pub fn desaturate_to_fit(self) -> Self {
+        let color: Self = self.into_iter().map(|c| c.max(0.)).collect();
+        let max = color.r.max(color.g).max(color.b);
+        if max <= 1. {
+            return color;
+        }
+
+        let scaled = color / max;
+        let excess = 1. - 1. / max;
+        scaled + (WHITE - scaled) * excess
+    }
+
     /// Formats the [`Color`] as a [`String`], converting the `f32` RGB values to `u8`.
     pub(crate) fn to_color_str(self) -> String {
         let rgb: [u8; 3] = self.into();
@@ -89,6 +109,13 @@ impl From<Rgb<u8>> for Color {
     }
 }
 
+impl From<Rgb<f32>> for Color {
+    /// Convert a floating point pixel (e.g. from an HDR image) without clamping.
+    fn from(value: Rgb<f32>) -> Self {
+        color![value[0], value[1], value[2]]
+    }
+}
+
 impl ops::Add for Color {
     type Output = Self;
 
@@ -232,6 +259,33 @@ impl FromIterator<f32> for Color {
     }
 }
 
+/// Curves to compress high dynamic range [`Color`]s into the displayable range.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ToneMap {
+    /// Leave the colors as they are, so bright channels clip.
+    #[default]
+    None,
+    /// Map each channel `c` to `c / (1 + c)`.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    AcesFilmic,
+}
+
+impl ToneMap {
+    /// Apply the curve to a linear [`Color`].
+    pub fn apply(self, color: Color) -> Color {
+        match self {
+            ToneMap::None => color,
+            ToneMap::Reinhard => color.into_iter().map(|c| c / (1. + c)).collect(),
+            ToneMap::AcesFilmic => color
+                .into_iter()
+                .map(|c| ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0., 1.))
+                .collect(),
+        }
+    }
+}
+
 pub const WHITE: Color = Color {
     r: 1.,
     g: 1.,
@@ -299,6 +353,23 @@ mod tests {
         assert_eq!(v / 2., color![0.5, 1., 1.5]);
     }
 
+    #[test]
+    fn desaturate_to_fit() {
+        // Hue as the position of the middle channel between the smallest and the largest one.
+        let hue = |color: Color| (color.g() - color.b()) / (color.r() - color.b());
+
+        let color = color![2., 1., 0.];
+        let fitted = color.desaturate_to_fit();
+        let clamped: Color = color.into_iter().map(|c| c.clamp(0., 1.)).collect();
+
+        assert!(fitted.into_iter().all(|c| (0. ..=1.).contains(&c)));
+        assert!((hue(fitted) - hue(color)).abs() < (hue(clamped) - hue(color)).abs());
+        assert!((hue(fitted) - hue(color)).abs() < 1e-5);
+
+        let in_gamut = color![0.2, 0.4, 0.6];
+        assert_eq!(in_gamut.desaturate_to_fit(), in_gamut);
+    }
+
     #[test]
     #[should_panic]
     #[allow(clippy::no_effect)]
@@ -306,4 +377,21 @@ mod tests {
         let v = color![1., 2., 3.];
         v[3];
     }
+
+    #[test]
+    fn tone_maps() {
+        let color = color![0., 1., 100.];
+
+        assert_eq!(ToneMap::None.apply(color), color);
+
+        let reinhard = ToneMap::Reinhard.apply(color);
+        assert_eq!(reinhard.r(), 0.);
+        assert_eq!(reinhard.g(), 0.5);
+        assert!(reinhard.b() < 1. && reinhard.b() > 0.99);
+
+        let aces = ToneMap::AcesFilmic.apply(color);
+        assert_eq!(aces.r(), 0.);
+        assert!((aces.g() - 0.8038).abs() < 1e-3);
+        assert_eq!(aces.b(), 1.);
+    }
 }
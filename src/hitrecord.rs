@@ -13,8 +13,11 @@ use crate::*;
 /// - (`u`, `v`): Coordinates on the surface submanifold (lie inside \[0,1\]).
 /// - `normal`: Normal vector to the surface.
 /// - `t`: Parameter of the [Ray] where the hit happened.
+/// - `distance`: Distance from the origin of the [Ray] to `point`, i.e. `t` times the length of its direction.
 /// - `front_face`: Whether the hit faces the front or the back of the [Hittable].
 /// - `material`: [Material] that was hit.
+/// - `tangent`: Optional unit tangent to the surface in the direction of increasing `u`, e.g. for anisotropic materials.
+/// - `object`: Index of the object in [`Raytracer::world`](crate::Raytracer::world) that was hit, if it was tracked (e.g. for [light AOVs](crate::Raytracer::render_light_aovs)).
 #[derive(Clone, Debug)]
 pub struct HitRecord<'a> {
     pub point: Vector3<f32>,
@@ -22,12 +25,17 @@ pub struct HitRecord<'a> {
     pub v: f32,
     pub normal: Vector3<f32>,
     pub t: f32,
+    pub distance: f32,
     pub front_face: bool,
     pub material: &'a dyn Material,
+    pub tangent: Option<Vector3<f32>>,
+    pub(crate) object: Option<usize>,
 }
 
 impl<'a> HitRecord<'a> {
     /// Create a hit record.
+    ///
+    /// The [Ray] is unknown, so its direction is assumed to be normalized, i.e. `distance` is `t`.
     pub fn new(
         point: Vector3<f32>,
         u: f32,
@@ -43,8 +51,11 @@ impl<'a> HitRecord<'a> {
             v,
             normal,
             t,
+            distance: t,
             front_face,
             material,
+            tangent: None,
+            object: None,
         }
     }
 
@@ -67,11 +78,20 @@ impl<'a> HitRecord<'a> {
             v,
             normal,
             t,
+            distance: t * ray.direction().norm(),
             front_face,
             material,
+            tangent: None,
+            object: None,
         }
     }
 
+    /// Consume `self` and add the `tangent` of the surface.
+    pub fn with_tangent(mut self, tangent: Vector3<f32>) -> Self {
+        self.tangent = Some(tangent);
+        self
+    }
+
     pub fn material(&self) -> &'a dyn Material {
         self.material
     }
@@ -9,6 +9,7 @@ use std::sync::Arc;
 
 use nalgebra::Rotation3;
 use rand::Rng;
+use rayon::prelude::*;
 
 use crate::hitrecord::HitRecord;
 use crate::ray::Ray;
@@ -17,6 +18,18 @@ use crate::*;
 
 type HittableArc = Arc<dyn Hittable>;
 
+/// Number of buckets per axis in which [`Bvh::new_sah`] evaluates candidate splits.
+const SAH_BUCKETS: usize = 12;
+
+/// Cost of traversing a [`Bvh`] node relative to the cost of hitting one [`Hittable`] in the surface area heuristic of [`Bvh::new_sah`].
+const SAH_TRAVERSAL_COST: f32 = 0.125;
+
+/// Minimum number of [`Hittable`]s in a [`HittableList`] for which [`HittableList::par_hit`] actually searches in parallel.
+pub const PAR_HIT_THRESHOLD: usize = 1024;
+
+/// Number of [`Hittable`]s [`HittableList::par_hit`] searches serially on one worker.
+const PAR_HIT_CHUNK: usize = 256;
+
 /// An abstraction over all objects that can be hit by [Ray]s.
 ///
 /// All objects that can be hit by [`Ray`]s and encompassed by [axis-aligned bounding boxes](Aabb) should implement [`Hittable`]. This not only includes shapes, but also more abstract objects like [lists of shapes](HittableList).
@@ -30,7 +43,7 @@ pub trait Hittable: Debug + Send + Sync {
     /// - `ray`: [Ray] to check
     /// - `t_min`: Minimum allowed parameter of the ray (excluded).
     /// - `t_max`: Maximum allowed parameter of the ray (excluded).
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
 
     /// Return the [`Aabb`] that completely encompasses the object at the origin.
     ///
@@ -50,7 +63,7 @@ pub trait Hittable: Debug + Send + Sync {
     /// - `ray`: [Ray] to check
     /// - `t_min`: Minimum allowed parameter of the ray (excluded).
     /// - `t_max`: Maximum allowed parameter of the ray (excluded).
-    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         self.center().hit(self, ray, t_min, t_max)
     }
 
@@ -76,23 +89,28 @@ pub trait Hittable: Debug + Send + Sync {
         let box1 = self.bounding_box(0., 0.).unwrap();
         let box2 = other.bounding_box(0., 0.).unwrap();
 
-        box1.minimum[axis]
-            .partial_cmp(&box2.minimum[axis])
-            .expect("NaN encountered")
+        box1.minimum[axis].total_cmp(&box2.minimum[axis])
     }
 
     /// Get a reference to the center ([`Offset`]) of the [`Hittable`].
     fn center(&self) -> &Offset;
+
+    /// The [`Hittable`] as a [`Bvh`] if it is one, so that [`Bvh::hit_counting`] can count the visits of its subtrees.
+    fn as_bvh(&self) -> Option<&Bvh> {
+        None
+    }
 }
 
 /// Stores a list of [`Hittable`]s.
 ///
 /// # Fields
 /// - `hittables`: [Vector](Vec) of [`Box`]s of [`Hittable`]s.
+/// - `parallel_hit`: Whether [`hit`](Hittable::hit) should use [`par_hit`](HittableList::par_hit).
 #[derive(Clone, Default, Debug)]
 pub struct HittableList {
     center: Offset,
     hittables: Vec<HittableArc>,
+    parallel_hit: bool,
 }
 
 impl HittableList {
@@ -101,7 +119,77 @@ impl HittableList {
         Self {
             center: Offset::new(center),
             hittables: Vec::new(),
+            parallel_hit: false,
+        }
+    }
+
+    /// Consume `self` and search for hits in parallel (see [`par_hit`](HittableList::par_hit)).
+    ///
+    /// This only pays off for very large lists that cannot be put into a [`Bvh`] (e.g. because of an infinite plane), as the image is already rendered in parallel.
+    pub fn with_parallel_hit(mut self) -> Self {
+        self.parallel_hit = true;
+        self
+    }
+
+    /// Check whether a [`Ray`] hits any of the [`Hittable`]s like [`hit`](Hittable::hit), but search through them in parallel.
+    ///
+    /// Lists shorter than [`PAR_HIT_THRESHOLD`] are searched serially, as the overhead of parallelization would dominate.
+    pub fn par_hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut hit_record_option =
+            self.par_hit_origin(self.center.transform_ray(ray), t_min, t_max);
+
+        if let Some(hit_record) = &mut hit_record_option {
+            self.center.transform_hit_record(hit_record, ray.time());
+        }
+
+        hit_record_option
+    }
+
+    fn par_hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        if self.hittables.len() < PAR_HIT_THRESHOLD {
+            return self.serial_hit_origin(ray, t_min, t_max);
+        }
+
+        // The workers do not share the random numbers of the current thread (e.g. for [`ConstantMedium`](crate::shapes::ConstantMedium)s),
+        // so each chunk gets a generator seeded from them, which keeps seeded renders reproducible.
+        let seed = random::rng().gen();
+        self.hittables
+            .par_chunks(PAR_HIT_CHUNK)
+            .enumerate()
+            .filter_map(|(chunk, hittables)| {
+                random::with_seed(random::mix_seed(seed, chunk as u64), || {
+                    HittableList::closest_hit(hittables, ray, t_min, t_max)
+                })
+            })
+            .min_by(|a, b| a.t.total_cmp(&b.t))
+    }
+
+    fn serial_hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        HittableList::closest_hit(&self.hittables, ray, t_min, t_max)
+    }
+
+    /// Find the closest hit among `hittables`.
+    ///
+    /// Degenerate shapes may report a NaN parameter, which must neither win nor abort the render.
+    fn closest_hit(
+        hittables: &[HittableArc],
+        ray: Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let mut hit_record_final = None;
+        let mut closest_so_far = t_max;
+
+        for hittable in hittables {
+            if let Some(hit_record) = hittable.hit(ray, t_min, closest_so_far) {
+                if hit_record.t.is_finite() {
+                    closest_so_far = hit_record.t;
+                    hit_record_final = Some(hit_record);
+                }
+            }
         }
+
+        hit_record_final
     }
 
     /// Push a new [`Hittable`] to the end.
@@ -129,15 +217,62 @@ impl HittableList {
         self.hittables.pop()
     }
 
+    /// Wrap each [`Hittable`] so that its hits record its index (see [`HitRecord::object`]).
+    ///
+    /// The [`Hittable`]s are shared, not cloned.
+    pub(crate) fn tagged(&self) -> Self {
+        Self {
+            center: self.center.clone(),
+            hittables: self
+                .hittables
+                .iter()
+                .enumerate()
+                .map(|(index, hittable)| {
+                    Arc::new(Tagged {
+                        center: Offset::default(),
+                        inner: hittable.clone(),
+                        index,
+                    }) as HittableArc
+                })
+                .collect(),
+            parallel_hit: self.parallel_hit,
+        }
+    }
+
     /// Sort by the value of the `minimum` of the [`Aabb`]s on an axis.
     ///
     /// This allows creating a kind of spatial hierarchy (see [Bvh]).
+    /// Ties are broken by the centers of the [`Aabb`]s on the other two axes, so that e.g. a grid of equal boxes is still sorted spatially.
     ///
     /// # Parameters
     /// - `axis`: Axis along which the minima should be compared.
     fn sort_by_box(&mut self, axis: usize) {
-        self.hittables
-            .sort_by(|a, b| Hittable::cmp_box(a.as_ref(), b.as_ref(), axis));
+        // The bounding boxes are computed once, as this can be expensive e.g. for moving or composite `Hittable`s.
+        // `Hittable`s without a box (which `Bvh::new` rules out beforehand) are sorted last.
+        let mut keyed: Vec<([f32; 3], HittableArc)> = std::mem::take(&mut self.hittables)
+            .into_iter()
+            .map(|hittable| {
+                let key = hittable
+                    .bounding_box(0., 0.)
+                    .map_or([f32::INFINITY; 3], |aabb| {
+                        let centroid = (aabb.minimum() + aabb.maximum()) / 2.;
+                        [
+                            aabb.minimum()[axis],
+                            centroid[(axis + 1) % 3],
+                            centroid[(axis + 2) % 3],
+                        ]
+                    });
+                (key, hittable)
+            })
+            .collect();
+
+        keyed.sort_by(|(a, _), (b, _)| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| a.total_cmp(b))
+                .fold(Ordering::Equal, Ordering::then)
+        });
+        self.hittables = keyed.into_iter().map(|(_, hittable)| hittable).collect();
     }
 
     /// Split at `mid` and return both halves.
@@ -148,28 +283,24 @@ impl HittableList {
             Self {
                 hittables: left.to_owned(),
                 center: self.center.clone(),
+                parallel_hit: self.parallel_hit,
             },
             Self {
                 hittables: right.to_owned(),
                 center: self.center,
+                parallel_hit: self.parallel_hit,
             },
         )
     }
 }
 
 impl Hittable for HittableList {
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let mut hit_record_final: Option<HitRecord> = None;
-        let mut closest_so_far = t_max;
-
-        for hittable in &self.hittables {
-            if let Some(hit_record) = hittable.hit(ray, t_min, closest_so_far) {
-                closest_so_far = hit_record.t;
-                hit_record_final = Some(hit_record);
-            }
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        if self.parallel_hit {
+            self.par_hit_origin(ray, t_min, t_max)
+        } else {
+            self.serial_hit_origin(ray, t_min, t_max)
         }
-
-        hit_record_final
     }
 
     fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
@@ -217,6 +348,34 @@ impl Index<usize> for HittableList {
     }
 }
 
+/// Records the index of a [`Hittable`] in its [`HittableList`] in its hits (see [`HittableList::tagged`]).
+///
+/// # Fields
+/// - `inner`: Wrapped [`Hittable`].
+/// - `index`: Index of `inner` in its [`HittableList`].
+#[derive(Debug)]
+struct Tagged {
+    center: Offset,
+    inner: HittableArc,
+    index: usize,
+}
+
+impl Hittable for Tagged {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut hit_record = self.inner.hit(ray, t_min, t_max)?;
+        hit_record.object = Some(self.index);
+        Some(hit_record)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
 /// An axis-aligned bounding box.
 ///
 /// This allows for a simple way to calculate [Ray] hits more easily by first checking for [Aabb]s encompassing the objects.
@@ -269,6 +428,27 @@ impl Aabb {
         Aabb { minimum, maximum }
     }
 
+    /// Expand the [`Aabb`] symmetrically so that no axis is thinner than `min_width`.
+    ///
+    /// Flat shapes should pad their boxes this way so that the slab test in [`hit`](Aabb::hit) does not degenerate.
+    pub fn pad(mut self, min_width: f32) -> Self {
+        for axis in 0..3 {
+            let width = self.maximum[axis] - self.minimum[axis];
+            if width < min_width {
+                let delta = (min_width - width) / 2.;
+                self.minimum[axis] -= delta;
+                self.maximum[axis] += delta;
+            }
+        }
+        self
+    }
+
+    /// Surface area of the [`Aabb`].
+    pub fn surface_area(&self) -> f32 {
+        let size = self.maximum - self.minimum;
+        2. * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
     pub fn minimum(&self) -> Vector3<f32> {
         self.minimum
     }
@@ -284,9 +464,9 @@ impl Aabb {
         for (((min, max), ray_direction), ray_origin) in self
             .minimum()
             .into_iter()
-            .zip(self.maximum().into_iter())
-            .zip(ray.direction().into_iter())
-            .zip(ray.origin().into_iter())
+            .zip(self.maximum().iter())
+            .zip(ray.direction().iter())
+            .zip(ray.origin().iter())
         {
             let inverse_distance = 1. / ray_direction;
             let mut t0 = (min - ray_origin) * inverse_distance;
@@ -303,6 +483,27 @@ impl Aabb {
         }
         true
     }
+
+    /// Calculate the parameter interval in which a [`Ray`] is inside the [`Aabb`].
+    ///
+    /// Returns [`None`] if the [`Ray`] does not pass through the [`Aabb`] inside the allowed parameter range.
+    pub fn hit_interval(&self, ray: Ray, mut t_min: f32, mut t_max: f32) -> Option<(f32, f32)> {
+        for axis in 0..3 {
+            let inverse_distance = 1. / ray.direction()[axis];
+            let mut t0 = (self.minimum[axis] - ray.origin()[axis]) * inverse_distance;
+            let mut t1 = (self.maximum[axis] - ray.origin()[axis]) * inverse_distance;
+            if inverse_distance < 0. {
+                (t0, t1) = (t1, t0);
+            }
+
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
 }
 
 /// Error when a [`Hittable`] cannot be encompassed by a [`Aabb`].
@@ -318,6 +519,7 @@ impl fmt::Display for BoundingBoxError {
 /// Possible nodes in a [`Bvh`].
 ///
 /// [`Bvh`]s are binary trees and might therefore sometimes end with only one node. With this enum, [`Option`] is not needed.
+/// Inner nodes are kept apart from the leaves, so that the tree can be walked without knowing the type of the [`Hittable`]s.
 #[derive(Clone, Debug)]
 enum BvhNode {
     One(HittableArc),
@@ -333,11 +535,13 @@ enum BvhNode {
 /// - `aabb`: [`Aabb`] of the subtree/node.
 /// - `left`: Left subtree/node.
 /// - `right`: Right subtree/node.
+/// - `depth`: Number of levels of the subtree (see [`depth`](Bvh::depth)).
 #[derive(Clone, Debug)]
-pub(crate) struct Bvh {
+pub struct Bvh {
     center: Offset,
     aabb: Aabb,
     subnode: BvhNode,
+    depth: usize,
 }
 
 impl Bvh {
@@ -358,36 +562,32 @@ impl Bvh {
             return Err(BoundingBoxError);
         }
 
-        let mut rand = rand::thread_rng();
+        let mut rand = random::rng();
 
         let center = hittables.center.clone();
         let subnode: BvhNode;
+        let mut depth = 1;
         let axis: usize = rand.gen_range(0..=2);
 
         if hittables.len() == 1 {
             let elem = hittables.pop().unwrap();
             subnode = BvhNode::One(elem);
         } else if hittables.len() == 2 {
+            hittables.sort_by_box(axis);
             let last = hittables.pop().unwrap();
             let first = hittables.pop().unwrap();
-            match first.cmp_box(last.as_ref(), axis) {
-                Ordering::Less | Ordering::Equal => {
-                    subnode = BvhNode::Two(first, last);
-                }
-                Ordering::Greater => {
-                    subnode = BvhNode::Two(last, first);
-                }
-            }
+            subnode = BvhNode::Two(first, last);
         } else {
             hittables.sort_by_box(axis);
 
             let mid = hittables.len() / 2;
             let split = hittables.split_at(mid);
 
-            let left = Arc::new(Bvh::new(split.0, time0, time1)?);
-            let right = Arc::new(Bvh::new(split.1, time0, time1)?);
+            let left = Bvh::new(split.0, time0, time1)?;
+            let right = Bvh::new(split.1, time0, time1)?;
 
-            subnode = BvhNode::Two(left, right);
+            depth += left.depth.max(right.depth);
+            subnode = BvhNode::Two(Arc::new(left), Arc::new(right));
         }
 
         let aabb = match &subnode {
@@ -402,9 +602,191 @@ impl Bvh {
             center,
             aabb,
             subnode,
+            depth,
         })
     }
 
+    /// Create a new [`Bvh`] like [`new`](Bvh::new), but split each level by the surface area heuristic (SAH).
+    ///
+    /// On each axis, the [`Hittable`]s are binned into [`SAH_BUCKETS`] buckets by the centroids of their [`Aabb`]s, and the split between two buckets with the lowest expected cost is chosen.
+    /// The cost of a split is [`SAH_TRAVERSAL_COST`] plus the number of [`Hittable`]s in each half weighted by the probability of hitting it, i.e. the ratio of its surface area to the one of the whole node.
+    /// This is slower to build, but results in a much better tree for unevenly distributed [`Hittable`]s (e.g. meshes).
+    /// If no split is cheaper than hitting all [`Hittable`]s (e.g. because they overlap heavily), they are split at the median along the axis their centroids spread the most, and both halves are split by the SAH again.
+    pub fn new_sah(
+        mut hittables: HittableList,
+        time0: f32,
+        time1: f32,
+    ) -> Result<Self, BoundingBoxError> {
+        if !Bvh::check_hittable_list(&hittables) {
+            return Err(BoundingBoxError);
+        }
+        if hittables.len() <= 2 {
+            return Bvh::new(hittables, time0, time1);
+        }
+
+        let boxes = hittables
+            .hittables
+            .iter()
+            .map(|hittable| hittable.bounding_box(time0, time1))
+            .collect::<Option<Vec<Aabb>>>()
+            .ok_or(BoundingBoxError)?;
+        let centroids: Vec<Vector3<f32>> = boxes
+            .iter()
+            .map(|aabb| (aabb.minimum + aabb.maximum) / 2.)
+            .collect();
+        let centroid_bounds = centroids
+            .iter()
+            .map(|centroid| Aabb::new(*centroid, *centroid))
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap();
+        let bucket = |centroid: &Vector3<f32>, axis: usize| {
+            let extent = centroid_bounds.maximum[axis] - centroid_bounds.minimum[axis];
+            let relative = (centroid[axis] - centroid_bounds.minimum[axis]) / extent;
+            ((relative * SAH_BUCKETS as f32) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        // Cost of hitting all `Hittable`s, in units of the cost of hitting one.
+        let mut best_cost = hittables.len() as f32;
+        let surface_area = boxes
+            .iter()
+            .copied()
+            .reduce(|a, b| a.surrounding(&b))
+            .unwrap()
+            .surface_area();
+        let mut best_split = None;
+        for axis in (0..3).filter(|_| surface_area > 0.) {
+            if centroid_bounds.maximum[axis] - centroid_bounds.minimum[axis] <= f32::EPSILON {
+                continue;
+            }
+
+            let mut buckets: [(usize, Option<Aabb>); SAH_BUCKETS] = [(0, None); SAH_BUCKETS];
+            for (aabb, centroid) in boxes.iter().zip(&centroids) {
+                let (count, bucket_box) = &mut buckets[bucket(centroid, axis)];
+                *count += 1;
+                *bucket_box =
+                    Some(bucket_box.map_or(*aabb, |bucket_box| bucket_box.surrounding(aabb)));
+            }
+
+            let cost_of = |buckets: &[(usize, Option<Aabb>)]| {
+                let count: usize = buckets.iter().map(|(count, _)| count).sum();
+                let aabb = buckets
+                    .iter()
+                    .filter_map(|(_, aabb)| *aabb)
+                    .reduce(|a, b| a.surrounding(&b));
+                aabb.map_or(0., |aabb| count as f32 * aabb.surface_area() / surface_area)
+            };
+            for split in 1..SAH_BUCKETS {
+                let cost =
+                    SAH_TRAVERSAL_COST + cost_of(&buckets[..split]) + cost_of(&buckets[split..]);
+                if cost < best_cost {
+                    best_cost = cost;
+                    best_split = Some((axis, split));
+                }
+            }
+        }
+
+        let center = hittables.center.clone();
+        let mut hittables: Vec<_> = std::mem::take(&mut hittables.hittables)
+            .into_iter()
+            .zip(&centroids)
+            .collect();
+        let (left, right) = match best_split {
+            Some((axis, split)) => hittables
+                .into_iter()
+                .partition(|(_, centroid)| bucket(centroid, axis) < split),
+            None => {
+                let extent =
+                    |axis: usize| centroid_bounds.maximum[axis] - centroid_bounds.minimum[axis];
+                let axis = (0..3)
+                    .max_by(|a, b| extent(*a).total_cmp(&extent(*b)))
+                    .unwrap();
+                hittables.sort_by(|(_, a), (_, b)| a[axis].total_cmp(&b[axis]));
+                let right = hittables.split_off(hittables.len() / 2);
+                (hittables, right)
+            }
+        };
+        let half = |hittables: Vec<(HittableArc, &Vector3<f32>)>| HittableList {
+            center: center.clone(),
+            hittables: hittables
+                .into_iter()
+                .map(|(hittable, _)| hittable)
+                .collect(),
+            parallel_hit: false,
+        };
+
+        let left = Bvh::new_sah(half(left), time0, time1)?;
+        let right = Bvh::new_sah(half(right), time0, time1)?;
+        let aabb = Aabb::surrounding(&left.aabb, &right.aabb);
+
+        Ok(Self {
+            center,
+            aabb,
+            depth: 1 + left.depth.max(right.depth),
+            subnode: BvhNode::Two(Arc::new(left), Arc::new(right)),
+        })
+    }
+
+    /// Number of levels of the tree, i.e. of nodes on the longest path from the root to a [`Hittable`].
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Check whether a [`Ray`] hits any of the [`Hittable`]s like [`hit`](Hittable::hit), and add the number of visited nodes of the tree to `visits`.
+    ///
+    /// This is a separate traversal for debugging (see [`ShadingMode::BvhHeatmap`](crate::raytracer::ShadingMode::BvhHeatmap)), so that [`hit`](Hittable::hit) does not pay for counting.
+    /// Only nodes of this tree are counted, not those of [`Bvh`]s nested inside its [`Hittable`]s.
+    pub fn hit_counting<'a>(
+        &'a self,
+        ray: Ray,
+        t_min: f32,
+        t_max: f32,
+        visits: &mut usize,
+    ) -> Option<HitRecord<'a>> {
+        self.center.hit_with(ray, |ray| {
+            *visits += 1;
+            if !self.aabb.hit(ray, t_min, t_max) {
+                return None;
+            }
+
+            // Subtrees are counted further, other `Hittable`s are hit as usual.
+            let mut hit_child = |child: &'a HittableArc, t_max: f32| match child.as_bvh() {
+                Some(bvh) => bvh.hit_counting(ray, t_min, t_max, visits),
+                None => child.hit(ray, t_min, t_max),
+            };
+            match &self.subnode {
+                BvhNode::One(child) => hit_child(child, t_max),
+                BvhNode::Two(left, right) => {
+                    let hit_left = hit_child(left, t_max);
+                    let t_max = match &hit_left {
+                        Some(hit_record) => hit_record.t,
+                        None => t_max,
+                    };
+                    let hit_right = hit_child(right, t_max);
+
+                    hit_right.or(hit_left)
+                }
+            }
+        })
+    }
+
+    /// Find the closer hit of `left` and `right`, searching `right` only up to the hit of `left`.
+    fn hit_pair<'a>(
+        left: &'a dyn Hittable,
+        right: &'a dyn Hittable,
+        ray: Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'a>> {
+        let hit_left = left.hit(ray, t_min, t_max);
+        let t_max = match &hit_left {
+            Some(hit_record) => hit_record.t,
+            None => t_max,
+        };
+        let hit_right = right.hit(ray, t_min, t_max);
+
+        hit_right.or(hit_left)
+    }
+
     pub fn check_hittable_list(hittables: &HittableList) -> bool {
         if hittables.is_empty() {
             return false;
@@ -421,7 +803,7 @@ impl Bvh {
 }
 
 impl Hittable for Bvh {
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         if !self.aabb.hit(ray, t_min, t_max) {
             return None;
         }
@@ -429,14 +811,7 @@ impl Hittable for Bvh {
         match &self.subnode {
             BvhNode::One(child) => child.hit(ray, t_min, t_max),
             BvhNode::Two(left, right) => {
-                let hit_left = left.hit(ray, t_min, t_max);
-                let t_max = match &hit_left {
-                    Some(hit_record) => hit_record.t,
-                    None => t_max,
-                };
-                let hit_right = right.hit(ray, t_min, t_max);
-
-                hit_right.or(hit_left)
+                Bvh::hit_pair(left.as_ref(), right.as_ref(), ray, t_min, t_max)
             }
         }
     }
@@ -448,22 +823,38 @@ impl Hittable for Bvh {
     fn center(&self) -> &Offset {
         &self.center
     }
+
+    fn as_bvh(&self) -> Option<&Bvh> {
+        Some(self)
+    }
 }
 
 /// Options to store [`Hittable`]s.
 ///
 /// Both [`HittableList`] and [`Bvh`] can store [`Hittable`]s. Latter is faster, but not always possible (see [`BoundingBoxError`], e.g. an infinite plane).
+#[derive(Clone, Debug)]
 pub(crate) enum HittableListOptions {
     HittableList(HittableList),
     Bvh(Bvh),
 }
 
+impl HittableListOptions {
+    /// Check whether a [`Ray`] hits any of the stored [`Hittable`]s (see [`Hittable::hit`]).
+    pub(crate) fn hit(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        match self {
+            HittableListOptions::HittableList(list) => list.hit(ray, t_min, t_max),
+            HittableListOptions::Bvh(bvh) => bvh.hit(ray, t_min, t_max),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::materials::Lambertian;
     use crate::shapes::Sphere;
     use crate::textures::SolidColor;
+    use crate::vec3::random_vector_in_range;
 
     #[test]
     fn bvh_hit() {
@@ -484,6 +875,7 @@ mod test {
             aabb,
             subnode,
             center: Offset::default(),
+            depth: 1,
         };
 
         let ray_hit_left = Ray::new(vector![0., 0., 0.], vector![-2., 0., -1.]);
@@ -498,4 +890,306 @@ mod test {
         let no_hit = bvh.hit(ray_no_hit, 0., f32::INFINITY);
         assert!(no_hit.is_none());
     }
+
+    #[test]
+    fn par_hit_matches_serial() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        for _ in 0..2 * PAR_HIT_THRESHOLD {
+            world.push(Sphere::new(
+                random_vector_in_range(-10., 10.),
+                0.3,
+                material.clone(),
+            ));
+        }
+
+        for _ in 0..100 {
+            let ray = Ray::new(
+                vector![0., 0., 20.],
+                random_vector_in_range(-0.5, 0.5) - Vector3::z(),
+            );
+            let serial = world.hit(ray, 0.001, f32::INFINITY);
+            let parallel = world.par_hit(ray, 0.001, f32::INFINITY);
+            assert_eq!(serial.map(|hit| hit.t), parallel.map(|hit| hit.t));
+        }
+    }
+
+    #[test]
+    fn seeded_par_hit() {
+        use crate::shapes::ConstantMedium;
+
+        // Thin media at random positions along the ray, so every hit depends on the random numbers of its worker.
+        let mut world = HittableList::default();
+        for index in 0..2 * PAR_HIT_THRESHOLD {
+            world.push(ConstantMedium::solid_color(
+                Sphere::new(
+                    vector![0., 0., -(index as f32) - 2.],
+                    0.5,
+                    Lambertian::default(),
+                ),
+                color![1., 1., 1.],
+                0.01,
+            ));
+        }
+
+        let ray = Ray::new(Vector3::zeros(), -Vector3::z());
+        let hit = |seed| {
+            random::with_seed(seed, || world.par_hit(ray, 0.001, f32::INFINITY)).map(|hit| hit.t)
+        };
+        assert!(hit(42).is_some());
+        assert_eq!(hit(42), hit(42));
+        assert_ne!(hit(42), hit(43));
+    }
+
+    /// Reports a hit with a NaN parameter for every ray, like a degenerate shape.
+    #[derive(Clone, Debug, Default)]
+    struct NanHit {
+        center: Offset,
+        material: Lambertian<SolidColor>,
+    }
+
+    impl Hittable for NanHit {
+        fn hit_origin(&self, ray: Ray, _t_min: f32, _t_max: f32) -> Option<HitRecord<'_>> {
+            Some(HitRecord::from_ray(
+                Vector3::zeros(),
+                0.,
+                0.,
+                Vector3::y(),
+                f32::NAN,
+                &self.material,
+                ray,
+            ))
+        }
+
+        fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+            None
+        }
+
+        fn center(&self) -> &Offset {
+            &self.center
+        }
+    }
+
+    #[test]
+    fn nan_hits_are_ignored() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        world.push(NanHit::default());
+        for index in 0..PAR_HIT_THRESHOLD {
+            world.push(Sphere::new(
+                vector![0., 0., -(index as f32) - 2.],
+                0.5,
+                material.clone(),
+            ));
+        }
+
+        let ray = Ray::new(vector![0., 0., 0.], -Vector3::z());
+        let serial = world.hit(ray, 0.001, f32::INFINITY).unwrap();
+        let parallel = world.par_hit(ray, 0.001, f32::INFINITY).unwrap();
+        assert!((serial.t - 1.5).abs() < 1e-5);
+        assert_eq!(serial.t, parallel.t);
+    }
+
+    #[test]
+    fn degenerate_bvh_visits_more_nodes() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        for i in 0..64 {
+            world.push(Sphere::new(
+                vector![i as f32 - 32., 0., -5.],
+                0.4,
+                material.clone(),
+            ));
+        }
+
+        // Chain the spheres so that every node contains all remaining ones.
+        let leaf = |hittable: &HittableArc| Bvh {
+            center: Offset::default(),
+            aabb: hittable.bounding_box(0., 0.).unwrap(),
+            subnode: BvhNode::One(hittable.clone()),
+            depth: 1,
+        };
+        let mut degenerate: Option<Bvh> = None;
+        for hittable in world.hittables.iter().rev() {
+            degenerate = Some(match degenerate.take() {
+                Some(node) => Bvh {
+                    center: Offset::default(),
+                    aabb: Aabb::surrounding(&hittable.bounding_box(0., 0.).unwrap(), &node.aabb),
+                    depth: 1 + node.depth,
+                    subnode: BvhNode::Two(Arc::new(leaf(hittable)), Arc::new(node)),
+                },
+                None => leaf(hittable),
+            });
+        }
+        let degenerate = degenerate.unwrap();
+        let balanced = Bvh::new(world, 0., 0.).unwrap();
+
+        let mut visits_degenerate = 0;
+        let mut visits_balanced = 0;
+        for i in 0..64 {
+            let ray = Ray::new(vector![i as f32 - 32., 0., 0.], vector![0., 0., -1.]);
+            let hit_degenerate =
+                degenerate.hit_counting(ray, 0., f32::INFINITY, &mut visits_degenerate);
+            let hit_balanced = balanced.hit_counting(ray, 0., f32::INFINITY, &mut visits_balanced);
+            // Counting does not change the hits.
+            assert_eq!(
+                hit_degenerate.map(|hit| hit.t),
+                balanced.hit(ray, 0., f32::INFINITY).map(|hit| hit.t)
+            );
+            assert_eq!(
+                hit_balanced.map(|hit| hit.t),
+                balanced.hit(ray, 0., f32::INFINITY).map(|hit| hit.t)
+            );
+        }
+
+        assert!(visits_degenerate > 2 * visits_balanced);
+    }
+
+    #[test]
+    fn aabb_pad() {
+        let aabb = Aabb::new(vector![-1., -1., 0.], vector![1., 1., 0.]).pad(0.1);
+
+        assert_eq!(aabb.minimum(), vector![-1., -1., -0.05]);
+        assert_eq!(aabb.maximum(), vector![1., 1., 0.05]);
+    }
+
+    #[test]
+    fn bvh_equal_minima() {
+        use rand::seq::SliceRandom;
+
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut ys: Vec<usize> = (0..256).collect();
+        ys.shuffle(&mut random::rng());
+
+        // All spheres share their minima along x and z.
+        let mut world = HittableList::default();
+        for y in ys {
+            world.push(Sphere::new(
+                vector![0., y as f32, -5.],
+                0.4,
+                material.clone(),
+            ));
+        }
+        let bvh = Bvh::new(world, 0., 0.).unwrap();
+
+        for y in [0., 100., 255.] {
+            let ray = Ray::new(vector![0., y, 0.], vector![0., 0., -1.]);
+            let mut visits = 0;
+            let hit = bvh.hit_counting(ray, 0., f32::INFINITY, &mut visits);
+            assert!((hit.unwrap().point.y - y).abs() < 1e-4);
+            // A balanced, spatially sorted tree only has to descend along one path.
+            assert!(visits <= 2 * 9);
+        }
+    }
+
+    #[test]
+    fn bvh_identical_boxes() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        for _ in 0..256 {
+            world.push(Sphere::new(vector![0., 0., -5.], 0.4, material.clone()));
+        }
+
+        // 256 `Hittable`s are halved 7 times down to pairs, which share the last node.
+        assert_eq!(Bvh::new(world.clone(), 0., 0.).unwrap().depth(), 8);
+        assert_eq!(Bvh::new_sah(world, 0., 0.).unwrap().depth(), 8);
+    }
+
+    #[test]
+    fn sort_by_nan_box() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        for x in [1., f32::NAN, 0.] {
+            world.push(Sphere::new(vector![x, 0., -5.], 0.4, material.clone()));
+        }
+
+        // The axis is random, so build often enough to sort along each of them.
+        for _ in 0..20 {
+            let bvh = Bvh::new(world.clone(), 0., 0.).unwrap();
+            let ray = Ray::new(vector![0., 0., 0.], vector![0., 0., -1.]);
+            assert!((bvh.hit(ray, 0., f32::INFINITY).unwrap().t - 4.6).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn sah_bvh() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        // Two distant clusters of different size, where a split at the median would cut the large one.
+        for _ in 0..40 {
+            world.push(Sphere::new(
+                random_vector_in_range(-1., 1.) - vector![10., 0., 0.],
+                0.1,
+                material.clone(),
+            ));
+        }
+        for _ in 0..10 {
+            world.push(Sphere::new(
+                random_vector_in_range(-1., 1.) + vector![10., 0., 0.],
+                0.1,
+                material.clone(),
+            ));
+        }
+
+        let bvh = Bvh::new_sah(world.clone(), 0., 0.).unwrap();
+        let BvhNode::Two(left, right) = &bvh.subnode else {
+            panic!("expected two subnodes");
+        };
+        let (left, right) = (
+            left.bounding_box(0., 0.).unwrap(),
+            right.bounding_box(0., 0.).unwrap(),
+        );
+        assert!(left.maximum.x < 0. && right.minimum.x > 0.);
+
+        for _ in 0..200 {
+            let ray = Ray::new(
+                vector![0., 0., 20.],
+                random_vector_in_range(-0.6, 0.6) - Vector3::z(),
+            );
+            let expected = world.hit(ray, 0.001, f32::INFINITY);
+            let actual = bvh.hit(ray, 0.001, f32::INFINITY);
+            assert_eq!(expected.map(|hit| hit.t), actual.map(|hit| hit.t));
+        }
+    }
+
+    #[test]
+    fn sah_bvh_overlapping() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let mut world = HittableList::default();
+        for _ in 0..5 {
+            world.push(Sphere::new(vector![0., 0., -2.], 1., material.clone()));
+        }
+
+        let bvh = Bvh::new_sah(world, 0., 0.).unwrap();
+        let ray = Ray::new(vector![0., 0., 0.], vector![0., 0., -1.]);
+        assert!((bvh.hit(ray, 0., f32::INFINITY).unwrap().t - 1.).abs() < 1e-5);
+        assert_eq!(bvh.aabb.surface_area(), 24.);
+
+        // Two almost coinciding large spheres with a cluster of small ones between them, so that no split beats hitting everything at the top.
+        let mut world = HittableList::default();
+        for x in [-1., 1.] {
+            world.push(Sphere::new(
+                vector![x * 0.6, 0., 0.],
+                500.,
+                material.clone(),
+            ));
+            for i in 0..15 {
+                let offset = 0.3 + i as f32 / 70.;
+                world.push(Sphere::new(
+                    vector![x * offset, 0., 0.],
+                    0.01,
+                    material.clone(),
+                ));
+            }
+        }
+        let bvh = Bvh::new_sah(world, 0., 0.).unwrap();
+
+        // After the median split, each half separates its large sphere from its cluster again, so a ray missing the clusters skips them right away.
+        let ray = Ray::new(vector![-1000., 1., 0.], vector![1., 0., 0.]);
+        let mut visits = 0;
+        let hit = bvh.hit_counting(ray, 0., f32::INFINITY, &mut visits);
+        assert!(hit.is_some());
+        // Falling back to the midpoint split of `Bvh::new` would keep part of the cluster next to the large sphere on every level.
+        assert!(visits <= 5);
+    }
 }
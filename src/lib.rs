@@ -3,17 +3,21 @@
 //! As the coordinate system is arbitrary, it was chosen in this way:
 //!  - y axis points up
 //!  - z axis points towards the camera
+//!
 //! In order to create a ray-traced image, one needs to create a [`Camera`], then a [`Raytracer`] and add [`Hittable`]s to its `world`.
 
 pub mod camera;
 pub mod color;
 pub mod hitrecord;
 pub mod hittable;
+pub mod lut;
 pub mod materials;
 pub mod perlin;
 pub mod ppm;
+pub mod random;
 pub mod ray;
 pub mod raytracer;
+pub mod scenes;
 pub mod shapes;
 pub mod textures;
 #[macro_use]
@@ -21,6 +25,6 @@ pub mod vec3;
 
 pub use camera::Camera;
 pub use color::Color;
-pub use hittable::{Hittable, HittableList};
+pub use hittable::{Bvh, Hittable, HittableList};
 pub use nalgebra::{vector, Vector3};
 pub use raytracer::Raytracer;
@@ -0,0 +1,220 @@
+//! Three-dimensional color lookup tables (`.cube`) for color grading.
+
+use std::path::Path;
+use std::{fs, io};
+
+use crate::*;
+
+/// A three-dimensional color lookup table.
+///
+/// Maps each input [`Color`] to an output [`Color`] by trilinearly interpolating between the entries of a `size`×`size`×`size` grid.
+///
+/// # Fields
+/// - `size`: Number of entries along each axis.
+/// - `domain_min`: Input color mapped to the first entry along each axis.
+/// - `domain_max`: Input color mapped to the last entry along each axis.
+/// - `table`: Flat [vector](Vec) of the entries with red changing fastest, then green, then blue.
+#[derive(Clone, Debug)]
+pub struct Lut3d {
+    size: usize,
+    domain_min: Color,
+    domain_max: Color,
+    table: Vec<Color>,
+}
+
+impl Lut3d {
+    /// Create a [`Lut3d`] from its entries.
+    ///
+    /// Returns [`None`] if `size` is smaller than 2 or `table` does not contain `size`³ entries.
+    pub fn new(size: usize, table: Vec<Color>) -> Option<Self> {
+        if size < 2 || table.len() != size.pow(3) {
+            return None;
+        }
+
+        Some(Self {
+            size,
+            domain_min: color![0., 0., 0.],
+            domain_max: color![1., 1., 1.],
+            table,
+        })
+    }
+
+    /// Create a [`Lut3d`] that maps every color onto itself.
+    pub fn identity(size: usize) -> Self {
+        let size = size.max(2);
+        let scale = (size - 1) as f32;
+        let table = (0..size.pow(3))
+            .map(|index| {
+                color![
+                    (index % size) as f32 / scale,
+                    (index / size % size) as f32 / scale,
+                    (index / (size * size)) as f32 / scale,
+                ]
+            })
+            .collect();
+
+        Self {
+            size,
+            domain_min: color![0., 0., 0.],
+            domain_max: color![1., 1., 1.],
+            table,
+        }
+    }
+
+    /// Load a [`Lut3d`] from an Adobe/Resolve `.cube` file.
+    ///
+    /// Only 3D tables are supported (`LUT_3D_SIZE`). `TITLE` lines, comments and other keywords (e.g. `LUT_3D_INPUT_RANGE`) are ignored.
+    pub fn from_cube<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Lut3d::parse_cube(&content)
+    }
+
+    fn parse_cube(content: &str) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let parse_color = |values: &[&str]| -> io::Result<Color> {
+            if values.len() != 3 {
+                return Err(invalid("expected three values per color"));
+            }
+            let mut channels = [0.; 3];
+            for (channel, value) in channels.iter_mut().zip(values) {
+                *channel = value
+                    .parse()
+                    .map_err(|_| invalid("invalid number in LUT"))?;
+            }
+            Ok(color![channels[0], channels[1], channels[2]])
+        };
+
+        let mut size = None;
+        let mut domain_min = color![0., 0., 0.];
+        let mut domain_max = color![1., 1., 1.];
+        let mut table = Vec::new();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens[0] {
+                "LUT_3D_SIZE" => {
+                    size = Some(
+                        tokens
+                            .get(1)
+                            .and_then(|size| size.parse::<usize>().ok())
+                            .ok_or_else(|| invalid("invalid LUT_3D_SIZE"))?,
+                    );
+                }
+                "LUT_1D_SIZE" => return Err(invalid("1D LUTs are not supported")),
+                "DOMAIN_MIN" => domain_min = parse_color(&tokens[1..])?,
+                "DOMAIN_MAX" => domain_max = parse_color(&tokens[1..])?,
+                // Only lines starting with a number are entries of the table.
+                token if token.parse::<f32>().is_ok() => table.push(parse_color(&tokens)?),
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or_else(|| invalid("missing LUT_3D_SIZE"))?;
+        let mut lut =
+            Lut3d::new(size, table).ok_or_else(|| invalid("LUT size does not match entries"))?;
+        lut.domain_min = domain_min;
+        lut.domain_max = domain_max;
+
+        Ok(lut)
+    }
+
+    /// Number of entries along each axis.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Map a [`Color`] through the table using trilinear interpolation.
+    pub fn apply(&self, color: Color) -> Color {
+        let scale = (self.size - 1) as f32;
+        let mut base = [0; 3];
+        let mut fraction = [0.; 3];
+        for axis in 0..3 {
+            // A collapsed domain maps every input of that axis onto the first entry.
+            let width = self.domain_max[axis] - self.domain_min[axis];
+            let position = if width > 0. {
+                ((color[axis] - self.domain_min[axis]) / width).clamp(0., 1.) * scale
+            } else {
+                0.
+            };
+            base[axis as usize] = (position.floor() as usize).min(self.size - 2);
+            fraction[axis as usize] = position - base[axis as usize] as f32;
+        }
+
+        let mut result = color![0., 0., 0.];
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let weight = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1 {
+                        fraction[axis]
+                    } else {
+                        1. - fraction[axis]
+                    }
+                })
+                .product::<f32>();
+            result += weight
+                * self.entry(
+                    base[0] + offset[0],
+                    base[1] + offset[1],
+                    base[2] + offset[2],
+                );
+        }
+
+        result
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> Color {
+        self.table[r + self.size * (g + self.size * b)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cube() {
+        let cube = "TITLE \"invert\"\n# comment\nLUT_3D_SIZE 2\n\
+            1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut3d::parse_cube(cube).unwrap();
+        assert_eq!(lut.size(), 2);
+
+        let inverted = lut.apply(color![0.25, 0.5, 1.]);
+        assert!((inverted - color![0.75, 0.5, 0.])
+            .into_iter()
+            .all(|c| c.abs() < 1e-5));
+
+        assert!(Lut3d::parse_cube("LUT_3D_SIZE 3\n0 0 0\n").is_err());
+        assert!(Lut3d::parse_cube("LUT_3D_SIZE 2\n0 0 x\n").is_err());
+    }
+
+    #[test]
+    fn unknown_keywords() {
+        // As exported by DaVinci Resolve.
+        let cube = "TITLE \"invert\"\nLUT_3D_SIZE 2\nLUT_3D_INPUT_RANGE 0.0 1.0\n\
+            1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut3d::parse_cube(cube).unwrap();
+
+        let inverted = lut.apply(color![0.25, 0.5, 1.]);
+        assert!((inverted - color![0.75, 0.5, 0.])
+            .into_iter()
+            .all(|c| c.abs() < 1e-5));
+    }
+
+    #[test]
+    fn collapsed_domain() {
+        let cube = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0.5\nDOMAIN_MAX 1 1 0.5\n\
+            1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let lut = Lut3d::parse_cube(cube).unwrap();
+
+        let inverted = lut.apply(color![0.25, 0.5, 0.7]);
+        assert!((inverted - color![0.75, 0.5, 1.])
+            .into_iter()
+            .all(|c| c.abs() < 1e-5));
+    }
+}
@@ -1,10 +1,11 @@
 //! Collection of materials of [`Hittable`]s.
 
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use rand::Rng;
 
-use crate::color::BLACK;
+use crate::color::{BLACK, WHITE};
 use crate::hitrecord::HitRecord;
 use crate::ray::Ray;
 use crate::textures::{SolidColor, Texture};
@@ -18,8 +19,29 @@ pub trait Material: Debug + Send + Sync {
     /// Scatters the incoming [`Ray`] into an outgoing [`Ray`] and includes [`Color`] information.
     fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)>;
 
-    /// Checks if and what color light is emitted at a certain point.
-    fn emit(&self, u: f32, v: f32, point: Vector3<f32>) -> Color;
+    /// Checks if and what color light is emitted where the [`Ray`] hit.
+    fn emit(&self, hit: &HitRecord) -> Color;
+
+    /// Whether [`scatter`](Material::scatter) reflects or refracts specularly instead of diffusely.
+    ///
+    /// This allows separate bounce budgets (see [`Raytracer::with_depth_limits`]).
+    fn is_specular(&self) -> bool {
+        false
+    }
+}
+
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        self.as_ref().scatter(ray, hit)
+    }
+
+    fn emit(&self, hit: &HitRecord) -> Color {
+        self.as_ref().emit(hit)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.as_ref().is_specular()
+    }
 }
 
 /// A realistic perfectly diffusive material.
@@ -44,9 +66,16 @@ impl Lambertian<SolidColor> {
     }
 }
 
+impl Default for Lambertian<SolidColor> {
+    /// A gray [`Lambertian`].
+    fn default() -> Self {
+        Self::solid_color(color![0.5, 0.5, 0.5])
+    }
+}
+
 impl<T: Texture> Material for Lambertian<T> {
     fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
-        let mut scatter_direction = hit.normal + random_unit_vector_in_unit_sphere();
+        let mut scatter_direction = hit.normal + random_unit_vector(&mut random::rng());
 
         if near_zero(&scatter_direction) {
             scatter_direction = hit.normal;
@@ -56,64 +85,364 @@ impl<T: Texture> Material for Lambertian<T> {
         Some((scattered, self.albedo.color_at(hit.u, hit.v, hit.point)))
     }
 
-    fn emit(&self, _u: f32, _v: f32, _hit_point: Vector3<f32>) -> Color {
+    fn emit(&self, _hit: &HitRecord) -> Color {
+        BLACK
+    }
+}
+
+/// A rough diffuse material following the Oren-Nayar reflectance model.
+///
+/// Unlike [`Lambertian`], rough surfaces like clay, concrete or the moon scatter more light back towards the viewer.
+///
+/// # Fields
+/// - `albedo`: Color of the [`OrenNayar`].
+/// - `sigma`: Standard deviation of the facet angles in radians. With 0 the material is [`Lambertian`].
+#[derive(Clone, Debug)]
+pub struct OrenNayar<T: Texture> {
+    albedo: T,
+    sigma: f32,
+}
+
+impl<T: Texture> OrenNayar<T> {
+    pub fn new(albedo: T, sigma: f32) -> Self {
+        Self {
+            albedo,
+            sigma: sigma.max(0.),
+        }
+    }
+
+    /// The Oren-Nayar factor relative to a [`Lambertian`] for the unit vectors `incoming` (pointing away from the surface) and `outgoing`.
+    fn reflectance(
+        &self,
+        normal: &Vector3<f32>,
+        incoming: &Vector3<f32>,
+        outgoing: &Vector3<f32>,
+    ) -> f32 {
+        let sigma2 = self.sigma.powi(2);
+        let a = 1. - 0.5 * sigma2 / (sigma2 + 0.33);
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        let cos_in = incoming.dot(normal).clamp(0., 1.);
+        let cos_out = outgoing.dot(normal).clamp(0., 1.);
+        let sin_in = (1. - cos_in.powi(2)).sqrt();
+        let sin_out = (1. - cos_out.powi(2)).sqrt();
+
+        // cos(phi_in - phi_out) from the projections onto the tangent plane.
+        let cos_phi = if sin_in > 1e-4 && sin_out > 1e-4 {
+            let tangent_in = incoming - cos_in * normal;
+            let tangent_out = outgoing - cos_out * normal;
+            (tangent_in.dot(&tangent_out) / (sin_in * sin_out)).max(0.)
+        } else {
+            0.
+        };
+
+        // sin(alpha) * tan(beta) with alpha the larger and beta the smaller polar angle.
+        let (sin_alpha, tan_beta) = if cos_in < cos_out {
+            (sin_in, sin_out / cos_out.max(1e-4))
+        } else {
+            (sin_out, sin_in / cos_in.max(1e-4))
+        };
+
+        a + b * cos_phi * sin_alpha * tan_beta
+    }
+}
+
+impl OrenNayar<SolidColor> {
+    pub fn solid_color(albedo: Color, sigma: f32) -> Self {
+        Self::new(SolidColor::new(albedo), sigma)
+    }
+}
+
+impl<T: Texture> Material for OrenNayar<T> {
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        let mut scatter_direction = hit.normal + random_unit_vector(&mut random::rng());
+
+        if near_zero(&scatter_direction) {
+            scatter_direction = hit.normal;
+        }
+
+        let factor = self.reflectance(
+            &hit.normal,
+            &-ray.direction().normalize(),
+            &scatter_direction.normalize(),
+        );
+        let scattered = Ray::new(hit.point, scatter_direction).with_time(ray.time());
+        Some((
+            scattered,
+            factor * self.albedo.color_at(hit.u, hit.v, hit.point),
+        ))
+    }
+
+    fn emit(&self, _hit: &HitRecord) -> Color {
         BLACK
     }
 }
 
 /// A fuzzy reflective material (metal).
+///
+/// # Fields
+/// - `albedo`: Color of the [`Metal`] at normal incidence.
+/// - `fuzz`: How much the reflected [`Ray`] is perturbed (between 0 and 1).
+/// - `fresnel`: Whether the reflectance should increase towards grazing angles (Fresnel-Schlick with `albedo` as F0).
+/// - `anisotropy`: How much more the reflected [`Ray`] is perturbed along the tangent than along the bitangent of the surface (between -1 and 1).
 #[derive(Clone, Debug)]
 pub struct Metal<T: Texture> {
     albedo: T,
     fuzz: f32,
+    fresnel: bool,
+    anisotropy: f32,
 }
 
 impl<T: Texture> Metal<T> {
     pub fn new(albedo: T, fuzz: f32) -> Self {
         let fuzz = if fuzz < 1. { fuzz } else { 1. };
-        Self { albedo, fuzz }
+        Self {
+            albedo,
+            fuzz,
+            fresnel: true,
+            anisotropy: 0.,
+        }
+    }
+
+    /// Create a [`Metal`] from a perceptual roughness in \[0,1\] as used in common PBR authoring tools.
+    ///
+    /// The roughness is squared (the GGX alpha convention) to get the `fuzz`, so that it changes perceptually linearly. Use [`new`](Metal::new) to set the `fuzz` directly.
+    pub fn from_roughness(albedo: T, perceptual_roughness: f32) -> Self {
+        Self::new(albedo, perceptual_roughness.clamp(0., 1.).powi(2))
+    }
+
+    /// Consume `self` and enable or disable the Fresnel weighting of the reflectance.
+    pub fn with_fresnel(mut self, fresnel: bool) -> Self {
+        self.fresnel = fresnel;
+        self
+    }
+
+    /// Consume `self` and stretch the perturbation of the reflected [`Ray`] along the tangent of the surface, e.g. for brushed metal.
+    ///
+    /// The deviation of each sample from the mirror direction is scaled by `1 + anisotropy` along the tangent and by `1 - anisotropy` along the bitangent, where `anisotropy` is clamped to \[-1,1\].
+    /// An `anisotropy` of 0 therefore gives the same lobe as without a tangent.
+    /// This only has an effect on shapes providing a tangent (see [`HitRecord::tangent`]), others are reflected isotropically.
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy.clamp(-1., 1.);
+        self
+    }
+
+    /// Schlick's approximation of the Fresnel reflectance with the color `f0` at normal incidence.
+    fn reflectance(f0: Color, cos: f32) -> Color {
+        f0 + (WHITE - f0) * (1. - cos.clamp(0., 1.)).powi(5)
     }
 }
 
 impl Metal<SolidColor> {
     pub fn solid_color(albedo: Color, fuzz: f32) -> Self {
-        let albedo = SolidColor::new(albedo);
-        Self { albedo, fuzz }
+        Self::new(SolidColor::new(albedo), fuzz)
+    }
+}
+
+impl Default for Metal<SolidColor> {
+    /// A gray, perfectly reflecting [`Metal`].
+    fn default() -> Self {
+        Self::solid_color(color![0.5, 0.5, 0.5], 0.)
     }
 }
 
 impl<T: Texture> Material for Metal<T> {
+    /// Reflects the [`Ray`] into a Phong lobe around the mirror direction.
+    ///
+    /// The exponent of the lobe is derived from `fuzz` via `2 / fuzz² - 2`, so `fuzz = 0` is a perfect mirror.
+    /// Samples below the surface are mirrored back above it so that no energy is lost.
     fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
         let reflected = reflect(&ray.direction().normalize(), &hit.normal);
-        let scattered = Ray::new(
-            hit.point,
-            reflected + self.fuzz * random_vector_in_unit_sphere(),
-        )
-        .with_time(ray.time());
-        if scattered.direction().dot(&hit.normal) > 0. {
-            return Some((scattered, self.albedo.color_at(hit.u, hit.v, hit.point)));
+        let tangent = hit.tangent.and_then(|tangent| {
+            (tangent - tangent.dot(&hit.normal) * hit.normal).try_normalize(1e-6)
+        });
+        let mut direction = if self.fuzz > 0. {
+            let exponent = 2. / self.fuzz.powi(2) - 2.;
+            let sample = random_vector_in_phong_lobe(&reflected, exponent);
+            match tangent {
+                Some(tangent) => {
+                    let bitangent = hit.normal.cross(&tangent);
+                    let offset = sample - reflected;
+                    reflected
+                        + (1. + self.anisotropy) * offset.dot(&tangent) * tangent
+                        + (1. - self.anisotropy) * offset.dot(&bitangent) * bitangent
+                        + offset.dot(&hit.normal) * hit.normal
+                }
+                None => sample,
+            }
+        } else {
+            reflected
+        };
+        if direction.dot(&hit.normal) < 0. {
+            direction = reflect(&direction, &hit.normal);
         }
-        None
+
+        let scattered = Ray::new(hit.point, direction).with_time(ray.time());
+        let albedo = self.albedo.color_at(hit.u, hit.v, hit.point);
+        let attenuation = if self.fresnel {
+            let cos_theta = -ray.direction().normalize().dot(&hit.normal);
+            Metal::<T>::reflectance(albedo, cos_theta)
+        } else {
+            albedo
+        };
+        Some((scattered, attenuation))
     }
 
-    fn emit(&self, _u: f32, _v: f32, _hit_point: Vector3<f32>) -> Color {
+    fn emit(&self, _hit: &HitRecord) -> Color {
         BLACK
     }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+/// Smallest GGX alpha used by [`Microfacet`], avoiding the singularity of a perfect mirror.
+const MIN_GGX_ALPHA: f32 = 1e-3;
+
+/// Largest roughness for which a metallic [`Microfacet`] still counts as specular (see [`Material::is_specular`]).
+const MAX_SPECULAR_ROUGHNESS: f32 = 0.2;
+
+/// A physically based material with a GGX (Trowbridge-Reitz) microfacet specular lobe and a Lambertian diffuse lobe.
+///
+/// `scatter` picks one of the lobes at random and importance-samples it, so the returned [`Color`] already contains the BRDF, the cosine term, and the division by the probability density.
+///
+/// # Fields
+/// - `albedo`: Base color, i.e. the diffuse color of dielectrics and the specular color (F0) of metals.
+/// - `roughness`: Perceptual roughness in \[0,1\]. The GGX alpha is its square.
+/// - `metallic`: Blend between a dielectric (0) and a metal (1).
+#[derive(Clone, Debug)]
+pub struct Microfacet<T: Texture> {
+    albedo: T,
+    roughness: f32,
+    metallic: f32,
+}
+
+impl<T: Texture> Microfacet<T> {
+    pub fn new(albedo: T, roughness: f32, metallic: f32) -> Self {
+        Self {
+            albedo,
+            roughness: roughness.clamp(0., 1.),
+            metallic: metallic.clamp(0., 1.),
+        }
+    }
+
+    /// Smith's masking function for GGX in the direction with cosine `cos` to the normal.
+    fn smith_g1(cos: f32, alpha: f32) -> f32 {
+        let alpha2 = alpha.powi(2);
+        2. * cos / (cos + (alpha2 + (1. - alpha2) * cos.powi(2)).sqrt())
+    }
+}
+
+impl Microfacet<SolidColor> {
+    pub fn solid_color(albedo: Color, roughness: f32, metallic: f32) -> Self {
+        Self::new(SolidColor::new(albedo), roughness, metallic)
+    }
+}
+
+impl<T: Texture> Material for Microfacet<T> {
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        let mut rng = random::rng();
+
+        let albedo = self.albedo.color_at(hit.u, hit.v, hit.point);
+        let view = -ray.direction().normalize();
+        let cos_view = view.dot(&hit.normal);
+        if cos_view <= 0. {
+            return None;
+        }
+
+        // Dielectrics reflect about 4 % at normal incidence.
+        let f0 = 0.04 * (1. - self.metallic) * WHITE + self.metallic * albedo;
+        // Metals have no diffuse lobe, so they always sample the specular one.
+        let specular_probability = (1. + self.metallic) / 2.;
+
+        if rng.gen::<f32>() < specular_probability {
+            let alpha = self.roughness.powi(2).max(MIN_GGX_ALPHA);
+            let half = random_vector_in_ggx_lobe(&hit.normal, alpha);
+            let direction = reflect(&-view, &half);
+            let cos_light = direction.dot(&hit.normal);
+            if cos_light <= 0. {
+                return None;
+            }
+
+            let cos_half = half.dot(&hit.normal);
+            let view_dot_half = view.dot(&half);
+            let fresnel = Metal::<T>::reflectance(f0, view_dot_half);
+            let masking = Microfacet::<T>::smith_g1(cos_view, alpha)
+                * Microfacet::<T>::smith_g1(cos_light, alpha);
+            // BRDF * cos / pdf, where the normal distribution cancels out.
+            let weight = masking * view_dot_half / (cos_view * cos_half);
+
+            let scattered = Ray::new(hit.point, direction).with_time(ray.time());
+            Some((scattered, fresnel * weight / specular_probability))
+        } else {
+            let mut direction = hit.normal + random_unit_vector(&mut rng);
+            if near_zero(&direction) {
+                direction = hit.normal;
+            }
+
+            // Light reflected specularly does not enter the surface.
+            let transmitted = WHITE - Metal::<T>::reflectance(f0, cos_view);
+            let scattered = Ray::new(hit.point, direction).with_time(ray.time());
+            Some((
+                scattered,
+                (1. - self.metallic) * albedo * transmitted / (1. - specular_probability),
+            ))
+        }
+    }
+
+    fn emit(&self, _hit: &HitRecord) -> Color {
+        BLACK
+    }
+
+    /// Mostly metallic, smooth [`Microfacet`]s count as specular. Rough metals scatter as broadly as diffuse surfaces.
+    fn is_specular(&self) -> bool {
+        self.metallic > 0.5 && self.roughness <= MAX_SPECULAR_ROUGHNESS
+    }
 }
 
 /// A transparent material.
+///
+/// # Fields
+/// - `index_of_refraction`: Its index of refraction.
+/// - `absorption`: Optional absorption coefficient per unit length for each channel, tinting light travelling through the medium (Beer–Lambert law).
+/// - `roughness`: Random perturbation of the reflected and refracted directions for frosted glass (in \[0,1\]).
 #[derive(Clone, Debug)]
 pub struct Dielectric {
     index_of_refraction: f32,
+    absorption: Option<Color>,
+    roughness: f32,
 }
 
 impl Dielectric {
+    /// Create a clear [`Dielectric`].
     pub fn new(index_of_refraction: f32) -> Self {
         Self {
             index_of_refraction,
+            absorption: None,
+            roughness: 0.,
         }
     }
 
+    /// Create a tinted [`Dielectric`] absorbing `absorption` per unit length inside.
+    ///
+    /// The absorption is applied when a [`Ray`] leaves the medium (hits a back face), so objects made of it should be closed.
+    pub fn colored(index_of_refraction: f32, absorption: Color) -> Self {
+        Self {
+            index_of_refraction,
+            absorption: Some(absorption),
+            roughness: 0.,
+        }
+    }
+
+    /// Consume `self` and perturb the scattered directions like the `fuzz` of [`Metal`], e.g. for frosted glass.
+    ///
+    /// `roughness` is clamped to \[0,1\], 0 being perfectly clear.
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.roughness = roughness.clamp(0., 1.);
+        self
+    }
+
     fn reflectance(cos: f32, refraction_ratio: f32) -> f32 {
         let mut r0 = (1. - refraction_ratio) / (1. + refraction_ratio);
         r0 *= r0;
@@ -121,9 +450,16 @@ impl Dielectric {
     }
 }
 
+impl Default for Dielectric {
+    /// A [`Dielectric`] with the index of refraction of glass (1.5).
+    fn default() -> Self {
+        Self::new(1.5)
+    }
+}
+
 impl Material for Dielectric {
     fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let refraction_ratio = if hit.front_face {
             1. / self.index_of_refraction
@@ -136,40 +472,135 @@ impl Material for Dielectric {
         let sin_theta = (1. - cos_theta.powi(2)).sqrt();
 
         let cannot_refrect = refraction_ratio * sin_theta > 1.;
-        let direction =
+        let mut direction =
             if cannot_refrect || Dielectric::reflectance(cos_theta, refraction_ratio) > rng.gen() {
                 reflect(&unit_direction, &hit.normal)
             } else {
                 refract(&unit_direction, &hit.normal, refraction_ratio)
             };
+        if self.roughness > 0. {
+            direction += self.roughness * random_vector_in_unit_sphere();
+        }
+
+        let attenuation = match self.absorption {
+            Some(absorption) if !hit.front_face => {
+                let distance = hit.t * ray.direction().norm();
+                absorption
+                    .into_iter()
+                    .map(|absorption| (-absorption * distance).exp())
+                    .collect()
+            }
+            _ => color![1., 1., 1.],
+        };
+
+        let scattered = Ray::new(hit.point, direction).with_time(ray.time());
+        Some((scattered, attenuation))
+    }
+
+    fn emit(&self, _hit: &HitRecord) -> Color {
+        BLACK
+    }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+/// A transparent material without thickness (e.g. a window pane modeled as a single [`Rectangle`](crate::shapes::Rectangle)).
+///
+/// Rays are either reflected or transmitted according to the Fresnel reflectance. As there is no volume, transmitted rays pass straight through without refraction.
+///
+/// # Fields
+/// - `index_of_refraction`: Index of refraction of the glass, determining the reflectance.
+/// - `tint`: Color of the transmitted light.
+#[derive(Clone, Debug)]
+pub struct ThinGlass {
+    index_of_refraction: f32,
+    tint: Color,
+}
+
+impl ThinGlass {
+    pub fn new(index_of_refraction: f32, tint: Color) -> Self {
+        Self {
+            index_of_refraction,
+            tint,
+        }
+    }
+}
+
+impl Material for ThinGlass {
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        let unit_direction = ray.direction().normalize();
+        let cos_theta = f32::min(-unit_direction.dot(&hit.normal), 1.);
+
+        // Both faces are interfaces from air into glass.
+        let reflectance = Dielectric::reflectance(cos_theta, 1. / self.index_of_refraction);
+        let (direction, attenuation) = if reflectance > random::rng().gen() {
+            (reflect(&unit_direction, &hit.normal), WHITE)
+        } else {
+            (unit_direction, self.tint)
+        };
 
         let scattered = Ray::new(hit.point, direction).with_time(ray.time());
-        Some((scattered, color![1., 1., 1.]))
+        Some((scattered, attenuation))
     }
 
-    fn emit(&self, _u: f32, _v: f32, _hit_point: Vector3<f32>) -> Color {
+    fn emit(&self, _hit: &HitRecord) -> Color {
         BLACK
     }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
 }
 
 /// A diffusive light-emitting material.
 ///
-/// Light emission does not work correctly yet. //TODO
+/// # Fields
+/// - `texture`: Color of the emitted light.
+/// - `one_sided`: Whether light is only emitted from the front face (the side the normal points to). This is the default.
+/// - `intensity`: Multiplier of the emitted light (1 by default).
+/// - `falloff`: Whether the emitted light decreases with the inverse square of the distance to the hit (off by default).
 #[derive(Clone, Debug)]
 pub struct DiffuseLight<T: Texture> {
     texture: T,
+    one_sided: bool,
+    intensity: f32,
+    falloff: bool,
 }
 
 impl<T: Texture> DiffuseLight<T> {
     pub fn new(texture: T) -> Self {
-        Self { texture }
+        Self {
+            texture,
+            one_sided: true,
+            intensity: 1.,
+            falloff: false,
+        }
+    }
+
+    /// Consume `self` and scale the emitted light by `intensity`.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity.max(0.);
+        self
+    }
+
+    /// Consume `self` and set whether the emitted light falls off with the inverse square of the distance to the hit (see [`HitRecord::distance`]).
+    pub fn with_falloff(mut self, falloff: bool) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// Consume `self` and emit light from both faces.
+    pub fn with_two_sided(mut self) -> Self {
+        self.one_sided = false;
+        self
     }
 }
 
 impl DiffuseLight<SolidColor> {
     pub fn solid_color(color: Color) -> Self {
-        let texture = SolidColor::new(color);
-        Self { texture }
+        Self::new(SolidColor::new(color))
     }
 }
 
@@ -178,8 +609,16 @@ impl<T: Texture> Material for DiffuseLight<T> {
         None
     }
 
-    fn emit(&self, u: f32, v: f32, hit_point: Vector3<f32>) -> Color {
-        self.texture.color_at(u, v, hit_point)
+    fn emit(&self, hit: &HitRecord) -> Color {
+        if self.one_sided && !hit.front_face {
+            return BLACK;
+        }
+
+        let mut intensity = self.intensity;
+        if self.falloff {
+            intensity /= hit.distance.powi(2).max(f32::EPSILON);
+        }
+        intensity * self.texture.color_at(hit.u, hit.v, hit.point)
     }
 }
 
@@ -204,12 +643,634 @@ impl Isotropic<SolidColor> {
 
 impl<T: Texture> Material for Isotropic<T> {
     fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
-        let scattered = Ray::new(hit.point, random_vector_in_unit_sphere()).with_time(ray.time());
+        let scattered =
+            Ray::new(hit.point, random_unit_vector(&mut random::rng())).with_time(ray.time());
         let attenuation = self.albedo.color_at(hit.u, hit.v, hit.point);
         Some((scattered, attenuation))
     }
 
-    fn emit(&self, _u: f32, _v: f32, _point: Vector3<f32>) -> Color {
+    fn emit(&self, _hit: &HitRecord) -> Color {
+        BLACK
+    }
+}
+
+/// A retroreflective material (road signs, cat's-eyes).
+///
+/// Instead of reflecting about the normal, light is sent back towards where it came from, so it appears bright to a viewer next to the light source.
+///
+/// # Fields
+/// - `albedo`: Color of the [`Retroreflective`].
+/// - `spread`: How much the scattered [`Ray`] deviates from the incoming direction (between 0 and 1).
+#[derive(Clone, Debug)]
+pub struct Retroreflective<T: Texture> {
+    albedo: T,
+    spread: f32,
+}
+
+impl<T: Texture> Retroreflective<T> {
+    pub fn new(albedo: T, spread: f32) -> Self {
+        Self {
+            albedo,
+            spread: spread.clamp(0., 1.),
+        }
+    }
+}
+
+impl Retroreflective<SolidColor> {
+    pub fn solid_color(albedo: Color, spread: f32) -> Self {
+        Self::new(SolidColor::new(albedo), spread)
+    }
+}
+
+impl<T: Texture> Material for Retroreflective<T> {
+    /// Scatters the [`Ray`] into a Phong lobe around the reversed incoming direction.
+    ///
+    /// The lobe is the same as for [`Metal`] with `spread` as `fuzz`.
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        let reversed = -ray.direction().normalize();
+        let mut direction = if self.spread > 0. {
+            let exponent = 2. / self.spread.powi(2) - 2.;
+            random_vector_in_phong_lobe(&reversed, exponent)
+        } else {
+            reversed
+        };
+        if direction.dot(&hit.normal) < 0. {
+            direction = reflect(&direction, &hit.normal);
+        }
+
+        let scattered = Ray::new(hit.point, direction).with_time(ray.time());
+        Some((scattered, self.albedo.color_at(hit.u, hit.v, hit.point)))
+    }
+
+    fn emit(&self, _hit: &HitRecord) -> Color {
         BLACK
     }
+
+    fn is_specular(&self) -> bool {
+        true
+    }
+}
+
+/// A cloth-like material (velvet, satin) with a sheen at grazing angles on top of a diffuse base.
+///
+/// The fibers scatter light back towards the viewer at grazing angles, which brightens the silhouette edges when lit from the front.
+///
+/// # Fields
+/// - `color`: Color of both the diffuse base and the sheen.
+/// - `intensity`: Strength of the sheen (between 0 and 1).
+#[derive(Clone, Debug)]
+pub struct Sheen<T: Texture> {
+    color: T,
+    intensity: f32,
+}
+
+/// Exponent of the Phong lobe around the reversed incoming direction used for the sheen of a [`Sheen`].
+const SHEEN_LOBE_EXPONENT: f32 = 20.;
+
+/// Roughness of the fibers in the Charlie distribution giving the sheen of a [`Sheen`] its falloff.
+const SHEEN_ROUGHNESS: f32 = 0.3;
+
+impl<T: Texture> Sheen<T> {
+    pub fn new(color: T, intensity: f32) -> Self {
+        Self {
+            color,
+            intensity: intensity.clamp(0., 1.),
+        }
+    }
+
+    /// Reflectance of the sheen relative to `color` when viewed at an angle with cosine `cos` to the normal.
+    ///
+    /// This is the Charlie distribution sin(θ)^(1/α) of fibers with roughness α, normalized to `intensity` at grazing angles.
+    fn sheen_factor(&self, cos: f32) -> f32 {
+        let sin_squared = 1. - cos.clamp(0., 1.).powi(2);
+        self.intensity * sin_squared.powf(0.5 / SHEEN_ROUGHNESS)
+    }
+}
+
+impl Sheen<SolidColor> {
+    pub fn solid_color(color: Color, intensity: f32) -> Self {
+        Self::new(SolidColor::new(color), intensity)
+    }
+}
+
+impl<T: Texture> Material for Sheen<T> {
+    /// Scatters either retroreflectively (see [`Retroreflective`]) or diffusely (see [`Lambertian`]).
+    ///
+    /// The sheen lobe reflects `color` weighted by the Charlie distribution of the fibers, the diffuse base the remaining `1 - sheen` of it.
+    /// Each lobe is chosen with the probability of its weight, so the attenuation is always `color` and the material never reflects more light than it receives.
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        let mut rng = random::rng();
+        let view = -ray.direction().normalize();
+        let color = self.color.color_at(hit.u, hit.v, hit.point);
+        let sheen = self.sheen_factor(view.dot(&hit.normal));
+        let mut direction = if rng.gen::<f32>() < sheen {
+            random_vector_in_phong_lobe(&view, SHEEN_LOBE_EXPONENT)
+        } else {
+            hit.normal + random_unit_vector(&mut rng)
+        };
+        if near_zero(&direction) {
+            direction = hit.normal;
+        }
+        if direction.dot(&hit.normal) < 0. {
+            direction = reflect(&direction, &hit.normal);
+        }
+
+        let scattered = Ray::new(hit.point, direction).with_time(ray.time());
+        Some((scattered, color))
+    }
+
+    fn emit(&self, _hit: &HitRecord) -> Color {
+        BLACK
+    }
+}
+
+/// A wrapper perturbing the shading normal of another [`Material`] with a tangent-space normal map.
+///
+/// The RGB color of the map at (u, v) is decoded into a vector in \[-1,1\]³, whose components are the coordinates along the tangent, the bitangent and the normal of the surface.
+/// A color of (128, 128, 255) therefore leaves the normal unchanged. Shapes not providing a tangent (see [`HitRecord`]) are shaded with their unperturbed normal.
+///
+/// # Fields
+/// - `material`: The wrapped [`Material`], which scatters with the perturbed normal.
+/// - `normal_map`: The tangent-space normal map, usually an [`ImageTexture`](crate::textures::ImageTexture).
+#[derive(Clone, Debug)]
+pub struct NormalMap<M: Material, T: Texture> {
+    material: M,
+    normal_map: T,
+}
+
+impl<M: Material, T: Texture> NormalMap<M, T> {
+    pub fn new(material: M, normal_map: T) -> Self {
+        Self {
+            material,
+            normal_map,
+        }
+    }
+
+    /// Replace the normal (and tangent) of `hit` by the one from the normal map.
+    fn perturb<'a>(&self, mut hit: HitRecord<'a>) -> HitRecord<'a> {
+        let Some(tangent) = hit.tangent else {
+            return hit;
+        };
+
+        // Decode so that 128 maps to exactly 0 and 255 to 1.
+        let texel = self.normal_map.color_at(hit.u, hit.v, hit.point);
+        let decode = |channel: f32| ((channel * 255. - 128.) / 127.).clamp(-1., 1.);
+        let (x, y, z) = (decode(texel.r()), decode(texel.g()), decode(texel.b()));
+
+        // Gram-Schmidt in case the tangent is not exactly perpendicular to the normal.
+        let tangent = tangent - tangent.dot(&hit.normal) * hit.normal;
+        if near_zero(&tangent) {
+            return hit;
+        }
+        let tangent = tangent.normalize();
+        let bitangent = hit.normal.cross(&tangent);
+
+        let normal = x * tangent + y * bitangent + z * hit.normal;
+        if near_zero(&normal) {
+            return hit;
+        }
+        let normal = normal.normalize();
+
+        hit.tangent = Some((tangent - tangent.dot(&normal) * normal).normalize());
+        hit.normal = normal;
+        hit
+    }
+}
+
+impl<M: Material, T: Texture> Material for NormalMap<M, T> {
+    fn scatter(&self, ray: Ray, hit: HitRecord) -> Option<(Ray, Color)> {
+        self.material.scatter(ray, self.perturb(hit))
+    }
+
+    fn emit(&self, hit: &HitRecord) -> Color {
+        self.material.emit(hit)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.material.is_specular()
+    }
+}
+
+/// A debug material showing the surface coordinates (u, v) as red and green.
+///
+/// It does not scatter, so rendering a shape with it shows its UV parametrization directly.
+#[derive(Clone, Debug, Default)]
+pub struct UvDebugMaterial;
+
+impl UvDebugMaterial {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Material for UvDebugMaterial {
+    fn scatter(&self, _ray: Ray, _hit: HitRecord) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn emit(&self, hit: &HitRecord) -> Color {
+        Color::new(hit.u, hit.v, 0.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+    use crate::shapes::Sphere;
+
+    #[test]
+    fn metal_fresnel_grazing() {
+        let metal = Metal::solid_color(color![0.5, 0.5, 0.5], 0.);
+        let normal = vector![0., 1., 0.];
+
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &metal);
+        let ray_normal = Ray::new(vector![0., 1., 0.], vector![0., -1., 0.]);
+        let (_, attenuation_normal) = metal.scatter(ray_normal, hit.clone()).unwrap();
+
+        let ray_grazing = Ray::new(vector![-1., 0.05, 0.], vector![1., -0.05, 0.]);
+        let (_, attenuation_grazing) = metal.scatter(ray_grazing, hit.clone()).unwrap();
+
+        assert!(attenuation_grazing.r() > attenuation_normal.r());
+        assert!((attenuation_normal.r() - 0.5).abs() < 1e-4);
+
+        let metal = metal.with_fresnel(false);
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &metal);
+        let (_, attenuation_grazing) = metal.scatter(ray_grazing, hit).unwrap();
+        assert_eq!(attenuation_grazing, color![0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn metal_from_roughness_zero_is_mirror() {
+        let metal = Metal::from_roughness(SolidColor::new(color![0.8, 0.8, 0.8]), 0.);
+        let normal = vector![0., 1., 0.];
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &metal);
+        let ray = Ray::new(vector![-1., 1., 0.], vector![1., -1., 0.]);
+
+        for _ in 0..100 {
+            let (scattered, _) = metal.scatter(ray, hit.clone()).unwrap();
+            assert!((scattered.direction() - vector![1., 1., 0.].normalize()).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn metal_no_wasted_samples() {
+        let metal = Metal::solid_color(color![0.8, 0.8, 0.8], 1.);
+        let normal = vector![0., 1., 0.];
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &metal);
+        let ray = Ray::new(vector![-1., 0.2, 0.], vector![1., -0.2, 0.]);
+
+        for _ in 0..10_000 {
+            let (scattered, _) = metal.scatter(ray, hit.clone()).unwrap();
+            assert!(scattered.direction().dot(&normal) >= 0.);
+        }
+    }
+
+    #[test]
+    fn retroreflective_scatters_back() {
+        let material = Retroreflective::solid_color(color![0.8, 0.8, 0.8], 0.1);
+        let normal = vector![0., 1., 0.];
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &material);
+        let ray = Ray::new(vector![-1., 0.5, 0.], vector![1., -0.5, 0.]);
+        let towards_origin = (ray.origin() - hit.point).normalize();
+
+        for _ in 0..1000 {
+            let (scattered, _) = material.scatter(ray, hit.clone()).unwrap();
+            assert!(scattered.direction().normalize().dot(&towards_origin) > 0.9);
+        }
+    }
+
+    #[test]
+    fn sheen_at_grazing_angles() {
+        let sheen = Sheen::solid_color(color![0.5, 0.1, 0.2], 1.);
+
+        assert!(sheen.sheen_factor(1.) < 1e-6);
+        assert!(sheen.sheen_factor(0.5) < sheen.sheen_factor(0.1));
+        assert!((sheen.sheen_factor(0.) - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sheen_conserves_energy() {
+        let color = color![0.5, 0.1, 0.2];
+        let sheen = Sheen::solid_color(color, 1.);
+        let normal = vector![0., 1., 0.];
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &sheen);
+        let mean_attenuation = |ray: Ray| {
+            (0..1000)
+                .map(|_| sheen.scatter(ray, hit.clone()).unwrap().1)
+                .fold(BLACK, |sum, attenuation| sum + attenuation)
+                / 1000.
+        };
+
+        let normal_incidence = Ray::new(vector![0., 1., 0.], vector![0., -1., 0.]);
+        let grazing = Ray::new(vector![-1., 0.01, 0.], vector![1., -0.01, 0.]);
+        for ray in [normal_incidence, grazing] {
+            let attenuation = mean_attenuation(ray);
+            for (attenuation, color) in attenuation.into_iter().zip(color) {
+                assert!(attenuation <= color + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn thin_glass_fresnel() {
+        let glass = ThinGlass::new(1.5, color![0.9, 1., 0.9]);
+        let normal = vector![0., 1., 0.];
+        let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &glass);
+        let transmitted_fraction = |ray: Ray| {
+            (0..1000)
+                .filter(|_| {
+                    let (scattered, _) = glass.scatter(ray, hit.clone()).unwrap();
+                    scattered.direction().dot(&normal) < 0.
+                })
+                .count() as f32
+                / 1000.
+        };
+
+        let normal_incidence = Ray::new(vector![0., 1., 0.], vector![0., -1., 0.]);
+        let grazing = Ray::new(vector![-1., 0.01, 0.], vector![1., -0.01, 0.]);
+
+        assert!(transmitted_fraction(normal_incidence) > 0.9);
+        assert!(transmitted_fraction(grazing) < 0.5);
+    }
+
+    #[test]
+    fn one_sided_light() {
+        use crate::shapes::Rectangle;
+
+        let color = color![4., 4., 4.];
+        let emitted = |light: DiffuseLight<SolidColor>, z: f32| {
+            let rectangle = Rectangle::xy(vector![0., 0., 0.], 1., 1., light);
+            let ray = Ray::new(vector![0., 0., z], vector![0., 0., -z]);
+            let hit = rectangle.hit(ray, 0., f32::INFINITY).unwrap();
+            hit.material().emit(&hit)
+        };
+
+        assert_eq!(emitted(DiffuseLight::solid_color(color), 1.), color);
+        assert_eq!(emitted(DiffuseLight::solid_color(color), -1.), BLACK);
+        assert_eq!(
+            emitted(DiffuseLight::solid_color(color).with_two_sided(), -1.),
+            color
+        );
+    }
+
+    #[test]
+    fn isotropic_ignores_normal() {
+        let color = color![0.2, 0.4, 0.6];
+        let isotropic = Isotropic::solid_color(color);
+        // Like the hits of a `ConstantMedium`, which have no meaningful normal.
+        let hit = HitRecord::new(
+            vector![1., 2., 3.],
+            0.,
+            0.,
+            Vector3::zeros(),
+            1.,
+            true,
+            &isotropic,
+        );
+        let ray = Ray::new(vector![0., 0., 0.], vector![1., 2., 3.]).with_time(0.5);
+
+        let mut mean = Vector3::zeros();
+        for _ in 0..2000 {
+            let (scattered, attenuation) = isotropic.scatter(ray, hit.clone()).unwrap();
+            assert_eq!(attenuation, color);
+            assert_eq!(scattered.origin(), hit.point);
+            assert_eq!(scattered.time(), 0.5);
+            assert!((scattered.direction().norm() - 1.).abs() < 1e-4);
+            mean += scattered.direction() / 2000.;
+        }
+        assert!(mean.norm() < 0.1);
+    }
+
+    #[test]
+    fn colored_dielectric_absorbs_inside() {
+        let glass = Dielectric::colored(1.5, color![0., 0.5, 1.]);
+        let normal = vector![0., 0., 1.];
+        // A ray travelling 2 units through the glass towards its back face.
+        let ray = Ray::new(vector![0., 0., -2.], vector![0., 0., 0.5]);
+        let back = HitRecord::new(vector![0., 0., 0.], 0., 0., -normal, 4., false, &glass);
+        let (_, attenuation) = glass.scatter(ray, back).unwrap();
+        let expected = color![1., (-1f32).exp(), (-2f32).exp()];
+        assert!((attenuation - expected).into_iter().all(|c| c.abs() < 1e-6));
+
+        let front = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 4., true, &glass);
+        assert_eq!(glass.scatter(ray, front).unwrap().1, WHITE);
+
+        let clear = Dielectric::new(1.5);
+        let back = HitRecord::new(vector![0., 0., 0.], 0., 0., -normal, 4., false, &clear);
+        assert_eq!(clear.scatter(ray, back).unwrap().1, WHITE);
+    }
+
+    #[test]
+    fn rough_dielectric() {
+        // Total internal reflection, so that the sharp direction is deterministic.
+        let normal = vector![0., 1., 0.];
+        let ray = Ray::new(vector![-1., -0.2, 0.], vector![1., 0.2, 0.]);
+        let sharp = reflect(&ray.direction().normalize(), &normal);
+        let scatter = |glass: &Dielectric| {
+            let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., false, glass);
+            glass.scatter(ray, hit).unwrap().0.direction()
+        };
+
+        let clear = Dielectric::new(1.5);
+        let smooth = Dielectric::new(1.5).with_roughness(0.);
+        assert_eq!(scatter(&clear), sharp);
+        assert_eq!(scatter(&smooth), sharp);
+
+        let frosted = Dielectric::new(1.5).with_roughness(2.);
+        assert_eq!(frosted.roughness, 1.);
+        let directions: Vec<_> = (0..10).map(|_| scatter(&frosted)).collect();
+        assert!(directions
+            .iter()
+            .all(|direction| (direction - sharp).norm() <= 1.));
+        assert!(directions.iter().any(|direction| *direction != sharp));
+    }
+
+    #[test]
+    fn anisotropic_metal() {
+        let metal = Metal::solid_color(color![0.9, 0.9, 0.9], 0.5).with_anisotropy(1.);
+        let normal = vector![0., 1., 0.];
+        let tangent = vector![1., 0., 0.];
+        let ray = Ray::new(vector![0., 1., 0.], vector![0., -1., 0.]);
+
+        let mut spread = Vector3::zeros();
+        for _ in 0..200 {
+            let hit = HitRecord::new(vector![0., 0., 0.], 0., 0., normal, 1., true, &metal)
+                .with_tangent(tangent);
+            let (scattered, _) = metal.scatter(ray, hit).unwrap();
+            let direction = scattered.direction();
+            assert!(direction.dot(&normal) >= 0.);
+            spread += direction.abs();
+        }
+        // With full anisotropy, the reflections only spread along the tangent.
+        assert!(spread.x > 10.);
+        assert!(spread.z < 1e-4);
+    }
+
+    #[test]
+    fn slightly_anisotropic_metal_matches_isotropic() {
+        let isotropic = Metal::solid_color(color![0.9, 0.9, 0.9], 0.5);
+        let anisotropic = isotropic.clone().with_anisotropy(1e-3);
+        let normal = vector![0., 1., 0.];
+        let ray = Ray::new(vector![-1., 1., 0.], vector![1., -1., 0.]);
+        let reflected = vector![1., 1., 0.].normalize();
+
+        let mean_cos = |metal: &Metal<SolidColor>| {
+            let samples = 4000;
+            let mut sum = 0.;
+            for _ in 0..samples {
+                let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, metal)
+                    .with_tangent(vector![1., 0., 0.]);
+                let (scattered, _) = metal.scatter(ray, hit).unwrap();
+                sum += scattered.direction().normalize().dot(&reflected);
+            }
+            sum / samples as f32
+        };
+        assert!((mean_cos(&anisotropic) - mean_cos(&isotropic)).abs() < 0.01);
+    }
+
+    #[test]
+    fn microfacet_smooth_metal_matches_metal() {
+        let albedo = color![0.9, 0.6, 0.3];
+        let microfacet = Microfacet::solid_color(albedo, 0., 1.);
+        let metal = Metal::solid_color(albedo, 0.);
+        let normal = vector![0., 1., 0.];
+        let ray = Ray::new(vector![-1., 1., 0.], vector![1., -1., 0.]);
+
+        let (reference, reference_attenuation) = metal
+            .scatter(
+                ray,
+                HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &metal),
+            )
+            .unwrap();
+        // GGX has long tails even for the smallest alpha, so only most samples are mirror-like.
+        let samples = 1000;
+        let mut mirrored = 0;
+        for _ in 0..samples {
+            let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &microfacet);
+            let Some((scattered, attenuation)) = microfacet.scatter(ray, hit) else {
+                continue;
+            };
+            let angle = scattered.direction().angle(&reference.direction());
+            if angle < 0.02
+                && (attenuation - reference_attenuation)
+                    .into_iter()
+                    .all(|c| c.abs() < 0.02)
+            {
+                mirrored += 1;
+            }
+        }
+        assert!(mirrored > samples * 9 / 10, "{mirrored}");
+    }
+
+    #[test]
+    fn microfacet_specular() {
+        assert!(Microfacet::solid_color(WHITE, 0., 1.).is_specular());
+        assert!(!Microfacet::solid_color(WHITE, 0.9, 1.).is_specular());
+        assert!(!Microfacet::solid_color(WHITE, 0., 0.).is_specular());
+    }
+
+    #[test]
+    fn microfacet_conserves_energy() {
+        for (roughness, metallic) in [(0.2, 1.), (0.6, 1.), (0.5, 0.), (0.3, 0.5)] {
+            let microfacet = Microfacet::solid_color(WHITE, roughness, metallic);
+            let normal = vector![0., 1., 0.];
+            let ray = Ray::new(vector![-1., 2., 0.], vector![1., -2., 0.]);
+
+            let samples = 5000;
+            let mut total = BLACK;
+            for _ in 0..samples {
+                let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &microfacet);
+                if let Some((scattered, attenuation)) = microfacet.scatter(ray, hit) {
+                    assert!(scattered.direction().dot(&normal) > 0.);
+                    total += attenuation;
+                }
+            }
+            let mean = total / samples as f32;
+            assert!(
+                mean.r() < 1.05 && mean.r() > 0.5,
+                "{roughness} {metallic}: {mean:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn oren_nayar() {
+        let albedo = color![0.8, 0.6, 0.4];
+        let normal = vector![0., 1., 0.];
+        let ray = Ray::new(vector![-1., 1., 0.], vector![1., -1., 0.]);
+
+        let smooth = OrenNayar::solid_color(albedo, 0.);
+        for _ in 0..100 {
+            let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &smooth);
+            let (_, attenuation) = smooth.scatter(ray, hit).unwrap();
+            assert!((attenuation - albedo).into_iter().all(|c| c.abs() < 1e-5));
+        }
+
+        // Rough surfaces scatter more light back towards the viewer than sideways.
+        let rough = OrenNayar::new(SolidColor::new(albedo), 0.5);
+        let incoming = vector![-1., 1., 0.].normalize();
+        let back = rough.reflectance(&normal, &incoming, &incoming);
+        let forward = rough.reflectance(&normal, &incoming, &vector![1., 1., 0.].normalize());
+        assert!(back > forward);
+        assert!(forward < 1.);
+    }
+
+    #[test]
+    fn light_intensity_and_falloff() {
+        let normal = vector![0., 1., 0.];
+        let color = color![0.5, 0.25, 1.];
+
+        let light = DiffuseLight::solid_color(color);
+        let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 2., true, &light);
+        assert_eq!(light.emit(&hit), color);
+
+        let light = DiffuseLight::solid_color(color).with_intensity(4.);
+        let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 2., true, &light);
+        assert_eq!(light.emit(&hit), 4. * color);
+
+        let light = DiffuseLight::solid_color(color)
+            .with_intensity(4.)
+            .with_falloff(true);
+        let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 2., true, &light);
+        assert_eq!(light.emit(&hit), color);
+
+        // The falloff uses the distance along the ray, not its parameter.
+        let sphere = Sphere::new(vector![0., 0., -3.], 1., light);
+        let ray = Ray::new(Vector3::zeros(), vector![0., 0., -2.]);
+        let hit = sphere.hit(ray, 0., f32::INFINITY).unwrap();
+        assert_eq!(hit.t, 1.);
+        assert_eq!(hit.material().emit(&hit), color);
+    }
+
+    #[test]
+    fn normal_map() {
+        let metal = Metal::solid_color(color![0.8, 0.8, 0.8], 0.);
+        let normal = vector![0., 1., 0.];
+        let tangent = vector![1., 0., 0.];
+        let ray = Ray::new(vector![-1., 1., 0.], vector![1., -1., 0.]);
+
+        let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &metal)
+            .with_tangent(tangent);
+        let (reference, _) = metal.scatter(ray, hit).unwrap();
+
+        let flat = NormalMap::new(metal.clone(), SolidColor::new(Rgb([128, 128, 255]).into()));
+        let hit =
+            HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &flat).with_tangent(tangent);
+        assert!((flat.perturb(hit.clone()).normal - normal).norm() < 1e-6);
+        let (scattered, _) = flat.scatter(ray, hit).unwrap();
+        assert!((scattered.direction() - reference.direction()).norm() < 1e-6);
+
+        // Tilting the normal towards the tangent (+x) tilts the reflection as well.
+        let tilted = NormalMap::new(metal, SolidColor::new(Rgb([200, 128, 230]).into()));
+        let hit = HitRecord::new(Vector3::zeros(), 0., 0., normal, 1., true, &tilted)
+            .with_tangent(tangent);
+        let perturbed = tilted.perturb(hit.clone());
+        assert!(perturbed.normal.x > 0.);
+        assert!((perturbed.normal.norm() - 1.).abs() < 1e-5);
+        assert!(perturbed.tangent.unwrap().dot(&perturbed.normal).abs() < 1e-5);
+        let (scattered, _) = tilted.scatter(ray, hit).unwrap();
+        assert!(scattered.direction().x > reference.direction().x);
+    }
 }
@@ -1,6 +1,6 @@
-use rand::rngs::ThreadRng;
 use rand::Rng;
 
+use crate::random::LocalRng;
 use crate::vec3::random_vector_in_range;
 use crate::*;
 
@@ -63,7 +63,7 @@ impl Perlin {
         accum.abs()
     }
 
-    fn generate_permutation(rng: &mut ThreadRng) -> [usize; POINT_COUNT] {
+    fn generate_permutation(rng: &mut LocalRng) -> [usize; POINT_COUNT] {
         let mut permutation: [usize; POINT_COUNT] =
             (0..POINT_COUNT).collect::<Vec<_>>().try_into().unwrap();
 
@@ -72,7 +72,7 @@ impl Perlin {
         permutation
     }
 
-    fn permute(permutation: &mut [usize], rng: &mut ThreadRng) {
+    fn permute(permutation: &mut [usize], rng: &mut LocalRng) {
         for i in (1..POINT_COUNT).rev() {
             let rand = rng.gen_range(0..=i);
             permutation.swap(i, rand);
@@ -105,7 +105,7 @@ impl Perlin {
 
 impl Default for Perlin {
     fn default() -> Self {
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
 
         let mut random_points = [vector![0., 0., 0.]; POINT_COUNT];
         for i in &mut random_points {
@@ -1,21 +1,22 @@
-//! A simple Portable Pixmap image format (`.ppm`) writer.
+//! A simple Portable Pixmap image format (`.ppm`) reader and writer.
 
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
 use crate::color::Color;
 
-/// Portable Pixmap image format (`.ppm`) writer struct.
+/// Portable Pixmap image format (`.ppm`) reader and writer struct.
 ///
 /// # Fields
 /// - `colors`: Flat [vector](Vec) of [colors](Color)
 /// - `image_width`: Width of the image
 /// - `image_height`: Height of the image
+#[derive(Debug, PartialEq)]
 pub struct PPM {
-    colors: Vec<Color>,
-    image_width: u16,
-    image_height: u16,
+    pub(crate) colors: Vec<Color>,
+    pub(crate) image_width: u16,
+    pub(crate) image_height: u16,
 }
 
 impl PPM {
@@ -29,6 +30,92 @@ impl PPM {
 
     /// Write the PPM file.
     pub fn write_ppm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = fs::File::create(PPM::ppm_path(path)?)?;
+        let mut out = String::new();
+
+        out.push_str(&format!(
+            "P3\n{} {}\n255\n",
+            self.image_width, self.image_height
+        ));
+        for color in self.colors.iter() {
+            out.push_str(&color.to_color_str());
+            out.push('\n');
+        }
+
+        file.write_all(out.as_bytes())
+    }
+
+    /// Write the PPM file in the binary (P6) variant, which is much smaller than the ASCII one of [`write_ppm`](PPM::write_ppm).
+    pub fn write_ppm_binary<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = fs::File::create(PPM::ppm_path(path)?)?;
+        let mut out = format!("P6\n{} {}\n255\n", self.image_width, self.image_height).into_bytes();
+
+        for color in self.colors.iter() {
+            let rgb: [u8; 3] = (*color).into();
+            out.extend_from_slice(&rgb);
+        }
+
+        file.write_all(&out)
+    }
+
+    /// Read a PPM file in either the ASCII (P3) or the binary (P6) variant.
+    ///
+    /// Channels are scaled by the maximum value of the header, so an image written by [`write_ppm`](PPM::write_ppm) or [`write_ppm_binary`](PPM::write_ppm_binary) reads back to the same bytes.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<PPM> {
+        PPM::parse(&fs::read(path)?)
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<PPM> {
+        let mut header = Header { bytes, pos: 0 };
+        let binary = match header.token()? {
+            b"P3" => false,
+            b"P6" => true,
+            _ => return Err(invalid_data("Invalid magic number")),
+        };
+        let image_width: u16 = header.number()?;
+        let image_height: u16 = header.number()?;
+        let max_value: u16 = header.number()?;
+        if max_value == 0 || max_value > 255 {
+            return Err(invalid_data("Unsupported maximum color value"));
+        }
+        let pixel_count = image_width as usize * image_height as usize;
+        let channel = |value: u16| -> io::Result<f32> {
+            if value > max_value {
+                return Err(invalid_data("Color value out of range"));
+            }
+            Ok(value as f32 / max_value as f32)
+        };
+
+        let channels = if binary {
+            // Exactly one whitespace character separates the header from the raster.
+            let raster = bytes.get(header.pos + 1..).unwrap_or_default();
+            if raster.len() != 3 * pixel_count {
+                return Err(invalid_data("Pixel count does not match the image size"));
+            }
+            raster
+                .iter()
+                .map(|&value| channel(value as u16))
+                .collect::<io::Result<Vec<_>>>()?
+        } else {
+            let mut channels = Vec::with_capacity(3 * pixel_count);
+            while header.skip_whitespace() {
+                channels.push(channel(header.number()?)?);
+            }
+            if channels.len() != 3 * pixel_count {
+                return Err(invalid_data("Pixel count does not match the image size"));
+            }
+            channels
+        };
+
+        let colors = channels
+            .chunks_exact(3)
+            .map(|rgb| Color::new(rgb[0], rgb[1], rgb[2]))
+            .collect();
+        Ok(PPM::new(colors, image_width, image_height))
+    }
+
+    /// Check that `path` has the `.ppm` extension, or add it if there is none.
+    fn ppm_path<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
         let mut path = path.as_ref().to_path_buf();
         match path.extension() {
             Some(ext) => {
@@ -43,18 +130,138 @@ impl PPM {
                 path.set_extension("ppm");
             }
         }
-        let mut file = fs::File::create(path)?;
-        let mut out = String::new();
+        Ok(path)
+    }
+}
 
-        out.push_str(&format!(
-            "P3\n{} {}\n255\n",
-            self.image_width, self.image_height
-        ));
-        for color in self.colors.iter() {
-            out.push_str(&color.to_color_str());
-            out.push('\n');
+/// Cursor over the whitespace separated tokens of a PPM header.
+struct Header<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Header<'a> {
+    /// Skip whitespace and comments, returning whether there is another token.
+    fn skip_whitespace(&mut self) -> bool {
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            if byte == b'#' {
+                while self.bytes.get(self.pos).is_some_and(|&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                return true;
+            }
         }
+        false
+    }
 
-        file.write_all(out.as_bytes())
+    fn token(&mut self) -> io::Result<&'a [u8]> {
+        if !self.skip_whitespace() {
+            return Err(invalid_data("Unexpected end of file"));
+        }
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        Ok(&self.bytes[start..self.pos])
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> io::Result<T> {
+        std::str::from_utf8(self.token()?)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| invalid_data("Invalid number"))
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn binary_round_trip() {
+        let colors = vec![
+            color![0., 0.5, 1.],
+            color![0.25, 0.75, 0.1],
+            color![1.5, -0.2, 0.999],
+            color![0.3, 0.3, 0.3],
+            color![0.9, 0.1, 0.4],
+            color![0.6, 0.0, 0.2],
+        ];
+        let ppm = PPM::new(colors.clone(), 3, 2);
+        let path = std::env::temp_dir().join("ray-tracing-binary.ppm");
+        ppm.write_ppm_binary(&path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+
+        let pixels = &bytes[header.len()..];
+        assert_eq!(pixels.len(), 3 * colors.len());
+        for (rgb, color) in pixels.chunks(3).zip(colors) {
+            let decoded = format!("{} {} {}", rgb[0], rgb[1], rgb[2]);
+            assert_eq!(decoded, color.to_color_str());
+        }
+    }
+
+    #[test]
+    fn binary_rejects_extension() {
+        let ppm = PPM::new(vec![crate::color::BLACK], 1, 1);
+        let path = std::env::temp_dir().join("ray-tracing-binary.png");
+        let error = ppm.write_ppm_binary(path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn read_round_trip() {
+        let colors = vec![
+            color![0., 0.5, 1.],
+            color![0.25, 0.75, 0.1],
+            color![0.3, 0.3, 0.3],
+            color![0.9, 0.1, 0.4],
+        ];
+        let ppm = PPM::new(colors, 2, 2);
+        let dir = std::env::temp_dir();
+        ppm.write_ppm(dir.join("ray-tracing-read-ascii.ppm"))
+            .unwrap();
+        ppm.write_ppm_binary(dir.join("ray-tracing-read-binary.ppm"))
+            .unwrap();
+
+        for name in ["ray-tracing-read-ascii.ppm", "ray-tracing-read-binary.ppm"] {
+            let read = PPM::read(dir.join(name)).unwrap();
+            assert_eq!(read.image_width, 2);
+            assert_eq!(read.image_height, 2);
+            let read_strs: Vec<_> = read.colors.iter().map(|c| c.to_color_str()).collect();
+            let strs: Vec<_> = ppm.colors.iter().map(|c| c.to_color_str()).collect();
+            assert_eq!(read_strs, strs);
+        }
+    }
+
+    #[test]
+    fn read_comments_and_errors() {
+        let ppm = PPM::parse(b"P3\n# comment\n1 1 # size\n15\n15 0 5\n").unwrap();
+        assert_eq!(ppm.colors, vec![color![1., 0., 1. / 3.]]);
+
+        for invalid in [
+            &b"P5\n1 1\n255\n\0"[..],
+            b"P3\n2 1\n255\n0 0 0\n",
+            b"P3\n1 1\n255\n0 0 256\n",
+            b"P6\n1 1\n255\n\0\0",
+            b"P6\n1",
+        ] {
+            let error = PPM::parse(invalid).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
     }
 }
@@ -0,0 +1,118 @@
+//! Source of randomness for rendering.
+//!
+//! All random numbers of the crate are drawn from a thread-local generator, which is seeded from the operating system by default.
+//! Inside [`with_seed`], it is replaced by a seeded one, which makes everything computed on the current thread reproducible.
+
+use std::cell::RefCell;
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Handle to the random number generator of the current thread, similar to [`rand::rngs::ThreadRng`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalRng;
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+/// The random number generator of the current thread.
+pub fn rng() -> LocalRng {
+    LocalRng
+}
+
+/// Seed the generator of the current thread with `seed` until the returned [`SeedGuard`] is dropped, which restores the previous generator.
+///
+/// As the generator is thread-local, work handed off to other threads (e.g. via [`rayon`]) is not affected.
+#[must_use = "the seed is reset when the guard is dropped"]
+pub fn seed(seed: u64) -> SeedGuard {
+    let previous = RNG.with(|rng| rng.replace(StdRng::seed_from_u64(seed)));
+    SeedGuard {
+        previous: Some(previous),
+    }
+}
+
+/// Run `op` with the generator of the current thread seeded with `seed` (see [`seed`]).
+pub fn with_seed<R>(seed: u64, op: impl FnOnce() -> R) -> R {
+    let _guard = self::seed(seed);
+    op()
+}
+
+/// Derive an independent seed for the `index`-th part of some work (e.g. a pixel) from `seed`.
+///
+/// Both are scrambled with SplitMix64, so that neighboring indices and seeds do not share parts of their random streams.
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+    splitmix64(seed ^ splitmix64(index))
+}
+
+/// The finalizer of the SplitMix64 generator.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Restores the previous generator of the current thread when dropped (see [`seed`]).
+#[derive(Debug)]
+pub struct SeedGuard {
+    previous: Option<StdRng>,
+}
+
+impl Drop for SeedGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            RNG.with(|rng| rng.replace(previous));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn seeded() {
+        let draw = || (0..10).map(|_| rng().gen::<u32>()).collect::<Vec<_>>();
+
+        let first = with_seed(42, draw);
+        let second = with_seed(42, draw);
+        assert_eq!(first, second);
+        assert_ne!(first, with_seed(43, draw));
+
+        // The generator is restored afterwards, so unseeded draws do not repeat the seeded ones.
+        assert_ne!(draw(), first);
+    }
+
+    #[test]
+    fn mixed_seeds() {
+        let first = |seed| with_seed(mix_seed(seed, 0), || rng().gen::<u64>());
+        let second = |seed| with_seed(mix_seed(seed, 1), || rng().gen::<u64>());
+
+        assert_eq!(mix_seed(42, 7), mix_seed(42, 7));
+        // Adjacent seeds and indices must not collide like with `seed + index`.
+        assert_ne!(mix_seed(42, 1), mix_seed(43, 0));
+        assert_ne!(first(42), second(42));
+        assert_ne!(second(42), first(43));
+    }
+}
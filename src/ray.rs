@@ -1,6 +1,8 @@
 //! A simple ray of light.
 
-use nalgebra::Vector3;
+use nalgebra::{Rotation3, Vector3};
+
+use crate::vec3::near_zero;
 
 /// A ray starting at `origin` at `time` pointing in `direction`.
 ///
@@ -53,7 +55,47 @@ impl Ray {
         self.origin + t * self.direction
     }
 
+    /// Whether the direction is (nearly) zero or not finite, so the [`Ray`] cannot hit anything.
+    ///
+    /// Such rays would otherwise produce NaNs in the intersection routines.
+    pub fn is_degenerate(&self) -> bool {
+        near_zero(&self.direction) || self.direction.iter().any(|x| !x.is_finite())
+    }
+
     pub fn time(&self) -> f32 {
         self.time
     }
+
+    /// Rotate the [`Ray`] around the origin by `rotation` (if any) and then move it by `translation`, keeping its time.
+    pub fn transformed(&self, rotation: Option<Rotation3<f32>>, translation: Vector3<f32>) -> Self {
+        let (origin, direction) = match rotation {
+            Some(rotation) => (rotation * self.origin, rotation * self.direction),
+            None => (self.origin, self.direction),
+        };
+
+        Self {
+            origin: origin + translation,
+            direction,
+            time: self.time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn transformed_keeps_time() {
+        let ray = Ray::new(vector![1., 0., 0.], vector![0., 1., 0.]).with_time(0.7);
+        let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2);
+
+        let transformed = ray.transformed(Some(rotation), vector![0., 0., 2.]);
+
+        assert_eq!(transformed.time(), 0.7);
+        assert!((transformed.origin() - vector![0., 1., 2.]).norm() < 1e-6);
+        assert!((transformed.direction() - vector![-1., 0., 0.]).norm() < 1e-6);
+        assert_eq!(ray.transformed(None, Vector3::zeros()).time(), 0.7);
+    }
 }
@@ -1,18 +1,37 @@
 //! Central struct for creating a ray tracer and rendering an image.
 
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::fmt;
+use std::io::Cursor;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use image::{ImageError, RgbImage};
+use image::error::{EncodingError, ImageFormatHint, ParameterError, ParameterErrorKind};
+use image::{ImageError, ImageFormat, ImageOutputFormat, RgbImage};
 use indicatif::{ProgressBar, ProgressStyle};
+use nalgebra::Rotation3;
 use rand::Rng;
 use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
-use crate::color::BLACK;
+use crate::camera::CameraPath;
+use crate::color::{ToneMap, BLACK, WHITE};
+use crate::hitrecord::HitRecord;
 use crate::hittable::{Bvh, HittableListOptions};
+use crate::lut::Lut3d;
+use crate::materials::Material;
 use crate::ppm::PPM;
+use crate::random::SeedGuard;
 use crate::ray::Ray;
+use crate::textures::{ImageTexture, Texture, Wrap};
 use crate::*;
 
+/// Edge length in pixels of the square tiles the image is rendered in.
+const TILE_SIZE: usize = 32;
+
 /// Central ray tracing struct.
 ///
 /// This struct allows setting attributes of the ray tracer, creating the world, and then rendering and saving it.
@@ -25,6 +44,25 @@ use crate::*;
 /// - `image_height`: Height of the resulting image.
 /// - `samples_per_pixel`: How many samples to take for each pixel for the purpose of anti-aliasing.
 /// - `max_depth`: How often a [`Ray`] should bounce at most.
+/// - `depth_limits`: Optional separate bounce budgets for diffuse and specular [`Material`]s, replacing `max_depth`.
+/// - `clay_override`: Optional [`Material`] used for all non-emitting objects instead of their own.
+/// - `median_of_means`: Optional number of batches for the median-of-means estimator.
+/// - `russian_roulette`: Optional number of bounces after which paths are terminated randomly (see [`with_russian_roulette`](Raytracer::with_russian_roulette)).
+/// - `stratified`: Whether the samples of each pixel are spread over a grid (see [`with_stratified_sampling`](Raytracer::with_stratified_sampling)).
+/// - `up_axis`: Axis pointing up in the coordinates of `world`.
+/// - `gamma`: Gamma the rendered colors are encoded with.
+/// - `tone_map`: [`ToneMap`] of the rendered images (see [`RaytracedImage::with_tone_map`]).
+/// - `shading_mode`: What the rendered pixels show (see [`ShadingMode`]).
+/// - `sah_bvh`: Whether the [`Bvh`] is built with the surface area heuristic (see [`Bvh::new_sah`]).
+/// - `prebuilt_bvh`: Optional [`Bvh`] used instead of building one from `world`.
+/// - `acceleration`: Optional copy of `world` (or `prebuilt_bvh`) optimized for [`render_into`](Raytracer::render_into) by [`build_acceleration`](Raytracer::build_acceleration).
+/// - `environment`: Optional equirectangular environment map seen by [`Ray`]s missing everything instead of the background color.
+/// - `background_fn`: Optional function computing the color of [`Ray`]s missing everything, taking precedence over `environment` and the background color.
+/// - `thread_pool`: Optional [`rayon`] thread pool rendering is done in instead of the global one.
+/// - `failed_threads`: Number of threads requested by [`with_threads`](Raytracer::with_threads) if their thread pool could not be created.
+/// - `seed`: Optional seed making renders reproducible.
+/// - `first_sample`: Index of the first sample of each pixel, so that the batches of a [`RenderSession`] continue the random numbers of the previous ones.
+/// - `progress_callback`: Optional function reporting the number of completed and total pixels while rendering.
 #[derive(Clone, Debug)]
 pub struct Raytracer {
     pub world: HittableList,
@@ -34,7 +72,25 @@ pub struct Raytracer {
     image_height: u16,
     samples_per_pixel: u16,
     max_depth: u16,
-    progressbar: Option<ProgressBar>,
+    depth_limits: Option<(u16, u16)>,
+    clay_override: Option<Arc<dyn Material>>,
+    median_of_means: Option<u16>,
+    russian_roulette: Option<u16>,
+    stratified: bool,
+    up_axis: Axis,
+    gamma: f32,
+    tone_map: ToneMap,
+    shading_mode: ShadingMode,
+    sah_bvh: bool,
+    prebuilt_bvh: Option<Bvh>,
+    acceleration: Option<HittableListOptions>,
+    environment: Option<ImageTexture>,
+    background_fn: Option<BackgroundFn>,
+    thread_pool: Option<Arc<ThreadPool>>,
+    failed_threads: Option<usize>,
+    seed: Option<u64>,
+    first_sample: u32,
+    progress_callback: Option<ProgressFn>,
 }
 
 impl Raytracer {
@@ -54,7 +110,25 @@ impl Raytracer {
             image_height,
             samples_per_pixel,
             max_depth,
-            progressbar: None,
+            depth_limits: None,
+            clay_override: None,
+            median_of_means: None,
+            russian_roulette: None,
+            stratified: false,
+            up_axis: Axis::Y,
+            gamma: 2.,
+            tone_map: ToneMap::None,
+            shading_mode: ShadingMode::Beauty,
+            sah_bvh: false,
+            prebuilt_bvh: None,
+            acceleration: None,
+            environment: None,
+            background_fn: None,
+            thread_pool: None,
+            failed_threads: None,
+            seed: None,
+            first_sample: 0,
+            progress_callback: None,
         }
     }
 
@@ -68,15 +142,326 @@ impl Raytracer {
             .unwrap()
             .progress_chars("#>-"),
         );
-        Self {
-            world: self.world,
-            camera: self.camera,
-            background: self.background,
+        self.with_progress_callback(Box::new(move |done, total| {
+            progressbar.set_length(total);
+            progressbar.set_position(done);
+        }))
+    }
+
+    /// Consume `self` and call `callback` with the number of completed and total pixels whenever a part of the image is finished, e.g. to drive a GUI.
+    ///
+    /// [`render`](Raytracer::render) calls it once per tile of up to 32×32 pixels. It may be called from several threads at once.
+    pub fn with_progress_callback(mut self, callback: Box<dyn Fn(u64, u64) + Send + Sync>) -> Self {
+        self.progress_callback = Some(ProgressFn(Arc::from(callback)));
+        self
+    }
+
+    /// Start counting the completed pixels of a render for the progress callback.
+    fn progress(&self) -> Progress<'_> {
+        Progress {
+            callback: self.progress_callback.as_ref(),
+            done: AtomicU64::new(0),
+            total: self.image_height as u64 * self.image_width as u64,
+        }
+    }
+
+    /// Consume `self` and use separate bounce budgets for diffuse and specular materials.
+    ///
+    /// A path is cut off once it would exceed either budget, so e.g. deep chains of refractions can be followed while diffuse interreflections stay cheap.
+    /// This replaces the single `max_depth`.
+    pub fn with_depth_limits(mut self, diffuse: u16, specular: u16) -> Self {
+        self.depth_limits = Some((diffuse, specular));
+        self
+    }
+
+    /// Consume `self` and terminate paths with Russian roulette after `min_bounces` bounces.
+    ///
+    /// Each further bounce survives with a probability given by the luminance of the attenuation accumulated along the path (at least 5 %), and survivors are weighted up accordingly.
+    /// This keeps the image unbiased while spending fewer [`Ray`]s on paths that contribute little, e.g. deep inside glass. `max_depth` and the depth limits still apply.
+    pub fn with_russian_roulette(mut self, min_bounces: u16) -> Self {
+        self.russian_roulette = Some(min_bounces);
+        self
+    }
+
+    /// Consume `self` and render `shading_mode` instead of the lit image, e.g. for debugging the scene.
+    pub fn with_shading_mode(mut self, shading_mode: ShadingMode) -> Self {
+        self.shading_mode = shading_mode;
+        self
+    }
+
+    /// Consume `self` and divide each pixel into a √`samples_per_pixel`×√`samples_per_pixel` grid, taking one randomly placed sample in each cell.
+    ///
+    /// This spreads the samples more evenly than placing all of them randomly, which reduces noise e.g. at edges.
+    /// `samples_per_pixel` is rounded down to a perfect square in this mode.
+    pub fn with_stratified_sampling(mut self) -> Self {
+        self.stratified = true;
+        self
+    }
+
+    /// Number of samples actually taken for each pixel, i.e. `samples_per_pixel` rounded down to a perfect square for stratified sampling.
+    fn samples(&self) -> u16 {
+        if self.stratified {
+            self.cells().pow(2)
+        } else {
+            self.samples_per_pixel
+        }
+    }
+
+    /// Number of cells along each side of the stratification grid.
+    fn cells(&self) -> u16 {
+        (self.samples_per_pixel as f64).sqrt() as u16
+    }
+
+    /// Random offset of the `sample`-th sample inside a pixel, in the corresponding cell of the grid for stratified sampling.
+    fn subpixel_offset(&self, sample: u16, rng: &mut impl Rng) -> (f32, f32) {
+        if self.stratified {
+            let cells = self.cells().max(1);
+            (
+                ((sample % cells) as f32 + rng.gen::<f32>()) / cells as f32,
+                ((sample / cells) as f32 + rng.gen::<f32>()) / cells as f32,
+            )
+        } else {
+            (rng.gen(), rng.gen())
+        }
+    }
+
+    /// Consume `self` and render every object with `material` instead of its own (e.g. a gray [`Lambertian`](crate::materials::Lambertian) for a clay render).
+    ///
+    /// Objects that emit light keep their own material, so the scene stays lit. The materials stored in `world` are not changed.
+    pub fn with_clay_override<M: Material + 'static>(mut self, material: M) -> Self {
+        self.clay_override = Some(Arc::new(material));
+        self
+    }
+
+    /// Consume `self` and estimate each pixel by the median of means instead of the mean of all samples.
+    ///
+    /// The samples of a pixel are split into `batches` batches, which are averaged separately. The median of these averages (per channel) rejects fireflies robustly with little bias.
+    pub fn with_median_of_means(mut self, batches: u16) -> Self {
+        self.median_of_means = Some(batches);
+        self
+    }
+
+    /// Consume `self` and build the [`Bvh`] with the surface area heuristic instead of splitting by a random axis.
+    ///
+    /// This takes longer to build, but pays off for scenes with unevenly distributed objects, e.g. meshes.
+    pub fn with_sah_bvh(mut self) -> Self {
+        self.sah_bvh = true;
+        self.acceleration = None;
+        self
+    }
+
+    /// Consume `self` and render `bvh` instead of building a [`Bvh`] from `world` for every render.
+    ///
+    /// This saves the construction time when rendering the same scene many times, e.g. from different camera angles.
+    /// `world` is ignored by all renders that use a [`Bvh`], except for [`render_without_bvh`](Raytracer::render_without_bvh) and [`render_light_aovs`](Raytracer::render_light_aovs).
+    /// The [`Aabb`](crate::hittable::Aabb)s of a [`Bvh`] are fixed for the time interval passed to [`Bvh::new`], which therefore has to contain the whole shutter interval of the [`Camera`] if objects are moving.
+    pub fn with_prebuilt_bvh(mut self, bvh: Bvh) -> Self {
+        self.prebuilt_bvh = Some(bvh);
+        self.acceleration = None;
+        self
+    }
+
+    /// Consume `self` and replace all settings independent of the scene by `settings`.
+    pub fn with_settings(mut self, settings: RenderSettings) -> Self {
+        self.image_width = settings.image_width;
+        self.image_height = settings.image_height;
+        self.samples_per_pixel = settings.samples_per_pixel;
+        self.max_depth = settings.max_depth;
+        self.depth_limits = settings.depth_limits;
+        self.median_of_means = settings.median_of_means;
+        self.russian_roulette = settings.russian_roulette;
+        self.up_axis = settings.up_axis;
+        self.stratified = settings.stratified;
+        self.gamma = settings.gamma;
+        self.tone_map = settings.tone_map;
+        self.seed = settings.seed;
+        self
+    }
+
+    /// All settings independent of the scene.
+    pub fn settings(&self) -> RenderSettings {
+        RenderSettings {
             image_width: self.image_width,
             image_height: self.image_height,
             samples_per_pixel: self.samples_per_pixel,
             max_depth: self.max_depth,
-            progressbar: Some(progressbar),
+            depth_limits: self.depth_limits,
+            median_of_means: self.median_of_means,
+            russian_roulette: self.russian_roulette,
+            up_axis: self.up_axis,
+            stratified: self.stratified,
+            gamma: self.gamma,
+            tone_map: self.tone_map,
+            seed: self.seed,
+        }
+    }
+
+    /// Consume `self` and light the scene with an equirectangular `environment` map (e.g. an HDR sky, see [`ImageTexture::open_hdr`]) instead of the constant background color.
+    ///
+    /// The center of the map lies in the -z direction and its top in the +y direction of `world`.
+    /// The map is repeated horizontally, so filtering blends across the seam at its left and right edges.
+    pub fn with_environment(mut self, environment: ImageTexture) -> Self {
+        self.environment = Some(environment.with_wrap_uv(Wrap::Repeat, Wrap::Clamp));
+        self
+    }
+
+    /// Consume `self` and color [`Ray`]s missing everything with `background` instead of the constant background color, e.g. for a sky gradient.
+    ///
+    /// This takes precedence over [`with_environment`](Raytracer::with_environment).
+    pub fn with_background_fn(
+        mut self,
+        background: Box<dyn Fn(Ray) -> Color + Send + Sync>,
+    ) -> Self {
+        self.background_fn = Some(BackgroundFn(Arc::from(background)));
+        self
+    }
+
+    /// Consume `self` and render with `threads` threads of a separate thread pool, e.g. to share the cores between several concurrent renders.
+    ///
+    /// With 0 threads, the global [`rayon`] thread pool is used (default).
+    /// If the thread pool cannot be created, the global one is used as well, and [`validate`](Raytracer::validate) reports [`ValidationIssue::ThreadPool`].
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.thread_pool = None;
+        self.failed_threads = None;
+        if threads > 0 {
+            match ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => self.thread_pool = Some(Arc::new(pool)),
+                Err(_) => self.failed_threads = Some(threads),
+            }
+        }
+        self
+    }
+
+    /// Run `op` in the thread pool of `self`, or in the global one if there is none.
+    fn in_pool<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match &self.thread_pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// Consume `self` and make renders reproducible by seeding the random numbers of each sample with `seed` mixed with the index of its pixel and its own index (see [`random::mix_seed`]).
+    ///
+    /// Each pixel is traced on a single thread, so the result does not depend on thread scheduling. Randomness used while building the [`Bvh`] is not seeded.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Seed the random numbers of the current thread for the `sample`-th sample (counted from `first_sample`) of the pixel at `index` until the returned guard is dropped, if there is a `seed`.
+    fn seed_sample(&self, index: usize, sample: u16) -> Option<SeedGuard> {
+        self.seed.map(|seed| {
+            let pixel = random::mix_seed(seed, index as u64);
+            random::seed(random::mix_seed(
+                pixel,
+                u64::from(self.first_sample + u32::from(sample)),
+            ))
+        })
+    }
+
+    /// Consume `self` and encode the rendered colors with `gamma` instead of the default of 2.
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Consume `self` and compress bright colors of the rendered images with `tone_map` (see [`RaytracedImage::with_tone_map`]).
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Consume `self` and interpret the coordinates of `world` with `up_axis` pointing up (default: [`Axis::Y`]).
+    ///
+    /// This allows rendering e.g. z-up datasets without rotating every object. The [`Camera`] and the environment map stay in the y-up convention of the crate.
+    pub fn with_up_axis(mut self, up_axis: Axis) -> Self {
+        self.up_axis = up_axis;
+        self
+    }
+
+    /// Rotation from the y-up convention of the crate into the coordinates of `world`, or `None` if `world` is y-up as well.
+    fn up_rotation(&self) -> Option<Rotation3<f32>> {
+        match self.up_axis {
+            Axis::X => Some(Rotation3::from_axis_angle(&Vector3::z_axis(), -FRAC_PI_2)),
+            Axis::Y => None,
+            Axis::Z => Some(Rotation3::from_axis_angle(&Vector3::x_axis(), FRAC_PI_2)),
+        }
+    }
+
+    /// Map a position in pixels (with pixel centers at half-integers) to the viewport coordinates of the [`Camera`].
+    ///
+    /// Usually the position is divided by one less than the number of pixels, as in the book.
+    /// A viewport that [wraps around](Camera::equirectangular) instead maps the full width onto `[0, 1]`, so the first and last columns are exactly one pixel apart across the seam.
+    fn viewport_uv(&self, x: f32, y: f32) -> (f32, f32) {
+        if self.camera.wraps_around() {
+            (x / self.image_width as f32, y / self.image_height as f32)
+        } else {
+            (
+                x / (self.image_width - 1) as f32,
+                y / (self.image_height - 1) as f32,
+            )
+        }
+    }
+
+    /// Emit a [`Ray`] from the [`Camera`] and transform it into the coordinates of `world`.
+    fn primary_ray(&self, u: f32, v: f32) -> Ray {
+        let ray = self.camera.get_ray(u, v);
+        match self.up_rotation() {
+            Some(rotation) => ray.transformed(Some(rotation), Vector3::zeros()),
+            None => ray,
+        }
+    }
+
+    /// Check the settings for problems that would make rendering panic or produce a meaningless image.
+    ///
+    /// All problems found are returned at once, so they can be fixed before starting a long render.
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = Vec::new();
+
+        if self.image_width < 2 || self.image_height < 2 {
+            issues.push(ValidationIssue::ImageTooSmall {
+                image_width: self.image_width,
+                image_height: self.image_height,
+            });
+        }
+        if self.samples_per_pixel == 0 {
+            issues.push(ValidationIssue::NoSamples);
+        }
+        if self.depth_limits.is_none() && self.max_depth == 0 {
+            issues.push(ValidationIssue::NoBounces);
+        }
+        if self.camera.is_degenerate() {
+            issues.push(ValidationIssue::DegenerateCamera);
+        }
+        if let Some(threads) = self.failed_threads {
+            issues.push(ValidationIssue::ThreadPool { threads });
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    fn depth_budget(&self) -> DepthBudget {
+        match self.depth_limits {
+            Some((diffuse, specular)) => DepthBudget {
+                total: u16::MAX,
+                diffuse,
+                specular,
+                bounces: 0,
+                throughput: WHITE,
+                russian_roulette: self.russian_roulette,
+            },
+            None => DepthBudget {
+                total: self.max_depth,
+                diffuse: u16::MAX,
+                specular: u16::MAX,
+                bounces: 0,
+                throughput: WHITE,
+                russian_roulette: self.russian_roulette,
+            },
         }
     }
 
@@ -87,189 +472,2483 @@ impl Raytracer {
     pub fn render(self) -> RaytracedImage {
         let image_width = self.image_width;
         let image_height = self.image_height;
+        let gamma = self.gamma;
+        let tone_map = self.tone_map;
         let image = self.render_multithreaded();
 
-        RaytracedImage {
-            image,
-            image_width,
-            image_height,
+        RaytracedImage::new(image, image_width, image_height)
+            .with_gamma(gamma)
+            .with_tone_map(tone_map)
+    }
+
+    /// Render like [`render`](Raytracer::render), but write the colors into `buffer` instead of allocating a new image.
+    ///
+    /// This allows for reusing the same buffer for repeated renders. The scene is rendered from the [`Bvh`] built by [`build_acceleration`](Raytracer::build_acceleration), which has to be called before and again after any change to `world`.
+    /// The only allocations per call are the bookkeeping of the tiles and, with [median of means](Raytracer::with_median_of_means), the samples of each pixel.
+    /// Returns [`ValidationIssue::BufferLength`] or [`ValidationIssue::AccelerationNotBuilt`] without rendering if `buffer` does not have exactly one entry per pixel or the [`Bvh`] has not been built.
+    pub fn render_into(&self, buffer: &mut [Color]) -> Result<(), ValidationIssue> {
+        let expected = self.image_height as usize * self.image_width as usize;
+        if buffer.len() != expected {
+            return Err(ValidationIssue::BufferLength {
+                expected,
+                actual: buffer.len(),
+            });
+        }
+
+        let world = self
+            .acceleration
+            .as_ref()
+            .ok_or(ValidationIssue::AccelerationNotBuilt)?;
+        self.in_pool(|| {
+            self.integrate_into(world, buffer);
+            self.post_process(buffer, self.samples().into());
+        });
+        Ok(())
+    }
+
+    /// Build the [`Bvh`] used by [`render_into`](Raytracer::render_into) from a copy of `world`.
+    ///
+    /// Changes made to `world` afterwards are only rendered once this is called again. [`with_sah_bvh`](Raytracer::with_sah_bvh) and [`with_prebuilt_bvh`](Raytracer::with_prebuilt_bvh) discard the [`Bvh`].
+    pub fn build_acceleration(&mut self) {
+        self.acceleration = Some(self.build_acceleration_structure());
+    }
+
+    /// Optimize a copy of `world` into a [`Bvh`] if possible, or use the prebuilt [`Bvh`] if there is one.
+    fn build_acceleration_structure(&self) -> HittableListOptions {
+        match &self.prebuilt_bvh {
+            Some(bvh) => HittableListOptions::Bvh(bvh.clone()),
+            None => Raytracer::build_world(self.world.clone(), self.sah_bvh),
         }
     }
 
     pub fn render_without_bvh(self) -> RaytracedImage {
         let image_width = self.image_width;
         let image_height = self.image_height;
+        let gamma = self.gamma;
+        let tone_map = self.tone_map;
         let image = self.render_multithreaded_without_bvh();
 
-        RaytracedImage {
-            image,
-            image_width,
-            image_height,
-        }
+        RaytracedImage::new(image, image_width, image_height)
+            .with_gamma(gamma)
+            .with_tone_map(tone_map)
     }
 
-    fn render_multithreaded(self) -> Vec<Color> {
-        let world = match Bvh::check_hittable_list(&self.world) {
-            true => HittableListOptions::Bvh(Bvh::new(self.world, 0., 0.).expect("creating BVH")),
-            false => HittableListOptions::HittableList(self.world),
-        };
+    /// Render only the background seen by the primary [`Ray`]s to a [`RaytracedImage`], ignoring `world`.
+    ///
+    /// This is useful for creating a backplate matching a render for compositing.
+    pub fn render_background_only(self) -> RaytracedImage {
+        let mut colors = vec![BLACK; self.image_height as usize * self.image_width as usize];
+        self.integrate_with(&mut colors, |index| {
+            let mut color = BLACK;
+            self.trace_samples(index, |ray| color += self.background_color(ray));
+            color / self.samples() as f32
+        });
 
-        let mut colors =
-            vec![color![0., 0., 0.]; self.image_height as usize * self.image_width as usize];
+        RaytracedImage::new(colors, self.image_width, self.image_height)
+            .with_gamma(self.gamma)
+            .with_tone_map(self.tone_map)
+    }
 
-        colors
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(index, color)| {
-                let mut rng = rand::thread_rng();
-                let i = index % self.image_width as usize;
-                let j = self.image_height as usize - index / self.image_width as usize - 1;
-
-                for _ in 0..self.samples_per_pixel {
-                    let u = (i as f32 + rng.gen::<f32>()) / (self.image_width - 1) as f32;
-                    let v = (j as f32 + rng.gen::<f32>()) / (self.image_height - 1) as f32;
-                    *color += Raytracer::ray_color(
-                        &world,
-                        self.camera.get_ray(u, v),
-                        self.background,
-                        self.max_depth,
-                    );
+    /// Render to a [`RaytracedImage`] like [`render`](Raytracer::render), together with auxiliary buffers for compositing and denoising.
+    ///
+    /// For each pixel, the buffers contain the normal (facing the [`Camera`], in the coordinates of `world`) and the distance of the first hit of the primary [`Ray`]s, averaged over the samples that hit something.
+    /// They are recorded while tracing the samples of the image, so they match its jitter and stratification.
+    /// The distance is measured along the [`Ray`] (see [`HitRecord::distance`]), so it is independent of how the [`Camera`] scales its directions.
+    /// Pixels where every sample misses have a zero normal and an infinite distance. The buffers are in the same order as the colors of the image.
+    pub fn render_with_aux(mut self) -> (RaytracedImage, Vec<Vector3<f32>>, Vec<f32>) {
+        let world = self.take_world();
+        let depth = self.depth_budget();
+        let mut pixels = vec![
+            (BLACK, Vector3::zeros(), 0.);
+            self.image_height as usize * self.image_width as usize
+        ];
+        self.integrate_with(&mut pixels, |index| {
+            let (mut normal, mut distance, mut hits) = (Vector3::zeros(), 0., 0);
+            let color = self.pixel_color(&world, index, depth, &mut |bounces, hit, _| {
+                if bounces == 0 {
+                    normal += hit.normal;
+                    distance += hit.distance;
+                    hits += 1;
                 }
+            });
+            if hits == 0 {
+                (color, Vector3::zeros(), f32::INFINITY)
+            } else {
+                (color, normal / hits as f32, distance / hits as f32)
+            }
+        });
 
-                if let Some(bar) = &self.progressbar {
-                    bar.inc(1);
-                }
+        let (mut colors, (normals, depths)): (Vec<Color>, (Vec<_>, Vec<_>)) = pixels
+            .into_iter()
+            .map(|(color, normal, distance)| (color, (normal, distance)))
+            .unzip();
+        self.in_pool(|| self.post_process(&mut colors, self.samples().into()));
+
+        let image = RaytracedImage::new(colors, self.image_width, self.image_height)
+            .with_gamma(self.gamma)
+            .with_tone_map(self.tone_map);
+        (image, normals, depths)
+    }
 
-                *color = color
-                    .into_iter()
-                    .map(|color| (color / self.samples_per_pixel as f32).sqrt())
+    /// Quantize the position of `distance` between `near` (0) and `far` (1) into `bands` levels.
+    fn depth_band(distance: f32, near: f32, far: f32, bands: u16) -> f32 {
+        let bands = bands.max(2) as f32;
+        let fraction = ((distance - near) / (far - near)).clamp(0., 1.);
+        (fraction * bands).floor().min(bands - 1.) / (bands - 1.)
+    }
+
+    /// Render the contribution of each light separately.
+    ///
+    /// Every object in `world` whose [`Material`] emits light is treated as a light, identified by its index in `world` (see [`LightId`]).
+    /// Each returned [`RaytracedImage`] contains only the light that was emitted by this object (directly or via any number of bounces).
+    /// The samples are traced like in [`render`](Raytracer::render), so the images and the light of the background add up to it. This does not hold exactly with [median of means](Raytracer::with_median_of_means), which is applied to each light separately.
+    ///
+    /// A [prebuilt BVH](Raytracer::with_prebuilt_bvh) is not used, as its objects cannot be told apart.
+    pub fn render_light_aovs(self) -> HashMap<LightId, RaytracedImage> {
+        let world = Raytracer::build_world(self.world.tagged(), self.sah_bvh);
+        let depth = self.depth_budget();
+        let mut contributions =
+            vec![Vec::new(); self.image_height as usize * self.image_width as usize];
+        self.integrate_with(&mut contributions, |index| {
+            self.light_contributions(&world, index, depth)
+        });
+
+        let mut lights: Vec<usize> = contributions
+            .iter()
+            .flat_map(|pixel| pixel.iter().map(|(light, _)| *light))
+            .collect();
+        lights.sort_unstable();
+        lights.dedup();
+
+        lights
+            .into_iter()
+            .map(|light| {
+                let mut image: Vec<Color> = contributions
+                    .iter()
+                    .map(|pixel| Raytracer::light_sum(pixel, light))
                     .collect();
+                self.post_process(&mut image, self.samples().into());
+                (
+                    LightId(light),
+                    RaytracedImage::new(image, self.image_width, self.image_height)
+                        .with_gamma(self.gamma)
+                        .with_tone_map(self.tone_map),
+                )
+            })
+            .collect()
+    }
+
+    /// Sum up the light emitted by each object over all samples of the pixel at `index` like [`pixel_color`](Raytracer::pixel_color).
+    ///
+    /// `world` has to be [tagged](HittableList::tagged), so that the emitting objects are known.
+    fn light_contributions(
+        &self,
+        world: &HittableListOptions,
+        index: usize,
+        depth: DepthBudget,
+    ) -> Vec<(usize, Color)> {
+        let mut samples = Vec::with_capacity(self.samples().into());
+        self.trace_samples(index, |ray| {
+            let mut sample = Vec::new();
+            self.sample_color(world, ray, depth, &mut |_, hit, emitted| {
+                if let (Some(object), false) = (hit.object, emitted == BLACK) {
+                    sample.push((object, emitted));
+                }
             });
+            samples.push(sample);
+        });
 
-        colors
+        let mut lights: Vec<usize> = samples.iter().flatten().map(|(light, _)| *light).collect();
+        lights.sort_unstable();
+        lights.dedup();
+
+        lights
+            .into_iter()
+            .map(|light| {
+                let colors: Vec<Color> = samples
+                    .iter()
+                    .map(|sample| Raytracer::light_sum(sample, light))
+                    .collect();
+                let sum = match self.median_of_means {
+                    // Scaled back to a sum, as `post_process` divides by the number of samples.
+                    Some(batches) => {
+                        Raytracer::median_of_means(&colors, batches) * colors.len() as f32
+                    }
+                    None => colors.iter().fold(BLACK, |sum, color| sum + *color),
+                };
+                (light, sum)
+            })
+            .collect()
     }
 
-    fn render_multithreaded_without_bvh(self) -> Vec<Color> {
-        let world = HittableListOptions::HittableList(self.world);
+    /// Sum of the light emitted by `light` among `contributions`.
+    fn light_sum(contributions: &[(usize, Color)], light: usize) -> Color {
+        contributions
+            .iter()
+            .filter(|(object, _)| *object == light)
+            .fold(BLACK, |sum, (_, color)| sum + *color)
+    }
 
-        let mut colors = vec![BLACK; self.image_height as usize * self.image_width as usize];
+    /// The [`Material`] at `hit`, replaced by `material_override` unless it emits light, so that a clay render keeps its lights.
+    fn shading_material<'a>(
+        hit: &HitRecord<'a>,
+        material_override: Option<&'a dyn Material>,
+    ) -> &'a dyn Material {
+        match material_override {
+            Some(material) if hit.material().emit(hit) == BLACK => material,
+            _ => hit.material(),
+        }
+    }
+
+    /// The color of a [`Ray`] that does not hit anything.
+    fn background_color(&self, ray: Ray) -> Color {
+        if let Some(BackgroundFn(background)) = &self.background_fn {
+            return background(ray);
+        }
+        // The environment map is y-up like the camera, so undo the rotation into the coordinates of `world`.
+        let ray = match self.up_rotation() {
+            Some(rotation) => ray.transformed(Some(rotation.inverse()), Vector3::zeros()),
+            None => ray,
+        };
+        Raytracer::miss_color(ray, self.background, self.environment.as_ref())
+    }
+
+    /// Sample `environment` in the direction of `ray` or fall back to `background` without one.
+    fn miss_color(ray: Ray, background: Color, environment: Option<&ImageTexture>) -> Color {
+        let Some(environment) = environment else {
+            return background;
+        };
+
+        // Inverse of the mapping of `Camera::equirectangular` looking at -z.
+        let direction = ray.direction().normalize();
+        let longitude = direction.x.atan2(-direction.z);
+        let latitude = direction.y.clamp(-1., 1.).asin();
+        let u = (longitude / PI + 1.) / 2.;
+        let v = latitude / PI + 0.5;
+        environment.color_at(u, v, direction)
+    }
+
+    fn render_multithreaded(mut self) -> Vec<Color> {
+        let world = self.take_world();
+        self.in_pool(|| {
+            let mut colors = self.integrate(&world);
+            self.post_process(&mut colors, self.samples().into());
+            colors
+        })
+    }
+
+    fn render_multithreaded_without_bvh(mut self) -> Vec<Color> {
+        let world = HittableListOptions::HittableList(std::mem::take(&mut self.world));
+        self.in_pool(|| {
+            let mut colors = self.integrate(&world);
+            self.post_process(&mut colors, self.samples().into());
+            colors
+        })
+    }
+
+    /// Render to a [`RaytracedImage`] like [`render`](Raytracer::render) and measure how long each stage took.
+    pub fn render_timed(mut self) -> (RaytracedImage, Timings) {
+        let start = Instant::now();
+        let world = self.take_world();
+        let bvh_build = start.elapsed();
+
+        let start = Instant::now();
+        let mut colors = self.in_pool(|| self.integrate(&world));
+        let integration = start.elapsed();
+
+        let start = Instant::now();
+        self.in_pool(|| self.post_process(&mut colors, self.samples().into()));
+        let post_processing = start.elapsed();
+
+        let timings = Timings {
+            bvh_build,
+            integration,
+            post_processing,
+        };
+        (
+            RaytracedImage::new(colors, self.image_width, self.image_height)
+                .with_gamma(self.gamma)
+                .with_tone_map(self.tone_map),
+            timings,
+        )
+    }
+
+    /// Render one frame for each of `frames` points in time spread evenly over `path`, replacing `camera`.
+    ///
+    /// Returns no frames if `path` has no keyframes.
+    pub fn render_camera_path(self, path: &CameraPath, frames: usize) -> Vec<RaytracedImage> {
+        let Some((time_start, time_end)) = path.time_range() else {
+            return Vec::new();
+        };
+
+        (0..frames)
+            .filter_map(|frame| {
+                let time = if frames > 1 {
+                    time_start + frame as f32 / (frames - 1) as f32 * (time_end - time_start)
+                } else {
+                    time_start
+                };
+                let mut raytracer = self.clone();
+                raytracer.camera = path.camera_at(time)?;
+                Some(raytracer.render())
+            })
+            .collect()
+    }
+
+    /// Start a [`RenderSession`] for refining the image incrementally.
+    ///
+    /// `world` is optimized into a [`Bvh`] once, and `samples_per_pixel` is ignored in favor of [`RenderSession::add_samples`].
+    pub fn into_session(mut self) -> RenderSession {
+        let world = self.take_world();
+        let accumulation = vec![BLACK; self.image_height as usize * self.image_width as usize];
+
+        RenderSession {
+            raytracer: self,
+            world,
+            accumulation,
+            samples: 0,
+        }
+    }
+
+    /// Take `world` out of `self` and optimize it into a [`Bvh`] if possible, or use the prebuilt [`Bvh`] if there is one.
+    fn take_world(&mut self) -> HittableListOptions {
+        let world = std::mem::take(&mut self.world);
+        match &self.prebuilt_bvh {
+            Some(bvh) => HittableListOptions::Bvh(bvh.clone()),
+            None => Raytracer::build_world(world, self.sah_bvh),
+        }
+    }
+
+    /// Optimize `world` into a [`Bvh`] if possible, using the surface area heuristic if `sah` is set.
+    fn build_world(world: HittableList, sah: bool) -> HittableListOptions {
+        match (Bvh::check_hittable_list(&world), sah) {
+            (true, false) => {
+                HittableListOptions::Bvh(Bvh::new(world, 0., 0.).expect("creating BVH"))
+            }
+            (true, true) => {
+                HittableListOptions::Bvh(Bvh::new_sah(world, 0., 0.).expect("creating BVH"))
+            }
+            (false, _) => HittableListOptions::HittableList(world),
+        }
+    }
 
+    /// Sum up the colors of all samples for each pixel.
+    fn integrate(&self, world: &HittableListOptions) -> Vec<Color> {
+        let mut colors = vec![BLACK; self.image_height as usize * self.image_width as usize];
+        self.integrate_into(world, &mut colors);
         colors
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(index, color)| {
-                let mut rng = rand::thread_rng();
-                let i = index % self.image_width as usize;
-                let j = self.image_height as usize - index / self.image_width as usize - 1;
-
-                for _ in 0..self.samples_per_pixel {
-                    let u = (i as f32 + rng.gen::<f32>()) / (self.image_width - 1) as f32;
-                    let v = (j as f32 + rng.gen::<f32>()) / (self.image_height - 1) as f32;
-                    *color += Raytracer::ray_color(
-                        &world,
-                        self.camera.get_ray(u, v),
-                        self.background,
-                        self.max_depth,
-                    );
-                }
+    }
+
+    /// Sum up the colors of all samples for each pixel into `colors`, overwriting its contents.
+    fn integrate_into(&self, world: &HittableListOptions, colors: &mut [Color]) {
+        let depth = self.depth_budget();
+        self.integrate_with(colors, |index| {
+            self.pixel_color(world, index, depth, &mut |_, _, _| {})
+        });
+    }
 
-                if let Some(bar) = &self.progressbar {
-                    bar.inc(1);
+    /// Compute each entry of `pixels` with `pixel` from the index of its pixel, overwriting its contents.
+    ///
+    /// The image is processed in tiles of [`TILE_SIZE`]×[`TILE_SIZE`] pixels, so that neighboring [`Ray`]s traverse the [`Bvh`] on the same thread.
+    fn integrate_with<T: Send>(&self, pixels: &mut [T], pixel: impl Fn(usize) -> T + Sync) {
+        let progress = self.progress();
+        let width = self.image_width as usize;
+        let tiles_x = width.div_ceil(TILE_SIZE);
+
+        self.in_pool(|| {
+            pixels
+                .par_chunks_mut(TILE_SIZE * width)
+                .enumerate()
+                .for_each(|(band, rows)| {
+                    // Split the rows of the band at the borders of the tiles and group the segments by tile.
+                    let mut tiles: Vec<Vec<&mut [T]>> = (0..tiles_x).map(|_| Vec::new()).collect();
+                    for row in rows.chunks_mut(width) {
+                        for (tile, segment) in tiles.iter_mut().zip(row.chunks_mut(TILE_SIZE)) {
+                            tile.push(segment);
+                        }
+                    }
+
+                    tiles
+                        .into_par_iter()
+                        .enumerate()
+                        .for_each(|(tile, segments)| {
+                            let mut pixels = 0;
+                            for (row, segment) in segments.into_iter().enumerate() {
+                                let start = (band * TILE_SIZE + row) * width + tile * TILE_SIZE;
+                                for (offset, value) in segment.iter_mut().enumerate() {
+                                    *value = pixel(start + offset);
+                                }
+                                pixels += segment.len();
+                            }
+                            progress.advance(pixels as u64);
+                        });
+                });
+        });
+    }
+
+    /// Sum up the colors of all samples for the pixel at `index`.
+    ///
+    /// `on_hit` observes the hits of all samples (see [`ray_color`](Raytracer::ray_color)).
+    fn pixel_color(
+        &self,
+        world: &HittableListOptions,
+        index: usize,
+        depth: DepthBudget,
+        on_hit: &mut dyn FnMut(u16, &HitRecord, Color),
+    ) -> Color {
+        match self.median_of_means {
+            Some(batches) => {
+                let mut samples = Vec::with_capacity(self.samples().into());
+                self.trace_samples(index, |ray| {
+                    samples.push(self.sample_color(world, ray, depth, on_hit));
+                });
+                // Scaled back to a sum, as `post_process` divides by the number of samples.
+                Raytracer::median_of_means(&samples, batches) * self.samples() as f32
+            }
+            None => {
+                let mut color = BLACK;
+                self.trace_samples(index, |ray| {
+                    color += self.sample_color(world, ray, depth, on_hit);
+                });
+                color
+            }
+        }
+    }
+
+    /// Call `trace` with the primary [`Ray`] of each sample of the pixel at `index`, with the random numbers seeded for that sample.
+    fn trace_samples(&self, index: usize, mut trace: impl FnMut(Ray)) {
+        let i = index % self.image_width as usize;
+        let j = self.image_height as usize - index / self.image_width as usize - 1;
+
+        for sample in 0..self.samples() {
+            let _seed = self.seed_sample(index, sample);
+            let (du, dv) = self.subpixel_offset(sample, &mut random::rng());
+            let (u, v) = self.viewport_uv(i as f32 + du, j as f32 + dv);
+            trace(self.primary_ray(u, v));
+        }
+    }
+
+    /// Color of a single sample along the primary [`Ray`] `ray` according to the [`ShadingMode`].
+    ///
+    /// `on_hit` observes the hits along the path (see [`ray_color`](Raytracer::ray_color)), which emit no light in the heatmaps.
+    fn sample_color(
+        &self,
+        world: &HittableListOptions,
+        ray: Ray,
+        depth: DepthBudget,
+        on_hit: &mut dyn FnMut(u16, &HitRecord, Color),
+    ) -> Color {
+        match self.shading_mode {
+            ShadingMode::Beauty | ShadingMode::DepthTerminationHeatmap => Raytracer::ray_color(
+                world,
+                ray,
+                &|ray| self.background_color(ray),
+                depth,
+                self.clay_override.as_deref(),
+                self.shading_mode == ShadingMode::DepthTerminationHeatmap,
+                on_hit,
+            ),
+            // The visits are summed up in the red channel and turned into colors by `post_process`.
+            ShadingMode::BvhHeatmap => match world {
+                HittableListOptions::Bvh(bvh) => {
+                    let mut visits = 0;
+                    if let Some(hit) = bvh.hit_counting(ray, 0.001, f32::INFINITY, &mut visits) {
+                        on_hit(0, &hit, BLACK);
+                    }
+                    color![visits as f32, 0., 0.]
                 }
+                HittableListOptions::HittableList(_) => BLACK,
+            },
+            ShadingMode::DepthBands { near, far, bands } => {
+                let level = match world.hit(ray, 0.001, f32::INFINITY) {
+                    Some(hit) => {
+                        on_hit(0, &hit, BLACK);
+                        Raytracer::depth_band(hit.distance, near, far, bands)
+                    }
+                    None => 1.,
+                };
+                // Linearized, so that the bands are evenly spaced gray levels of the output.
+                WHITE * level.powf(self.gamma)
+            }
+        }
+    }
+
+    /// Split `samples` into `batches` batches, average each, and take the median of the averages per channel.
+    ///
+    /// The batch sizes differ by at most one sample. Averages that are not finite (e.g. from a degenerate path producing NaN) are ignored.
+    fn median_of_means(samples: &[Color], batches: u16) -> Color {
+        let batches = (batches.max(1) as usize).min(samples.len());
+        let means: Vec<Color> = (0..batches)
+            .map(|batch| {
+                let batch = &samples
+                    [batch * samples.len() / batches..(batch + 1) * samples.len() / batches];
+                batch.iter().fold(BLACK, |sum, sample| sum + *sample) / batch.len() as f32
+            })
+            .collect();
 
-                *color = color
-                    .into_iter()
-                    .map(|color| (color / self.samples_per_pixel as f32).sqrt())
+        (0..3)
+            .map(|channel| {
+                let mut values: Vec<f32> = means
+                    .iter()
+                    .map(|mean| mean[channel])
+                    .filter(|value| value.is_finite())
                     .collect();
+                if values.is_empty() {
+                    return 0.;
+                }
+                values.sort_by(f32::total_cmp);
+                let mid = values.len() / 2;
+                if values.len() % 2 == 0 {
+                    (values[mid - 1] + values[mid]) / 2.
+                } else {
+                    values[mid]
+                }
+            })
+            .collect()
+    }
+
+    /// Average the summed samples.
+    ///
+    /// For [`ShadingMode::BvhHeatmap`], the average visits are mapped from blue (none) to red (the most in the image) instead.
+    fn post_process(&self, colors: &mut [Color], samples: u32) {
+        if self.shading_mode == ShadingMode::BvhHeatmap {
+            let max_visits = colors.iter().map(|color| color.r()).fold(1., f32::max);
+            colors.par_iter_mut().for_each(|color| {
+                let heat = color.r() / max_visits;
+                *color = color![heat, 0., 1. - heat];
             });
+            return;
+        }
 
         colors
+            .par_iter_mut()
+            .for_each(|color| *color /= samples as f32);
     }
 
     /// Colors the [`Ray`] according to hits.
+    ///
+    /// The path is followed iteratively, adding up the emitted light weighted by the attenuation accumulated so far, until it misses everything or is absorbed.
+    /// `on_hit` is called for every hit with the number of bounces before it and the weighted light emitted there.
+    ///
+    /// With `termination_heatmap` (see [`ShadingMode::DepthTerminationHeatmap`]), the light is ignored and the remaining throughput is returned if the bounce budget ends the path, otherwise black.
     fn ray_color(
         world_option: &HittableListOptions,
-        ray: Ray,
-        background: Color,
-        depth: u16,
+        mut ray: Ray,
+        background: &dyn Fn(Ray) -> Color,
+        mut depth: DepthBudget,
+        material_override: Option<&dyn Material>,
+        termination_heatmap: bool,
+        on_hit: &mut dyn FnMut(u16, &HitRecord, Color),
     ) -> Color {
-        if depth == 0 {
-            return BLACK;
-        }
+        let mut color = BLACK;
 
-        match world_option {
-            HittableListOptions::Bvh(world) => {
-                if let Some(hit) = world.hit(ray, 0.001, f32::INFINITY) {
-                    let emitted = hit.material().emit(hit.u, hit.v, hit.point);
-                    if let Some((scattered, attenuation)) = hit.material().scatter(ray, hit) {
-                        return emitted
-                            + attenuation
-                                * Raytracer::ray_color(
-                                    world_option,
-                                    scattered,
-                                    background,
-                                    depth - 1,
-                                );
-                    }
-                    return emitted;
+        while depth.total > 0 {
+            let hit = world_option.hit(ray, 0.001, f32::INFINITY);
+            let Some(hit) = hit else {
+                if termination_heatmap {
+                    return BLACK;
                 }
-            }
-            HittableListOptions::HittableList(world) => {
-                if let Some(hit) = world.hit(ray, 0.001, f32::INFINITY) {
-                    let emitted = hit.material().emit(hit.u, hit.v, hit.point);
-                    if let Some((scattered, attenuation)) = hit.material().scatter(ray, hit) {
-                        return emitted
-                            + attenuation
-                                * Raytracer::ray_color(
-                                    world_option,
-                                    scattered,
-                                    background,
-                                    depth - 1,
-                                );
-                    }
-                    return emitted;
+                return color + depth.throughput * background(ray);
+            };
+
+            let material = Raytracer::shading_material(&hit, material_override);
+            let emitted = if termination_heatmap {
+                BLACK
+            } else {
+                depth.throughput * material.emit(&hit)
+            };
+            on_hit(depth.bounces, &hit, emitted);
+            color += emitted;
+            let specular = material.is_specular();
+            let Some((scattered, attenuation)) = material.scatter(ray, hit) else {
+                return color;
+            };
+            let Some(bounced) = depth.bounce(specular) else {
+                if termination_heatmap {
+                    return depth.throughput * attenuation;
                 }
-            }
+                return color;
+            };
+            let Some(remaining) = bounced.roulette(attenuation) else {
+                return color;
+            };
+
+            ray = scattered;
+            depth = remaining;
         }
 
-        background
+        if termination_heatmap {
+            depth.throughput
+        } else {
+            color
+        }
     }
 }
 
-/// A result of a raytraced render.
-///
-/// This is a wrapper around the result of [`render`](Raytracer::render) in order to allow for interoperability with different image formats.
-pub struct RaytracedImage {
-    image: Vec<Color>,
-    image_width: u16,
-    image_height: u16,
+impl Default for Raytracer {
+    /// A [`Raytracer`] with the default [`Camera`], a black background, 400×225 pixels, 10 samples per pixel, and a maximum depth of 10.
+    fn default() -> Self {
+        Self::new(Camera::default(), BLACK, 400, 225, 10, 10)
+    }
 }
 
-impl RaytracedImage {
-    /// Save the image.
-    ///
-    /// Defaults to [`image`] as the backend.
-    pub fn save<P: AsRef<Path>>(self, path: P) -> Result<(), ImageError> {
-        let image = self.into_image().expect("creating image");
-        image.save(path)
+/// A function coloring [`Ray`]s that miss everything (see [`Raytracer::with_background_fn`]).
+#[derive(Clone)]
+struct BackgroundFn(Arc<dyn Fn(Ray) -> Color + Send + Sync>);
+
+impl fmt::Debug for BackgroundFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("BackgroundFn")
     }
+}
 
-    /// Convert the image to a [`RgbImage`].
-    ///
+/// A function reporting the progress of a render (see [`Raytracer::with_progress_callback`]).
+#[derive(Clone)]
+struct ProgressFn(Arc<dyn Fn(u64, u64) + Send + Sync>);
+
+impl fmt::Debug for ProgressFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressFn")
+    }
+}
+
+/// Number of completed pixels of a single render, reported to the progress callback of a [`Raytracer`].
+struct Progress<'a> {
+    callback: Option<&'a ProgressFn>,
+    done: AtomicU64,
+    total: u64,
+}
+
+impl Progress<'_> {
+    /// Count `pixels` more pixels as completed.
+    fn advance(&self, pixels: u64) {
+        if let Some(ProgressFn(callback)) = self.callback {
+            let done = self.done.fetch_add(pixels, Ordering::Relaxed) + pixels;
+            callback(done, self.total);
+        }
+    }
+}
+
+/// Settings of a [`Raytracer`] independent of the scene, e.g. for saving and reproducing a render configuration.
+///
+/// With the `serde` feature, this can be serialized and deserialized.
+///
+/// # Fields
+/// See [`Raytracer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenderSettings {
+    pub image_width: u16,
+    pub image_height: u16,
+    pub samples_per_pixel: u16,
+    pub max_depth: u16,
+    pub depth_limits: Option<(u16, u16)>,
+    pub median_of_means: Option<u16>,
+    pub russian_roulette: Option<u16>,
+    pub up_axis: Axis,
+    pub stratified: bool,
+    pub gamma: f32,
+    pub tone_map: ToneMap,
+    pub seed: Option<u64>,
+}
+
+/// What [`Raytracer::render`] and the other renders tracing full paths show in each pixel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShadingMode {
+    /// The lit scene.
+    #[default]
+    Beauty,
+    /// How many [`Bvh`] nodes the primary [`Ray`]s of each pixel visited, from blue (few) to red (the most in the image).
+    ///
+    /// A poorly balanced [`Bvh`] shows up as large red areas. If `world` cannot be optimized into a [`Bvh`], the image is completely blue.
+    BvhHeatmap,
+    /// Where energy is lost to the bounce limit, for debugging dark renders.
+    ///
+    /// Instead of the gathered light, each path contributes the throughput it still carried when it was cut off by `max_depth` (or the [depth limits](Raytracer::with_depth_limits)).
+    /// Paths that escape to the background, are absorbed or are terminated by [Russian roulette](Raytracer::with_russian_roulette) contribute black, so bright regions are those that would benefit from a higher bounce budget.
+    DepthTerminationHeatmap,
+    /// The distance of the first hit in `bands` discrete gray levels, e.g. for stylized fog or cel shading.
+    ///
+    /// Distances up to `near` are black, distances from `far` on (and misses) white.
+    DepthBands { near: f32, far: f32, bands: u16 },
+}
+
+/// A coordinate axis.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Axis {
+    X,
+    #[default]
+    Y,
+    Z,
+}
+
+/// Problem found by [`Raytracer::validate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The image needs to be at least 2×2 pixels.
+    ImageTooSmall { image_width: u16, image_height: u16 },
+    /// `samples_per_pixel` is zero.
+    NoSamples,
+    /// `max_depth` is zero, so no [`Ray`] would be traced.
+    NoBounces,
+    /// The [`Camera`] has no valid viewport, e.g. because `vup` is parallel to the viewing direction.
+    DegenerateCamera,
+    /// The buffer passed to [`Raytracer::render_into`] does not have one entry per pixel.
+    BufferLength { expected: usize, actual: usize },
+    /// The thread pool of [`Raytracer::with_threads`] could not be created, so the global one is used.
+    ThreadPool { threads: usize },
+    /// [`Raytracer::render_into`] was called before [`Raytracer::build_acceleration`].
+    AccelerationNotBuilt,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::ImageTooSmall {
+                image_width,
+                image_height,
+            } => write!(
+                f,
+                "image of {image_width}×{image_height} pixels is smaller than 2×2"
+            ),
+            ValidationIssue::NoSamples => write!(f, "samples_per_pixel is zero"),
+            ValidationIssue::NoBounces => write!(f, "max_depth is zero"),
+            ValidationIssue::DegenerateCamera => {
+                write!(f, "camera has vup parallel to the viewing direction")
+            }
+            ValidationIssue::BufferLength { expected, actual } => {
+                write!(f, "buffer has {actual} entries instead of {expected}")
+            }
+            ValidationIssue::ThreadPool { threads } => {
+                write!(f, "thread pool with {threads} threads could not be created")
+            }
+            ValidationIssue::AccelerationNotBuilt => {
+                write!(f, "build_acceleration was not called before render_into")
+            }
+        }
+    }
+}
+
+/// A render that can be refined incrementally, e.g. for showing the image while it converges.
+///
+/// Created by [`Raytracer::into_session`].
+///
+/// # Fields
+/// - `raytracer`: Settings of the render (without its `world`).
+/// - `world`: `world` of the [`Raytracer`], optimized into a [`Bvh`] if possible.
+/// - `accumulation`: Sum of all samples so far for each pixel.
+/// - `samples`: Number of samples per pixel so far.
+pub struct RenderSession {
+    raytracer: Raytracer,
+    world: HittableListOptions,
+    accumulation: Vec<Color>,
+    samples: u32,
+}
+
+impl RenderSession {
+    /// Trace `samples` more samples for each pixel (rounded down to a perfect square for stratified sampling).
+    ///
+    /// With a seed, the samples continue where the previous call stopped, so e.g. `add_samples(5)` twice gives the same image as `add_samples(10)` once.
+    /// This does not hold for stratified sampling and median of means, which depend on the number of samples of each call.
+    pub fn add_samples(&mut self, samples: u16) {
+        self.raytracer.samples_per_pixel = samples;
+        self.raytracer.first_sample = self.samples;
+        let (raytracer, world, accumulation) =
+            (&self.raytracer, &self.world, &mut self.accumulation);
+        raytracer.in_pool(|| {
+            let colors = raytracer.integrate(world);
+            accumulation
+                .par_iter_mut()
+                .zip(colors)
+                .for_each(|(sum, color)| *sum += color);
+        });
+        self.samples += u32::from(self.raytracer.samples());
+    }
+
+    /// Number of samples per pixel so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// The image averaged over all samples so far.
+    pub fn current_image(&self) -> RaytracedImage {
+        let mut colors = self.accumulation.clone();
+        self.raytracer.in_pool(|| {
+            self.raytracer
+                .post_process(&mut colors, self.samples.max(1))
+        });
+        RaytracedImage::new(
+            colors,
+            self.raytracer.image_width,
+            self.raytracer.image_height,
+        )
+        .with_gamma(self.raytracer.gamma)
+        .with_tone_map(self.raytracer.tone_map)
+    }
+}
+
+/// Time spent in each stage of [`Raytracer::render_timed`].
+///
+/// # Fields
+/// - `bvh_build`: Building the [`Bvh`] (or deciding against it).
+/// - `integration`: Tracing all [`Ray`]s.
+/// - `post_processing`: Averaging the samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub bvh_build: Duration,
+    pub integration: Duration,
+    pub post_processing: Duration,
+}
+
+impl Timings {
+    /// Total time spent in all stages.
+    pub fn total(&self) -> Duration {
+        self.bvh_build + self.integration + self.post_processing
+    }
+}
+
+/// Identifier of a light-emitting object, given by its index in [`Raytracer::world`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LightId(pub usize);
+
+/// Remaining number of bounces of a [`Ray`].
+///
+/// # Fields
+/// - `total`: Remaining number of [`Ray`]s regardless of the material.
+/// - `diffuse`: Remaining bounces off diffuse materials.
+/// - `specular`: Remaining bounces off specular materials (see [`Material::is_specular`](crate::materials::Material::is_specular)).
+/// - `bounces`: Number of bounces so far.
+/// - `throughput`: Attenuation accumulated along the path so far, including the weights of Russian roulette.
+/// - `russian_roulette`: Optional number of bounces after which [`roulette`](DepthBudget::roulette) may terminate the path.
+#[derive(Clone, Copy, Debug)]
+struct DepthBudget {
+    total: u16,
+    diffuse: u16,
+    specular: u16,
+    bounces: u16,
+    throughput: Color,
+    russian_roulette: Option<u16>,
+}
+
+impl DepthBudget {
+    /// Spend one bounce off a diffuse or specular material.
+    ///
+    /// Returns [`None`] if the corresponding budget is exhausted.
+    fn bounce(self, specular: bool) -> Option<Self> {
+        let mut depth = self;
+        depth.total -= 1;
+        depth.bounces = depth.bounces.saturating_add(1);
+        if specular {
+            depth.specular = depth.specular.checked_sub(1)?;
+        } else {
+            depth.diffuse = depth.diffuse.checked_sub(1)?;
+        }
+        Some(depth)
+    }
+
+    /// Apply the `attenuation` of the last bounce and play Russian roulette if enabled.
+    ///
+    /// Returns [`None`] if the path is terminated. The `throughput` of survivors is divided by the probability they survived with.
+    fn roulette(mut self, attenuation: Color) -> Option<Self> {
+        self.throughput *= attenuation;
+        match self.russian_roulette {
+            Some(min_bounces) if self.bounces > min_bounces => {
+                let survival = self.throughput.luminance().clamp(0.05, 1.);
+                if random::rng().gen::<f32>() >= survival {
+                    return None;
+                }
+                self.throughput /= survival;
+                Some(self)
+            }
+            _ => Some(self),
+        }
+    }
+}
+
+/// A result of a raytraced render.
+///
+/// This is a wrapper around the result of [`render`](Raytracer::render) in order to allow for interoperability with different image formats.
+/// The colors are stored linearly and only tone mapped, graded and gamma-corrected when converting the image.
+pub struct RaytracedImage {
+    image: Vec<Color>,
+    image_width: u16,
+    image_height: u16,
+    gamut_mapping: bool,
+    dither: bool,
+    tone_map: ToneMap,
+    lut: Option<Lut3d>,
+    gamma: f32,
+}
+
+/// 8×8 Bayer matrix for ordered dithering.
+const BAYER_MATRIX: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+impl RaytracedImage {
+    pub(crate) fn new(image: Vec<Color>, image_width: u16, image_height: u16) -> Self {
+        Self {
+            image,
+            image_width,
+            image_height,
+            gamut_mapping: false,
+            dither: false,
+            tone_map: ToneMap::None,
+            lut: None,
+            gamma: 2.,
+        }
+    }
+
+    /// Consume `self` and fit out-of-gamut colors by desaturating them (see [`Color::desaturate_to_fit`]) instead of clamping each channel when converting the image.
+    pub fn with_gamut_mapping(mut self) -> Self {
+        self.gamut_mapping = true;
+        self
+    }
+
+    /// Consume `self` and apply an ordered dither before quantizing to 8 bits when converting the image.
+    ///
+    /// This breaks up banding in smooth gradients (e.g. the sky).
+    pub fn with_dither(mut self) -> Self {
+        self.dither = true;
+        self
+    }
+
+    /// Consume `self` and compress bright colors with `tone_map` instead of letting them clip when converting the image.
+    ///
+    /// The curve is applied to the linear colors, i.e. before gamma correction.
+    pub fn with_tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Consume `self` and encode the image with `gamma` instead of the gamma of the [`Raytracer`] (see [`Raytracer::with_gamma`]) when converting the image.
+    pub fn with_gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Cut out the pixels with `x0 <= x < x1` and `y0 <= y < y1`, where (0, 0) is the top left corner.
+    ///
+    /// Returns [`None`] if the region is empty or exceeds the image.
+    pub fn crop(&self, x0: u16, y0: u16, x1: u16, y1: u16) -> Option<RaytracedImage> {
+        if x0 >= x1 || y0 >= y1 || x1 > self.image_width || y1 > self.image_height {
+            return None;
+        }
+
+        let image = (y0..y1)
+            .flat_map(|y| {
+                let row = y as usize * self.image_width as usize;
+                self.image[row + x0 as usize..row + x1 as usize]
+                    .iter()
+                    .copied()
+            })
+            .collect();
+
+        Some(RaytracedImage {
+            image,
+            image_width: x1 - x0,
+            image_height: y1 - y0,
+            gamut_mapping: self.gamut_mapping,
+            dither: self.dither,
+            tone_map: self.tone_map,
+            lut: self.lut.clone(),
+            gamma: self.gamma,
+        })
+    }
+
+    /// Save the image.
+    ///
+    /// Defaults to [`image`] as the backend. The format is inferred from the extension of `path`.
+    /// If the extension is not recognized, an [`ImageError::Unsupported`] naming it is returned before anything is encoded.
+    pub fn save<P: AsRef<Path>>(self, path: P) -> Result<(), ImageError> {
+        let format = ImageFormat::from_path(&path)?;
+        self.save_as(path, format)
+    }
+
+    /// Save the image in `format` regardless of the extension of `path`.
+    pub fn save_as<P: AsRef<Path>>(self, path: P, format: ImageFormat) -> Result<(), ImageError> {
+        let image = self
+            .into_image()
+            .ok_or_else(RaytracedImage::size_mismatch)?;
+        image.save_with_format(path, format)
+    }
+
+    /// Save the linear colors as 32-bit floating point OpenEXR, e.g. for grading in compositing software.
+    ///
+    /// Unlike [`save`](RaytracedImage::save), the output is linear, i.e. not gamma-corrected, and keeps colors brighter than 1.
+    /// Tone mapping, LUT, gamut mapping and dithering are not applied either.
+    pub fn save_exr<P: AsRef<Path>>(&self, path: P) -> Result<(), ImageError> {
+        let image_width = self.image_width as usize;
+        if self.image.len() < image_width * self.image_height as usize {
+            return Err(RaytracedImage::size_mismatch());
+        }
+
+        exr::prelude::write_rgb_file(path, image_width, self.image_height.into(), |x, y| {
+            let color = self.image[y * image_width + x];
+            (color.r(), color.g(), color.b())
+        })
+        .map_err(|error| match error {
+            exr::error::Error::Io(error) => ImageError::IoError(error),
+            error => ImageError::Encoding(EncodingError::new(
+                ImageFormatHint::Exact(ImageFormat::OpenExr),
+                error,
+            )),
+        })
+    }
+
+    /// Convert the image to a [`RgbImage`].
+    ///
     /// Returns [`None`] if the [`Vec`] of [`Color`]s is not long enough.
     pub fn into_image(self) -> Option<RgbImage> {
-        let image: Vec<u8> = self
-            .image
-            .iter()
-            .flat_map(|color| Into::<[u8; 3]>::into(*color))
-            .collect();
-        RgbImage::from_vec(self.image_width.into(), self.image_height.into(), image)
+        let image_width = self.image_width;
+        let image_height = self.image_height;
+        let image = RaytracedImage::colors_to_bytes(&self.into_output_colors());
+        RgbImage::from_vec(image_width.into(), image_height.into(), image)
+    }
+
+    /// The error for when the [`Color`]s do not fill the image.
+    fn size_mismatch() -> ImageError {
+        ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::DimensionMismatch,
+        ))
+    }
+
+    /// Encode the image as PNG in memory.
+    ///
+    /// This allows serving or inspecting the image without touching the filesystem.
+    pub fn to_png_bytes(self) -> Result<Vec<u8>, ImageError> {
+        let image = self
+            .into_image()
+            .ok_or_else(RaytracedImage::size_mismatch)?;
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageOutputFormat::Png)?;
+        Ok(bytes.into_inner())
+    }
+
+    /// Grade every pixel with a 3D color lookup table when converting the image.
+    ///
+    /// The [`Lut3d`] is applied after the [`ToneMap`] and before gamma correction.
+    pub fn apply_lut(&mut self, lut: &Lut3d) {
+        self.lut = Some(lut.clone());
+    }
+
+    /// Consume `self` and grade every pixel with `lut` (see [`apply_lut`](RaytracedImage::apply_lut)).
+    pub fn with_lut(mut self, lut: Lut3d) -> Self {
+        self.lut = Some(lut);
+        self
     }
 
     /// Convert the image to a [`PPM`].
     ///
     /// Saving the image as an [`image`](RaytracedImage::into_image) should be preferred as other image formats are much smaller and the resulting [`RgbImage`] has more possible functions.
     pub fn into_ppm(self) -> PPM {
-        PPM::new(self.image, self.image_width, self.image_height)
+        let image_width = self.image_width;
+        let image_height = self.image_height;
+        PPM::new(self.into_output_colors(), image_width, image_height)
+    }
+
+    /// Apply all conversions that happen right before quantization.
+    fn into_output_colors(self) -> Vec<Color> {
+        let mut colors = self.image;
+        colors.par_iter_mut().for_each(|color| {
+            let mut graded = self.tone_map.apply(*color);
+            if let Some(lut) = &self.lut {
+                graded = lut.apply(graded);
+            }
+            *color = graded
+                .into_iter()
+                .map(|c| RaytracedImage::gamma_correct(c.max(0.), self.gamma))
+                .collect();
+        });
+        if self.gamut_mapping {
+            colors
+                .par_iter_mut()
+                .for_each(|color| *color = color.desaturate_to_fit());
+        }
+        if self.dither {
+            let image_width = self.image_width as usize;
+            colors
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(index, color)| {
+                    let threshold =
+                        BAYER_MATRIX[index / image_width % 8][index % image_width % 8] as f32;
+                    // Shift by up to half a quantization step in either direction.
+                    let offset = ((threshold + 0.5) / 64. - 0.5) / 256.;
+                    *color = color.into_iter().map(|channel| channel + offset).collect();
+                });
+        }
+        colors
+    }
+
+    /// Encode a linear channel with `gamma`.
+    ///
+    /// The default gamma of 2 uses [`f32::sqrt`], which is exact, to keep the output identical to before gamma became configurable.
+    fn gamma_correct(value: f32, gamma: f32) -> f32 {
+        if gamma == 2. {
+            value.sqrt()
+        } else {
+            value.powf(1. / gamma)
+        }
+    }
+
+    /// Quantize [`Color`]s to interleaved RGB bytes in parallel.
+    fn colors_to_bytes(colors: &[Color]) -> Vec<u8> {
+        colors
+            .par_iter()
+            .flat_map_iter(|color| Into::<[u8; 3]>::into(*color))
+            .collect()
+    }
+}
+
+impl From<PPM> for RaytracedImage {
+    fn from(ppm: PPM) -> Self {
+        // PPMs are encoded with the default gamma of 2.
+        let colors = ppm.colors.into_iter().map(|color| color * color).collect();
+        Self::new(colors, ppm.image_width, ppm.image_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{Dielectric, DiffuseLight, Lambertian, Metal, Sheen, UvDebugMaterial};
+    use crate::shapes::{InfinitePlane, Rectangle, Sphere};
+    use crate::textures::Filter;
+
+    /// Color of the sky in tests expecting a constant image, which is `color![0.5, 0.7, 0.9]` after gamma correction.
+    fn sky() -> Color {
+        color![0.25, 0.49, 0.81]
+    }
+
+    /// A two-sided light of the color of the [`sky`] around the camera, so that every sample sees exactly the sky.
+    fn sky_light() -> impl Hittable {
+        Sphere::new(
+            vector![0., 0., 0.],
+            100.,
+            DiffuseLight::solid_color(sky()).with_two_sided(),
+        )
+    }
+
+    /// Whether `color` is the [`sky`].
+    fn is_sky(color: &Color) -> bool {
+        (*color - sky()).into_iter().all(|c| c.abs() < 1e-5)
+    }
+
+    /// A seeded red sphere on a gray ground under a blue sky, whose pixels vary from sample to sample.
+    fn noisy_scene(samples_per_pixel: u16) -> Raytracer {
+        let mut raytracer = Raytracer::new(
+            Camera::default(),
+            color![0.5, 0.7, 1.],
+            8,
+            6,
+            samples_per_pixel,
+            10,
+        )
+        .with_seed(7);
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., -1.],
+            0.5,
+            Lambertian::solid_color(color![0.8, 0.3, 0.3]),
+        ));
+        raytracer.world.push(Sphere::new(
+            vector![0., -100.5, -1.],
+            100.,
+            Lambertian::solid_color(color![0.5, 0.5, 0.5]),
+        ));
+        raytracer
+    }
+
+    #[test]
+    fn depth_limits_nested_glass() {
+        let mut raytracer = Raytracer::new(Camera::default(), WHITE, 5, 5, 100, 50);
+        for radius in [1.5, 1., 0.5] {
+            raytracer.world.push(Sphere::new(
+                vector![0., 0., -5.],
+                radius,
+                Dielectric::new(1.5),
+            ));
+        }
+
+        let center = |raytracer: Raytracer| raytracer.render().image[12];
+
+        let deep = center(raytracer.clone().with_depth_limits(0, 20));
+        let shallow = center(raytracer.with_depth_limits(20, 2));
+
+        assert!(deep.r() > 0.8);
+        assert!(shallow.r() < 0.5);
+    }
+
+    #[test]
+    fn render_timed() {
+        let mut raytracer = Raytracer::new(Camera::default(), WHITE, 64, 36, 16, 10);
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., -2.],
+            0.5,
+            Lambertian::solid_color(color![0.5, 0.5, 0.5]),
+        ));
+
+        let start = Instant::now();
+        let (image, timings) = raytracer.render_timed();
+        let wall_time = start.elapsed();
+
+        assert_eq!(image.image.len(), 64 * 36);
+        assert!(timings.total() <= wall_time);
+        assert!(timings.total() >= wall_time.mul_f32(0.5));
+    }
+
+    #[test]
+    fn light_aovs_sum_to_beauty() {
+        let scene = |left: Color, right: Color| {
+            let mut raytracer =
+                Raytracer::new(Camera::default(), color![0.2, 0.3, 0.4], 8, 6, 16, 10)
+                    .with_stratified_sampling()
+                    .with_russian_roulette(1)
+                    .with_seed(3);
+            raytracer.world.push(Rectangle::xz(
+                vector![0., -0.5, -3.],
+                4.,
+                4.,
+                Lambertian::solid_color(color![0.8, 0.8, 0.8]),
+            ));
+            raytracer.world.push(Rectangle::xy(
+                vector![-0.5, 0., -4.],
+                1.,
+                1.,
+                DiffuseLight::solid_color(left),
+            ));
+            raytracer.world.push(Rectangle::xy(
+                vector![0.5, 0., -4.],
+                1.,
+                1.,
+                DiffuseLight::solid_color(right),
+            ));
+            raytracer
+        };
+        let (left, right) = (color![4., 1., 1.], color![1., 1., 4.]);
+
+        let beauty = scene(left, right).render();
+        // Lights that emit nothing follow the same paths, so only the light of the background is left.
+        let background = scene(BLACK, BLACK).render();
+        let aovs = scene(left, right).render_light_aovs();
+
+        assert_eq!(aovs.len(), 2);
+        assert!(aovs.contains_key(&LightId(1)) && aovs.contains_key(&LightId(2)));
+        assert!(aovs
+            .values()
+            .all(|aov| aov.image.iter().any(|color| *color != BLACK)));
+        for (index, color) in beauty.image.iter().enumerate() {
+            let sum = aovs
+                .values()
+                .fold(background.image[index], |sum, aov| sum + aov.image[index]);
+            assert!(
+                (*color - sum).into_iter().all(|c| c.abs() < 1e-4),
+                "{color:?} != {sum:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sheen_brightens_edges() {
+        let render = |material: Arc<dyn Material>,
+                      background: Box<dyn Fn(Ray) -> Color + Send + Sync>| {
+            let mut raytracer = Raytracer::new(Camera::default(), BLACK, 64, 36, 16, 2)
+                .with_seed(4)
+                .with_background_fn(background);
+            raytracer
+                .world
+                .push(Sphere::new(vector![0., 0., -3.], 1., material));
+            raytracer.render_with_aux()
+        };
+        let color = color![0.6, 0.3, 0.2];
+        let uniform = || Box::new(|_| color![0.5, 0.5, 0.5]);
+        // Light only from behind the camera, which the fibers reflect back towards it at grazing angles.
+        let frontal = || Box::new(|ray: Ray| WHITE * ray.direction().normalize().z.max(0.).powi(4));
+        let (plain, normals, _) = render(Arc::new(Lambertian::solid_color(color)), uniform());
+        let (lambertian, _, _) = render(Arc::new(Lambertian::solid_color(color)), frontal());
+        let (sheen, _, _) = render(Arc::new(Sheen::solid_color(color, 1.)), frontal());
+
+        // Under the uniform sky, the Lambertian sphere has the same color everywhere, so this finds the pixels it covers completely.
+        let covered = (color * 0.5).luminance();
+        let brightness = |image: &RaytracedImage, edge: bool| -> f32 {
+            image
+                .image
+                .iter()
+                .zip(&plain.image)
+                .zip(&normals)
+                .filter(|((_, plain), normal)| {
+                    (plain.luminance() - covered).abs() < 1e-4
+                        && if edge {
+                            normal.z < 0.5
+                        } else {
+                            normal.z > 0.95
+                        }
+                })
+                .map(|((color, _), _)| color.luminance())
+                .sum()
+        };
+        assert!(brightness(&sheen, true) > 1.3 * brightness(&lambertian, true));
+        assert!(brightness(&sheen, false) < 1.1 * brightness(&lambertian, false));
+    }
+
+    #[test]
+    fn clay_override() {
+        let mut raytracer = Raytracer::new(Camera::default(), WHITE, 8, 6, 4, 10);
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., -101.],
+            100.,
+            Metal::solid_color(color![0.8, 0.8, 0.8], 0.2),
+        ));
+        raytracer
+            .world
+            .push(Sphere::new(vector![0., 0., -3.], 0.5, Dielectric::new(1.5)));
+        raytracer.world.push(Sphere::new(
+            vector![0.5, 0., -2.],
+            0.2,
+            Lambertian::solid_color(color![0., 0., 1.]),
+        ));
+
+        let image = raytracer
+            .with_clay_override(Lambertian::solid_color(color![1., 0., 0.]))
+            .render();
+
+        for color in image.image {
+            assert!(color.r() > 0.);
+            assert_eq!(color.g(), 0.);
+            assert_eq!(color.b(), 0.);
+        }
+    }
+
+    #[test]
+    fn clay_override_keeps_lights() {
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 8, 6, 4, 10);
+        raytracer.world.push(sky_light());
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., -3.],
+            1.,
+            Metal::solid_color(color![0.8, 0.8, 0.8], 0.2),
+        ));
+
+        let image = raytracer
+            .with_clay_override(Lambertian::solid_color(color![1., 0., 0.]))
+            .render();
+
+        // The light is seen directly around the sphere and lights the clay of the sphere.
+        assert!(image.image.iter().any(is_sky));
+        assert!(image.image.iter().all(|color| color.r() > 0.));
+        assert!(image
+            .image
+            .iter()
+            .any(|color| color.g() == 0. && color.b() == 0.));
+    }
+
+    #[test]
+    fn uv_debug_rectangle() {
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 8, 8, 4, 10);
+        raytracer.world.push(Rectangle::xy(
+            vector![0., 0., -1.],
+            2.,
+            1.,
+            UvDebugMaterial::new(),
+        ));
+
+        let image = raytracer.render();
+        let pixel = |i: usize, j: usize| image.image[j * 8 + i];
+
+        for row in 0..8 {
+            assert!(pixel(0, row).r() < pixel(7, row).r());
+            assert_eq!(pixel(0, row).b(), 0.);
+        }
+        for column in 0..8 {
+            assert!(pixel(column, 7).g() < pixel(column, 0).g());
+        }
+    }
+
+    #[test]
+    fn default_render() {
+        let mut raytracer = Raytracer::default();
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., -1.],
+            0.5,
+            Lambertian::default(),
+        ));
+
+        let image = raytracer.render();
+
+        assert_eq!((image.image_width, image.image_height), (400, 225));
+        assert_eq!(image.image.len(), 400 * 225);
+    }
+
+    #[test]
+    fn bvh_heatmap() {
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 8, 6, 1, 10);
+        for x in [-0.2, 0., 0.2] {
+            raytracer
+                .world
+                .push(Sphere::new(vector![x, 0., -3.], 0.1, Lambertian::default()));
+        }
+
+        let image = raytracer
+            .with_shading_mode(ShadingMode::BvhHeatmap)
+            .render();
+
+        assert_eq!(image.image.len(), 48);
+        assert!(image.image.iter().any(|color| color.r() == 1.));
+        assert!(image.image.iter().all(|color| color.g() == 0.));
+    }
+
+    #[test]
+    fn render_into() {
+        let mut raytracer = noisy_scene(2);
+
+        let mut buffer = vec![WHITE; 48];
+        assert_eq!(
+            raytracer.render_into(&mut buffer),
+            Err(ValidationIssue::AccelerationNotBuilt)
+        );
+        assert!(buffer.iter().all(|color| *color == WHITE));
+
+        raytracer.build_acceleration();
+        raytracer.render_into(&mut buffer).unwrap();
+        raytracer.render_into(&mut buffer).unwrap();
+        let image = raytracer.clone().render();
+        assert_eq!(buffer, image.image);
+
+        // After a change to `world`, rebuilding renders the new scene, i.e. only the background.
+        raytracer.world = HittableList::default();
+        raytracer.build_acceleration();
+        raytracer.render_into(&mut buffer).unwrap();
+        assert_ne!(buffer, image.image);
+        assert!(buffer.iter().all(|color| *color == buffer[0]));
+
+        // Changing how the BVH is built discards it.
+        let raytracer = raytracer.with_sah_bvh();
+        assert_eq!(
+            raytracer.render_into(&mut buffer),
+            Err(ValidationIssue::AccelerationNotBuilt)
+        );
+
+        assert_eq!(
+            raytracer.render_into(&mut [BLACK; 47]),
+            Err(ValidationIssue::BufferLength {
+                expected: 48,
+                actual: 47
+            })
+        );
+    }
+
+    #[test]
+    fn median_of_means_rejects_outlier() {
+        let mut samples = vec![color![0.5, 0.5, 0.5]; 64];
+        samples[17] = color![1e6, 1e6, 1e6];
+
+        let estimate = Raytracer::median_of_means(&samples, 8);
+
+        assert!((estimate - color![0.5, 0.5, 0.5])
+            .into_iter()
+            .all(|c| c.abs() < 1e-4));
+
+        // A NaN sample only spoils its own batch.
+        samples[17] = color![f32::NAN, 0.5, 0.5];
+        let estimate = Raytracer::median_of_means(&samples, 8);
+        assert!((estimate - color![0.5, 0.5, 0.5])
+            .into_iter()
+            .all(|c| c.abs() < 1e-4));
+
+        // 10 samples in 4 batches are split 2, 3, 2, 3, so the median of the means is 1.
+        let samples: Vec<Color> = [0., 0., 1., 1., 1., 1., 1., 4., 4., 4.]
+            .into_iter()
+            .map(|value| color![value, value, value])
+            .collect();
+        let estimate = Raytracer::median_of_means(&samples, 4);
+        assert!((estimate - color![1., 1., 1.])
+            .into_iter()
+            .all(|c| c.abs() < 1e-5));
+        assert_eq!(Raytracer::median_of_means(&[], 4), BLACK);
+    }
+
+    #[test]
+    fn render_session() {
+        let mut incremental = noisy_scene(1).into_session();
+        incremental.add_samples(5);
+        incremental.add_samples(5);
+        let mut once = noisy_scene(1).into_session();
+        once.add_samples(10);
+        let mut reseeded = noisy_scene(1).with_seed(8).into_session();
+        reseeded.add_samples(10);
+
+        assert_eq!(incremental.samples(), 10);
+        let (incremental, once) = (incremental.current_image(), once.current_image());
+        for (a, b) in incremental.image.iter().zip(&once.image) {
+            assert!((*a - *b).into_iter().all(|c| c.abs() < 1e-5));
+        }
+        // The pixels are noisy, so other random numbers would show.
+        assert!(once
+            .image
+            .iter()
+            .zip(&reseeded.current_image().image)
+            .any(|(a, b)| (*a - *b).into_iter().any(|c| c.abs() > 1e-3)));
+    }
+
+    #[test]
+    fn camera_path_frames() {
+        let path = CameraPath::new()
+            .with_keyframe(0., Camera::default())
+            .with_keyframe(1., Camera::default());
+        let raytracer = Raytracer::new(Camera::default(), WHITE, 4, 3, 1, 10);
+
+        let frames = raytracer.render_camera_path(&path, 3);
+
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|frame| frame.image.len() == 12));
+    }
+
+    #[test]
+    fn z_up_matches_y_up() {
+        let red = DiffuseLight::solid_color(color![1., 0., 0.]);
+        let blue = DiffuseLight::solid_color(color![0., 0., 1.]);
+
+        let mut y_up = Raytracer::new(Camera::default(), BLACK, 8, 6, 1, 10);
+        y_up.world
+            .push(Sphere::new(vector![0., 0.5, -3.], 0.4, red.clone()));
+        y_up.world
+            .push(Sphere::new(vector![0.5, 0., -3.], 0.4, blue.clone()));
+
+        // (x, y, z) in y-up coordinates is (x, -z, y) in z-up coordinates.
+        let mut z_up = Raytracer::new(Camera::default(), BLACK, 8, 6, 1, 10).with_up_axis(Axis::Z);
+        z_up.world.push(Sphere::new(vector![0., 3., 0.5], 0.4, red));
+        z_up.world
+            .push(Sphere::new(vector![0.5, 3., 0.], 0.4, blue));
+
+        let y_world = Raytracer::build_world(y_up.world.clone(), false);
+        let z_world = Raytracer::build_world(z_up.world.clone(), false);
+        let mut hits = 0;
+        for i in 0..20 {
+            for j in 0..20 {
+                let (u, v) = (i as f32 / 19., j as f32 / 19.);
+                let color_y = Raytracer::ray_color(
+                    &y_world,
+                    y_up.primary_ray(u, v),
+                    &|_| BLACK,
+                    y_up.depth_budget(),
+                    None,
+                    false,
+                    &mut |_, _, _| {},
+                );
+                let color_z = Raytracer::ray_color(
+                    &z_world,
+                    z_up.primary_ray(u, v),
+                    &|_| BLACK,
+                    z_up.depth_budget(),
+                    None,
+                    false,
+                    &mut |_, _, _| {},
+                );
+                assert_eq!(color_y, color_z);
+                if color_y != BLACK {
+                    hits += 1;
+                }
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn depth_bands() {
+        assert_eq!(Raytracer::depth_band(0.5, 1., 5., 4), 0.);
+        assert_eq!(Raytracer::depth_band(2.5, 1., 5., 4), 1. / 3.);
+        assert_eq!(Raytracer::depth_band(10., 1., 5., 4), 1.);
+
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 8, 6, 1, 10);
+        raytracer.world.push(Sphere::new(
+            vector![-1., 0., -3.],
+            1.,
+            Lambertian::default(),
+        ));
+        raytracer.world.push(Sphere::new(
+            vector![4., 0., -12.],
+            4.,
+            Lambertian::default(),
+        ));
+
+        let image = raytracer
+            .with_shading_mode(ShadingMode::DepthBands {
+                near: 1.,
+                far: 15.,
+                bands: 4,
+            })
+            .render();
+
+        // Near hits on the left, far hits on the right.
+        let (left, right) = (image.image[3 * 8], image.image[3 * 8 + 7]);
+        assert!(left.r() < right.r());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        // A small light over a diffuse floor (z-up), so the image is noisy unless seeded.
+        let scene = |camera| {
+            let mut raytracer = Raytracer::new(camera, BLACK, 8, 6, 1, 10);
+            raytracer.world.push(Sphere::new(
+                vector![0., 1., -100.5],
+                100.,
+                Lambertian::solid_color(color![0.5, 0.5, 0.5]),
+            ));
+            raytracer.world.push(Sphere::new(
+                vector![0., 1., 1.],
+                0.3,
+                DiffuseLight::solid_color(color![8., 8., 8.]),
+            ));
+            raytracer
+        };
+
+        let original = scene(Camera::default()).with_settings(RenderSettings {
+            image_width: 12,
+            image_height: 9,
+            samples_per_pixel: 4,
+            max_depth: 5,
+            depth_limits: Some((3, 7)),
+            median_of_means: Some(2),
+            russian_roulette: Some(3),
+            up_axis: Axis::Z,
+            stratified: true,
+            gamma: 2.2,
+            tone_map: ToneMap::Reinhard,
+            seed: Some(42),
+        });
+        let settings = original.settings();
+        let replayed = scene(Camera::default()).with_settings(settings);
+        let reseeded = scene(Camera::default()).with_settings(RenderSettings {
+            seed: Some(43),
+            ..settings
+        });
+
+        assert_eq!(replayed.settings(), settings);
+        let original = original.render().into_image().unwrap().into_raw();
+        assert_eq!(original, replayed.render().into_image().unwrap().into_raw());
+        assert_ne!(original, reseeded.render().into_image().unwrap().into_raw());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn settings_serde() {
+        let settings = Raytracer::default().with_depth_limits(3, 7).settings();
+
+        let json = serde_json::to_string(&settings).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<RenderSettings>(&json).unwrap(),
+            settings
+        );
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(Raytracer::default().validate(), Ok(()));
+
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 400, 225, 0, 10);
+        assert_eq!(raytracer.validate(), Err(vec![ValidationIssue::NoSamples]));
+
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 0, 1, 0, 0);
+        assert_eq!(raytracer.validate().unwrap_err().len(), 3);
+
+        // A thread pool that could not be created falls back to the global one.
+        let raytracer = Raytracer {
+            failed_threads: Some(4),
+            ..Raytracer::default()
+        };
+        assert_eq!(
+            raytracer.validate(),
+            Err(vec![ValidationIssue::ThreadPool { threads: 4 }])
+        );
+        assert_eq!(raytracer.with_threads(2).validate(), Ok(()));
+    }
+
+    #[test]
+    fn background_only() {
+        let mut raytracer = Raytracer::new(Camera::default(), sky(), 4, 3, 4, 10);
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., -1.],
+            100.,
+            Dielectric::new(1.5),
+        ));
+
+        let image = raytracer.render_background_only();
+
+        assert_eq!(image.image.len(), 12);
+        assert!(image.image.iter().all(is_sky));
+    }
+
+    #[test]
+    fn background_only_matches_empty_render() {
+        // The backplate uses the stratified samples and the tone map of a render.
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 8, 6, 10, 10)
+            .with_stratified_sampling()
+            .with_seed(3)
+            .with_tone_map(ToneMap::Reinhard)
+            .with_background_fn(Box::new(|ray| {
+                let direction = ray.direction().normalize();
+                color![4. * direction.x.abs(), direction.y.abs(), 0.]
+            }));
+
+        let render = raytracer.clone().render();
+        let background = raytracer.render_background_only();
+        assert_eq!(background.tone_map, ToneMap::Reinhard);
+        assert_eq!(background.image, render.image);
+        assert_eq!(background.into_image(), render.into_image());
+    }
+
+    #[test]
+    fn environment() {
+        // Red on the left (-x), blue on the right (+x) half of the map.
+        let map = RgbImage::from_fn(2, 1, |i, _| {
+            if i == 0 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let environment = ImageTexture::new(map);
+        let miss = |direction| {
+            Raytracer::miss_color(
+                Ray::new(Vector3::zeros(), direction),
+                BLACK,
+                Some(&environment),
+            )
+        };
+        assert_eq!(miss(vector![-1., 0., 0.]), color![1., 0., 0.]);
+        assert_eq!(miss(vector![1., 0.2, 0.]), color![0., 0., 1.]);
+
+        let background = sky();
+        let ray = Ray::new(Vector3::zeros(), vector![0., 0., -1.]);
+        assert_eq!(Raytracer::miss_color(ray, background, None), background);
+
+        // Rays missing everything see the environment instead of the background.
+        let uniform = ImageTexture::new(RgbImage::from_pixel(2, 1, image::Rgb([255, 0, 0])));
+        let raytracer =
+            Raytracer::new(Camera::default(), background, 4, 3, 4, 10).with_environment(uniform);
+        let image = raytracer.render();
+        assert!(image.image.iter().all(|color| *color == color![1., 0., 0.]));
+    }
+
+    #[test]
+    fn equirectangular_seam() {
+        let camera =
+            Camera::equirectangular(Vector3::zeros(), vector![0., 0., -1.], vector![0., 1., 0.]);
+        let raytracer = Raytracer::new(camera, BLACK, 64, 32, 1, 10);
+        let first = raytracer.viewport_uv(0.5, 0.5).0;
+        let second = raytracer.viewport_uv(1.5, 0.5).0;
+        let last = raytracer.viewport_uv(63.5, 0.5).0;
+
+        assert!((second - first - 1. / 64.).abs() < 1e-6);
+        assert!((first + 1. - last - 1. / 64.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn environment_seam() {
+        let map = RgbImage::from_fn(2, 1, |i, _| {
+            if i == 0 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 4, 3, 1, 10)
+            .with_environment(ImageTexture::new(map).with_filter(Filter::Bilinear));
+
+        // Straight behind the camera, both sides of the seam blend into each other.
+        let behind = raytracer.background_color(Ray::new(Vector3::zeros(), vector![0., 0., 1.]));
+        assert!((behind - color![0.5, 0., 0.5])
+            .into_iter()
+            .all(|c| c.abs() < 1e-2));
+    }
+
+    #[test]
+    fn environment_z_up() {
+        // Red sky above a blue ground.
+        let map = RgbImage::from_fn(1, 2, |_, j| {
+            if j == 0 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 255])
+            }
+        });
+        let y_up = Raytracer::new(Camera::default(), BLACK, 4, 3, 1, 10)
+            .with_environment(ImageTexture::new(map.clone()));
+        let z_up = Raytracer::new(Camera::default(), BLACK, 4, 3, 1, 10)
+            .with_environment(ImageTexture::new(map))
+            .with_up_axis(Axis::Z);
+
+        let sky = y_up.background_color(Ray::new(Vector3::zeros(), vector![0., 1., 0.]));
+        let ground = y_up.background_color(Ray::new(Vector3::zeros(), vector![0., -1., 0.]));
+        assert_ne!(sky, ground);
+        assert_eq!(
+            z_up.background_color(Ray::new(Vector3::zeros(), vector![0., 0., 1.])),
+            sky
+        );
+        assert_eq!(
+            z_up.background_color(Ray::new(Vector3::zeros(), vector![0., 0., -1.])),
+            ground
+        );
+    }
+
+    #[test]
+    fn background_fn() {
+        // The sky gradient of the first book.
+        let sky = |ray: Ray| {
+            let t = 0.5 * (ray.direction().normalize().y + 1.);
+            (1. - t) * WHITE + t * color![0.5, 0.7, 1.]
+        };
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 4, 3, 1, 10)
+            .with_environment(ImageTexture::new(RgbImage::new(1, 1)))
+            .with_background_fn(Box::new(sky));
+
+        let up = Ray::new(Vector3::zeros(), vector![0., 1., 0.]);
+        let down = Ray::new(Vector3::zeros(), vector![0., -1., 0.]);
+        assert_eq!(raytracer.background_color(up), color![0.5, 0.7, 1.]);
+        assert_eq!(raytracer.background_color(down), WHITE);
+
+        // Higher pixels are bluer, i.e. have less red.
+        let image = raytracer.render();
+        let top = image.image[0];
+        let bottom = image.image[image.image.len() - 1];
+        assert!(top.r() < bottom.r());
+        assert!(top.b() == 1.);
+    }
+
+    #[test]
+    fn debug_render_in_pool() {
+        use std::sync::atomic::AtomicBool;
+
+        let outside = Arc::new(AtomicBool::new(false));
+        let flag = outside.clone();
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 16, 12, 2, 10)
+            .with_background_fn(Box::new(move |_| {
+                let name = std::thread::current().name().map(str::to_owned);
+                if !name.is_some_and(|name| name.starts_with("render-pool-")) {
+                    flag.store(true, Ordering::Relaxed);
+                }
+                BLACK
+            }));
+        raytracer.thread_pool = Some(Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(2)
+                .thread_name(|index| format!("render-pool-{index}"))
+                .build()
+                .unwrap(),
+        ));
+
+        raytracer.render_background_only();
+        assert!(!outside.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn threads() {
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 4, 3, 2, 10);
+        assert_eq!(
+            raytracer.in_pool(rayon::current_num_threads),
+            rayon::current_num_threads()
+        );
+        assert!(raytracer.clone().with_threads(0).thread_pool.is_none());
+
+        let raytracer = raytracer.with_threads(2);
+        assert_eq!(raytracer.in_pool(rayon::current_num_threads), 2);
+
+        // Seeded renders do not depend on the number of threads.
+        let serial = noisy_scene(4).with_threads(1).render();
+        let parallel = noisy_scene(4).with_threads(2);
+        assert_eq!(parallel.clone().render().image, serial.image);
+        for (a, b) in parallel
+            .render_without_bvh()
+            .image
+            .iter()
+            .zip(&serial.image)
+        {
+            assert!((*a - *b).into_iter().all(|c| c.abs() < 1e-5));
+        }
+    }
+
+    #[test]
+    fn seed() {
+        let scene = || noisy_scene(4);
+
+        let first = scene().render();
+        let second = scene().with_threads(3).render();
+        assert_eq!(first.image, second.image);
+        assert_ne!(first.image, scene().with_seed(8).render().image);
+
+        // Each batch of a session draws different samples.
+        let mut session = scene().into_session();
+        session.add_samples(2);
+        let first_batch = session.accumulation.clone();
+        session.add_samples(2);
+        let second_batch: Vec<Color> = session
+            .accumulation
+            .iter()
+            .zip(&first_batch)
+            .map(|(sum, first)| *sum - *first)
+            .collect();
+        assert_ne!(first_batch, second_batch);
+    }
+
+    #[test]
+    fn russian_roulette() {
+        // Every path bounces off the plane once and escapes to the white background, so the expected color is the albedo.
+        let albedo = 0.5;
+        let mut world = HittableList::default();
+        world.push(InfinitePlane::new(
+            Vector3::zeros(),
+            vector![0., 1., 0.],
+            Lambertian::solid_color(albedo * WHITE),
+        ));
+        let world = HittableListOptions::HittableList(world);
+        let ray = Ray::new(vector![0., 1., 0.], vector![0., -1., 0.]);
+        let color = |raytracer: &Raytracer| {
+            Raytracer::ray_color(
+                &world,
+                ray,
+                &|_| WHITE,
+                raytracer.depth_budget(),
+                None,
+                false,
+                &mut |_, _, _| {},
+            )
+        };
+
+        let raytracer = Raytracer::default();
+        assert_eq!(color(&raytracer), albedo * WHITE);
+
+        // Paths are terminated with probability 1/2, and survivors are weighted by 2.
+        let raytracer = raytracer.with_russian_roulette(0);
+        let samples = 20_000;
+        let mut terminated = 0;
+        let mut sum = 0.;
+        for _ in 0..samples {
+            let sample = color(&raytracer);
+            if sample == BLACK {
+                terminated += 1;
+            } else {
+                assert_eq!(sample, WHITE);
+            }
+            sum += sample.r();
+        }
+        assert!((sum / samples as f32 - albedo).abs() < 0.02);
+        assert!(terminated > samples / 3 && terminated < 2 * samples / 3);
+
+        // Bounces before `min_bounces` are never terminated.
+        let raytracer = raytracer.with_russian_roulette(1);
+        for _ in 0..100 {
+            assert_eq!(color(&raytracer), albedo * WHITE);
+        }
+    }
+
+    /// A recursive formulation of [`Raytracer::ray_color`], weighting the light by the throughput of `depth` like it.
+    fn ray_color_recursive(
+        world: &HittableListOptions,
+        ray: Ray,
+        background: &dyn Fn(Ray) -> Color,
+        depth: DepthBudget,
+    ) -> Color {
+        if depth.total == 0 {
+            return BLACK;
+        }
+        let hit = world.hit(ray, 0.001, f32::INFINITY);
+        let Some(hit) = hit else {
+            return depth.throughput * background(ray);
+        };
+
+        let material = hit.material();
+        let emitted = depth.throughput * material.emit(&hit);
+        let specular = material.is_specular();
+        if let Some((scattered, attenuation)) = material.scatter(ray, hit) {
+            if let Some(depth) = depth
+                .bounce(specular)
+                .and_then(|depth| depth.roulette(attenuation))
+            {
+                return emitted + ray_color_recursive(world, scattered, background, depth);
+            }
+        }
+        emitted
+    }
+
+    #[test]
+    fn iterative_ray_color_matches_recursive() {
+        let mut raytracer =
+            Raytracer::new(Camera::default(), BLACK, 8, 6, 1, 50).with_russian_roulette(3);
+        raytracer
+            .world
+            .push(Sphere::new(vector![0., 0., -1.], 0.5, Dielectric::new(1.5)));
+        raytracer.world.push(Sphere::new(
+            vector![0., -100.5, -1.],
+            100.,
+            Lambertian::solid_color(color![0.8, 0.8, 0.]),
+        ));
+        raytracer.world.push(Sphere::new(
+            vector![1., 0., -1.],
+            0.5,
+            DiffuseLight::solid_color(color![4., 4., 4.]),
+        ));
+        let background = |ray: Ray| {
+            let t = 0.5 * (ray.direction().normalize().y + 1.);
+            (1. - t) * WHITE + t * color![0.5, 0.7, 1.]
+        };
+
+        for world in [
+            Raytracer::build_world(raytracer.world.clone(), false),
+            HittableListOptions::HittableList(raytracer.world.clone()),
+        ] {
+            for seed in 0..200 {
+                let (u, v) = ((seed % 20) as f32 / 19., (seed / 20) as f32 / 9.);
+                let iterative = random::with_seed(seed, || {
+                    let ray = raytracer.primary_ray(u, v);
+                    Raytracer::ray_color(
+                        &world,
+                        ray,
+                        &background,
+                        raytracer.depth_budget(),
+                        None,
+                        false,
+                        &mut |_, _, _| {},
+                    )
+                });
+                let recursive = random::with_seed(seed, || {
+                    let ray = raytracer.primary_ray(u, v);
+                    ray_color_recursive(&world, ray, &background, raytracer.depth_budget())
+                });
+                assert!(
+                    (iterative - recursive).into_iter().all(|c| c.abs() < 1e-4),
+                    "{iterative:?} != {recursive:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_sampling() {
+        let raytracer =
+            Raytracer::new(Camera::default(), BLACK, 4, 3, 10, 10).with_stratified_sampling();
+        assert_eq!(raytracer.samples(), 9);
+
+        // Each of the 3×3 cells gets exactly one sample.
+        let mut rng = rand::thread_rng();
+        let mut cells: Vec<(u16, u16)> = (0..raytracer.samples())
+            .map(|sample| {
+                let (du, dv) = raytracer.subpixel_offset(sample, &mut rng);
+                assert!((0. ..1.).contains(&du) && (0. ..1.).contains(&dv));
+                ((du * 3.) as u16, (dv * 3.) as u16)
+            })
+            .collect();
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 9);
+
+        // A session takes the same stratified samples as a render, which differ from random ones.
+        let stratified = noisy_scene(5).with_stratified_sampling();
+        let mut session = stratified.clone().into_session();
+        session.add_samples(5);
+        assert_eq!(session.samples(), 4);
+        let image = stratified.render().image;
+        for (a, b) in session.current_image().image.iter().zip(&image) {
+            assert!((*a - *b).into_iter().all(|c| c.abs() < 1e-5));
+        }
+        assert_ne!(image, noisy_scene(4).render().image);
+    }
+
+    #[test]
+    fn tiles_keep_pixel_positions() {
+        // Without objects, a render sees the same backgrounds as the primary rays of each pixel traced without tiles.
+        let raytracer = Raytracer::new(Camera::default(), BLACK, 70, 40, 1, 10)
+            .with_seed(7)
+            .with_background_fn(Box::new(|ray| {
+                let direction = ray.direction().normalize();
+                color![direction.x.abs(), direction.y.abs(), 0.]
+            }));
+
+        let untiled: Vec<Color> = (0..70 * 40)
+            .map(|index| {
+                let mut color = BLACK;
+                raytracer.trace_samples(index, |ray| color += raytracer.background_color(ray));
+                color
+            })
+            .collect();
+        assert_eq!(raytracer.clone().render().image, untiled);
+        assert_eq!(raytracer.render_background_only().image, untiled);
+    }
+
+    #[test]
+    fn progress_callback() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let last = Arc::new(AtomicU64::new(0));
+        let (calls_clone, last_clone) = (calls.clone(), last.clone());
+
+        // 70×40 pixels are 3×2 tiles, the ones at the edges being smaller.
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 70, 40, 1, 10)
+            .with_progress_callback(Box::new(move |done, total| {
+                assert_eq!(total, 70 * 40);
+                calls_clone.fetch_add(1, Ordering::Relaxed);
+                last_clone.fetch_max(done, Ordering::Relaxed);
+            }));
+        raytracer.world.push(sky_light());
+
+        let image = raytracer.render();
+        assert_eq!(calls.load(Ordering::Relaxed), 6);
+        assert_eq!(last.load(Ordering::Relaxed), 70 * 40);
+        // Every pixel is covered by exactly one tile.
+        assert!(image.image.iter().all(is_sky));
+    }
+
+    #[test]
+    fn render_with_aux() {
+        let raytracer = || {
+            let mut raytracer = Raytracer::new(Camera::default(), BLACK, 8, 6, 16, 10)
+                .with_stratified_sampling()
+                .with_seed(5);
+            raytracer.world.push(Sphere::new(
+                vector![0.1, 0.05, -3.],
+                1.,
+                DiffuseLight::solid_color(WHITE),
+            ));
+            raytracer
+        };
+
+        let (image, normals, depths) = raytracer().render_with_aux();
+        assert_eq!(normals.len(), image.image.len());
+        assert_eq!(depths.len(), image.image.len());
+        assert_eq!(image.image, raytracer().render().image);
+
+        // The center of the image sees the front of the sphere, the corners see nothing.
+        let center = 3 * 8 + 4;
+        assert!((depths[center] - 2.).abs() < 0.1);
+        assert!(normals[center].z > 0.9);
+        assert_eq!(depths[0], f32::INFINITY);
+        assert_eq!(normals[0], Vector3::zeros());
+
+        // The color is the fraction of samples hitting the light, so the buffers see the same samples as the image, also at the partially covered edge.
+        assert!(image
+            .image
+            .iter()
+            .any(|color| color.r() > 0. && color.r() < 1.));
+        for (color, depth) in image.image.iter().zip(&depths) {
+            assert_eq!(color.r() > 0., depth.is_finite());
+        }
+    }
+
+    #[test]
+    fn save_exr() {
+        let path = std::env::temp_dir().join("ray-tracing-save-exr.exr");
+        let mut raytracer = Raytracer::new(Camera::default(), BLACK, 4, 3, 2, 10);
+        raytracer.world.push(Sphere::new(
+            vector![0., 0., 0.],
+            100.,
+            DiffuseLight::solid_color(color![4., 0.49, 0.]).with_two_sided(),
+        ));
+        raytracer.render().save_exr(&path).unwrap();
+
+        let exr = image::open(&path).unwrap().into_rgb32f();
+        assert_eq!(exr.dimensions(), (4, 3));
+        for pixel in exr.pixels() {
+            assert_eq!(pixel.0, [4., 0.49, 0.]);
+        }
+
+        let too_small = RaytracedImage::new(vec![BLACK; 11], 4, 3);
+        assert!(matches!(
+            too_small.save_exr(&path),
+            Err(ImageError::Parameter(_))
+        ));
+    }
+
+    #[test]
+    fn parallel_bytes_match_serial() {
+        let mut rng = rand::thread_rng();
+        let colors: Vec<Color> = (0..10_000)
+            .map(|_| color![rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+
+        let serial: Vec<u8> = colors
+            .iter()
+            .flat_map(|color| Into::<[u8; 3]>::into(*color))
+            .collect();
+
+        assert_eq!(RaytracedImage::colors_to_bytes(&colors), serial);
+    }
+
+    #[test]
+    fn save_format() {
+        let image = || RaytracedImage::new(vec![color![0.2, 0.4, 0.6]; 12], 4, 3);
+        let path = std::env::temp_dir().join("ray-tracing-save-format.xyz");
+
+        let error = image().save(&path).unwrap_err();
+        assert!(matches!(error, ImageError::Unsupported(_)));
+        assert!(error.to_string().contains("xyz"));
+
+        image().save_as(&path, ImageFormat::Png).unwrap();
+        let decoded = image::load_from_memory(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+    }
+
+    #[test]
+    fn crop() {
+        let colors = (0..12).map(|i| color![i as f32, 0., 0.]).collect();
+        let image = RaytracedImage::new(colors, 4, 3);
+
+        let full = image.crop(0, 0, 4, 3).unwrap();
+        assert_eq!((full.image_width, full.image_height), (4, 3));
+        assert_eq!(full.image, image.image);
+
+        let region = image.crop(1, 1, 3, 3).unwrap();
+        assert_eq!((region.image_width, region.image_height), (2, 2));
+        let reds: Vec<f32> = region.image.iter().map(|color| color.r()).collect();
+        assert_eq!(reds, vec![5., 6., 9., 10.]);
+
+        assert!(image.crop(0, 0, 5, 3).is_none());
+        assert!(image.crop(2, 0, 2, 3).is_none());
+    }
+
+    #[test]
+    fn dither_breaks_up_banding() {
+        let gradient = || {
+            let colors = (0..8)
+                .flat_map(|_| {
+                    (0..64).map(|x| Color::new((0.5 + x as f32 / 64. / 128.).powi(2), 0.25, 0.25))
+                })
+                .collect();
+            RaytracedImage::new(colors, 64, 8)
+        };
+        let transitions = |image: RgbImage| {
+            image
+                .rows()
+                .map(|row| {
+                    let row: Vec<u8> = row.map(|pixel| pixel[0]).collect();
+                    row.windows(2).filter(|pair| pair[0] != pair[1]).count()
+                })
+                .sum::<usize>()
+        };
+
+        let plain = transitions(gradient().into_image().unwrap());
+        let dithered = transitions(gradient().with_dither().into_image().unwrap());
+
+        assert!(dithered > 4 * plain);
+    }
+
+    #[test]
+    fn png_bytes() {
+        let image = RaytracedImage::new(vec![color![0.2, 0.4, 0.6]; 12], 4, 3);
+
+        let bytes = image.to_png_bytes().unwrap();
+        let decoded = image::load_from_memory(&bytes).unwrap();
+
+        assert_eq!((decoded.width(), decoded.height()), (4, 3));
+
+        let too_small = RaytracedImage::new(vec![color![0.2, 0.4, 0.6]; 11], 4, 3);
+        assert!(matches!(
+            too_small.to_png_bytes(),
+            Err(ImageError::Parameter(_))
+        ));
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let colors = vec![
+            color![0., 0., 0.],
+            color![0.2, 0.4, 0.6],
+            color![1., 0.5, 0.25],
+        ];
+        let image = RaytracedImage::new(colors, 3, 1).with_gamma(1.);
+        let path = std::env::temp_dir().join("ray-tracing-round-trip.ppm");
+        image.into_ppm().write_ppm(&path).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "P3\n3 1\n255\n0 0 0\n51 102 153\n255 128 64\n"
+        );
+
+        let image: RaytracedImage = PPM::read(&path).unwrap().into();
+
+        assert_eq!(image.image_width, 3);
+        assert_eq!(image.image_height, 1);
+        let expected = [[0, 0, 0], [51, 102, 153], [255, 128, 64]];
+        for (converted, bytes) in image.image.iter().zip(expected) {
+            let expected = color![
+                (bytes[0] as f32 / 255.).powi(2),
+                (bytes[1] as f32 / 255.).powi(2),
+                (bytes[2] as f32 / 255.).powi(2)
+            ];
+            assert!((*converted - expected).into_iter().all(|c| c.abs() < 1e-6));
+        }
+    }
+
+    #[test]
+    fn identity_lut() {
+        let colors = vec![
+            color![0., 0., 0.],
+            color![0.2, 0.4, 0.6],
+            color![1., 0.5, 0.25],
+            color![0.9, 0.1, 0.7],
+        ];
+        let mut image = RaytracedImage::new(colors.clone(), 2, 2);
+        image.apply_lut(&Lut3d::identity(17));
+
+        for (graded, original) in image.into_output_colors().into_iter().zip(colors) {
+            let encoded: Color = original.into_iter().map(f32::sqrt).collect();
+            assert!((graded - encoded).into_iter().all(|c| c.abs() < 1e-5));
+        }
+    }
+
+    #[test]
+    fn lut_after_tone_map() {
+        let invert = Lut3d::new(
+            2,
+            (0..8)
+                .map(|index| {
+                    color![
+                        (1 - index % 2) as f32,
+                        (1 - index / 2 % 2) as f32,
+                        (1 - index / 4) as f32
+                    ]
+                })
+                .collect(),
+        )
+        .unwrap();
+        // Too bright for the LUT, so grading before tone mapping would clip it to black.
+        let image = RaytracedImage::new(vec![color![1.5, 1.5, 1.5]], 1, 1)
+            .with_tone_map(ToneMap::Reinhard)
+            .with_lut(invert);
+
+        // Reinhard maps 1.5 to 0.6, which the LUT inverts before gamma correction.
+        let expected = 0.4f32.sqrt();
+        for channel in image.into_output_colors()[0].into_iter() {
+            assert!((channel - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn termination_heatmap() {
+        let enclosed = |max_depth| {
+            let mut raytracer = Raytracer::new(Camera::default(), BLACK, 8, 6, 2, max_depth);
+            raytracer.world.push(Sphere::new(
+                vector![0., 0., 0.],
+                100.,
+                Lambertian::default(),
+            ));
+            raytracer
+                .with_shading_mode(ShadingMode::DepthTerminationHeatmap)
+                .render()
+        };
+
+        // Every path is cut off after its single bounce, carrying the albedo of 0.5.
+        let shallow = enclosed(1);
+        assert!(shallow
+            .image
+            .iter()
+            .all(|color| (color.r() - 0.5).abs() < 1e-5));
+
+        let deep = enclosed(30);
+        assert!(deep.image.iter().all(|color| color.r() < 1e-3));
+
+        // Russian roulette terminates half of the paths after each bounce and doubles the survivors, like in the beauty render.
+        let mut roulette = Raytracer::new(Camera::default(), BLACK, 8, 6, 64, 2)
+            .with_russian_roulette(0)
+            .with_seed(2);
+        roulette.world.push(Sphere::new(
+            vector![0., 0., 0.],
+            100.,
+            Lambertian::default(),
+        ));
+        let image = roulette
+            .with_shading_mode(ShadingMode::DepthTerminationHeatmap)
+            .render();
+        let mean = image.image.iter().map(|color| color.r()).sum::<f32>() / 48.;
+        assert!((mean - 0.25).abs() < 0.03);
+        assert!(image
+            .image
+            .iter()
+            .any(|color| (color.r() - 0.25).abs() > 1e-3));
+
+        let mut open = Raytracer::new(Camera::default(), BLACK, 8, 6, 2, 1);
+        open.world.push(Sphere::new(
+            vector![0., 0., -100.],
+            0.1,
+            Lambertian::default(),
+        ));
+        assert!(open
+            .with_shading_mode(ShadingMode::DepthTerminationHeatmap)
+            .render()
+            .image
+            .iter()
+            .all(|color| *color == BLACK));
+    }
+
+    #[test]
+    fn tone_map_before_gamma() {
+        let image = || RaytracedImage::new(vec![color![4., 4., 0.25]], 1, 1);
+
+        let clipped = image().into_image().unwrap();
+        assert_eq!(clipped.get_pixel(0, 0).0, [255, 255, 128]);
+
+        let reinhard = image()
+            .with_tone_map(ToneMap::Reinhard)
+            .into_image()
+            .unwrap();
+        let expected: [u8; 3] = color![0.8f32.sqrt(), 0.8f32.sqrt(), 0.2f32.sqrt()].into();
+        assert_eq!(reinhard.get_pixel(0, 0).0, expected);
+
+        let linear = image().with_gamma(1.).into_ppm();
+        assert!((linear.colors[0].b() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gamma() {
+        let raytracer = || {
+            let mut raytracer = Raytracer::new(Camera::default(), BLACK, 4, 3, 2, 10);
+            raytracer.world.push(sky_light());
+            raytracer
+        };
+
+        let default = raytracer().render().into_ppm();
+        assert!(default
+            .colors
+            .iter()
+            .all(|color| *color == color![0.5, 0.7, 0.9]));
+
+        // The stored colors stay linear, only the output is encoded.
+        let image = raytracer().with_gamma(2.2).render();
+        assert!(image.image.iter().all(is_sky));
+        let expected: Color = sky().into_iter().map(|c| c.powf(1. / 2.2)).collect();
+        assert!(image
+            .into_ppm()
+            .colors
+            .iter()
+            .all(|color| (*color - expected).into_iter().all(|c| c.abs() < 1e-6)));
+
+        // Converting back to the default gamma matches rendering with it.
+        let reencoded = raytracer()
+            .with_gamma(2.2)
+            .render()
+            .with_gamma(2.)
+            .into_ppm();
+        assert_eq!(reencoded.colors, default.colors);
+    }
+
+    #[test]
+    fn prebuilt_bvh() {
+        let mut world = HittableList::default();
+        world.push(sky_light());
+        let bvh = Bvh::new(world, 0., 1.).unwrap();
+
+        // `world` stays empty, so anything rendered comes from the prebuilt BVH.
+        let raytracer =
+            Raytracer::new(Camera::default(), BLACK, 4, 3, 2, 10).with_prebuilt_bvh(bvh);
+        for _ in 0..2 {
+            let image = raytracer.clone().render();
+            assert!(image.image.iter().all(is_sky));
+        }
     }
 }
@@ -0,0 +1,80 @@
+//! Ready-made scenes.
+
+#[cfg(feature = "gltf")]
+mod gltf;
+
+use std::sync::Arc;
+
+use crate::materials::{Lambertian, Material};
+use crate::shapes::Sphere;
+use crate::*;
+
+#[cfg(feature = "gltf")]
+pub use self::gltf::{load_gltf, GltfError};
+
+/// Create a [`Raytracer`] showing one sphere per [`Material`] on a grid for previewing a batch of materials at once.
+///
+/// The spheres are laid out row by row from left to right, starting at the front, on top of a gray ground sphere and lit by a bright sky.
+pub fn material_preview(materials: Vec<Box<dyn Material>>) -> Raytracer {
+    let columns = (materials.len() as f32).sqrt().ceil().max(1.) as usize;
+    let rows = materials.len().div_ceil(columns).max(1);
+    let spacing = 1.2;
+
+    let grid_center = vector![
+        (columns - 1) as f32 * spacing / 2.,
+        0.5,
+        -((rows - 1) as f32) * spacing / 2.
+    ];
+    let extent = columns.max(rows) as f32 * spacing;
+    let camera = Camera::new(
+        grid_center + vector![0., 0.6 * extent, 1.2 * extent],
+        grid_center,
+        vector![0., 1., 0.],
+        std::f32::consts::FRAC_PI_6,
+        16. / 9.,
+        0.,
+        1.,
+    );
+
+    let mut raytracer = Raytracer::new(camera, color![0.7, 0.8, 1.], 400, 225, 50, 20);
+
+    raytracer.world.push(Sphere::new(
+        vector![grid_center.x, -1000., grid_center.z],
+        1000.,
+        Lambertian::solid_color(color![0.5, 0.5, 0.5]),
+    ));
+    for (index, material) in materials.into_iter().enumerate() {
+        let center = vector![
+            (index % columns) as f32 * spacing,
+            0.5,
+            -((index / columns) as f32) * spacing
+        ];
+        let material: Arc<dyn Material> = material.into();
+        raytracer.world.push(Sphere::new(center, 0.5, material));
+    }
+
+    raytracer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{Dielectric, Metal};
+
+    #[test]
+    fn material_preview_spheres() {
+        let materials: Vec<Box<dyn Material>> = vec![
+            Box::new(Lambertian::default()),
+            Box::new(Metal::default()),
+            Box::new(Dielectric::default()),
+            Box::new(Lambertian::solid_color(color![0.8, 0.1, 0.1])),
+            Box::new(Metal::solid_color(color![0.9, 0.7, 0.3], 0.3)),
+        ];
+
+        let raytracer = material_preview(materials);
+
+        // One sphere per material plus the ground.
+        assert_eq!(raytracer.world.len(), 6);
+        assert_eq!(raytracer.validate(), Ok(()));
+    }
+}
@@ -0,0 +1,534 @@
+//! Loading of glTF scenes.
+
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+use gltf::buffer::{self, Source};
+use gltf::camera::Projection;
+use gltf::mesh::Mode;
+use gltf::{Document, Mesh, Node};
+use nalgebra::{Matrix3, Matrix4, Point3, Rotation3};
+
+use crate::materials::Microfacet;
+use crate::shapes::{Instance, Movable, SmoothTriangle, Triangle};
+use crate::textures::SolidColor;
+use crate::*;
+
+/// Error when loading a glTF file.
+#[derive(Debug)]
+pub enum GltfError {
+    /// The glTF file could not be read.
+    Io(io::Error),
+    /// The file is not a valid glTF file or one of its buffers could not be loaded (see [`gltf::Error`]).
+    Gltf(gltf::Error),
+    /// The file uses unsupported features or references a buffer outside of its directory.
+    Invalid(String),
+    /// The scene does not contain a camera.
+    NoCamera,
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Io(error) => write!(f, "could not read glTF file: {error}"),
+            GltfError::Gltf(error) => write!(f, "invalid glTF file: {error}"),
+            GltfError::Invalid(message) => write!(f, "{message}"),
+            GltfError::NoCamera => write!(f, "the scene does not contain a camera"),
+        }
+    }
+}
+
+impl From<io::Error> for GltfError {
+    fn from(error: io::Error) -> Self {
+        GltfError::Io(error)
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(error: gltf::Error) -> Self {
+        GltfError::Gltf(error)
+    }
+}
+
+/// Load the default scene of a glTF file (`.gltf` or binary `.glb`) into a [`HittableList`] and its first [`Camera`].
+///
+/// Meshes become [`SmoothTriangle`]s if they have vertex normals, otherwise flat [`Triangle`]s, with a [`Microfacet`] material from the base color, metallic and roughness factors (textures are ignored).
+/// Each mesh is built once and placed by an [`Instance`] per node. Nodes that scale or mirror their mesh get their own copy with the transform applied to the vertices.
+/// Buffers can be embedded as base64 data URIs, stored in the binary chunk of a `.glb` or in files next to `path`, which must not lie outside of its directory.
+/// Primitives other than triangles are not supported.
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<(HittableList, Camera), GltfError> {
+    let path = path.as_ref();
+    let content = fs::read(path)?;
+    Gltf::parse(&content, Some(path.parent().unwrap_or(Path::new(""))))?.scene()
+}
+
+/// A parsed glTF document together with the contents of its buffers.
+struct Gltf {
+    document: Document,
+    buffers: Vec<buffer::Data>,
+}
+
+impl Gltf {
+    /// Parse `content` (JSON or binary glTF) and load its buffers, resolving file names relative to `directory`.
+    ///
+    /// Without a `directory`, only embedded buffers can be loaded.
+    fn parse(content: &[u8], directory: Option<&Path>) -> Result<Self, GltfError> {
+        let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(content)?;
+        for buffer in document.buffers() {
+            if let Source::Uri(uri) = buffer.source() {
+                Gltf::check_uri(uri)?;
+            }
+        }
+        let buffers = gltf::import_buffers(&document, directory, blob)?;
+
+        Ok(Self { document, buffers })
+    }
+
+    /// Reject URIs of files that are not relative paths inside the directory of the glTF file (after percent-decoding).
+    fn check_uri(uri: &str) -> Result<(), GltfError> {
+        if uri.starts_with("data:") {
+            return Ok(());
+        }
+
+        let decoded = urlencoding::decode(uri)
+            .map_err(|_| invalid(format!("the buffer URI {uri} is not valid UTF-8")))?;
+        let path = Path::new(&*decoded);
+        if uri.contains(':')
+            || !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(invalid(format!(
+                "the buffer URI {uri} points outside of the directory of the glTF file"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Build the default scene and find its first camera (in the order of the node hierarchy).
+    fn scene(&self) -> Result<(HittableList, Camera), GltfError> {
+        let roots: Vec<Node> = match self
+            .document
+            .default_scene()
+            .or_else(|| self.document.scenes().next())
+        {
+            Some(scene) => scene.nodes().collect(),
+            // Without scenes, all nodes that are nobody's children are shown.
+            None => {
+                let children: Vec<usize> = self
+                    .document
+                    .nodes()
+                    .flat_map(|node| node.children().map(|child| child.index()))
+                    .collect();
+                self.document
+                    .nodes()
+                    .filter(|node| !children.contains(&node.index()))
+                    .collect()
+            }
+        };
+
+        let node_count = self.document.nodes().len();
+        let mut world = HittableList::new(vector![0., 0., 0.]);
+        let mut camera = None;
+        let mut meshes: HashMap<usize, Option<Arc<dyn Hittable>>> = HashMap::new();
+        let mut stack: Vec<(Node, Matrix4<f32>, usize)> = roots
+            .into_iter()
+            .rev()
+            .map(|node| (node, Matrix4::identity(), 0))
+            .collect();
+
+        while let Some((node, parent, depth)) = stack.pop() {
+            if depth > node_count {
+                return Err(invalid("the node hierarchy contains a cycle".to_string()));
+            }
+            let transform = parent * Matrix4::from(node.transform().matrix());
+
+            if let Some(mesh) = node.mesh() {
+                let linear: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into();
+                let translation: Vector3<f32> = transform.fixed_view::<3, 1>(0, 3).into();
+                let rigid = (linear.transpose() * linear - Matrix3::identity()).amax() < 1e-4
+                    && linear.determinant() > 0.;
+
+                if rigid {
+                    let shared = match meshes.get(&mesh.index()) {
+                        Some(shared) => shared.clone(),
+                        None => {
+                            let shared = Bvh::new(self.mesh(&mesh, &Matrix4::identity())?, 0., 0.)
+                                .ok()
+                                .map(|bvh| Arc::new(bvh) as Arc<dyn Hittable>);
+                            meshes.insert(mesh.index(), shared.clone());
+                            shared
+                        }
+                    };
+                    if let Some(shared) = shared {
+                        // `Offset` maps into the local coordinates by rotating first and subtracting its offset afterwards.
+                        let rotation = Rotation3::from_matrix_unchecked(linear.transpose());
+                        world.push(
+                            Instance::new(shared, rotation * translation).with_rotation(rotation),
+                        );
+                    }
+                } else if let Ok(bvh) = Bvh::new(self.mesh(&mesh, &transform)?, 0., 0.) {
+                    world.push(bvh);
+                }
+            }
+
+            if let (None, Some(node_camera)) = (&camera, node.camera()) {
+                camera = Some(Gltf::camera(&node_camera, &transform)?);
+            }
+
+            for child in node.children().collect::<Vec<_>>().into_iter().rev() {
+                stack.push((child, transform, depth + 1));
+            }
+        }
+
+        Ok((world, camera.ok_or(GltfError::NoCamera)?))
+    }
+
+    /// Create the [`Triangle`]s of all primitives of `mesh` with `transform` applied to their vertices.
+    fn mesh(&self, mesh: &Mesh, transform: &Matrix4<f32>) -> Result<HittableList, GltfError> {
+        let index = mesh.index();
+        let mut triangles = HittableList::new(vector![0., 0., 0.]);
+        let linear: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into();
+        // Mirroring reverses the winding order, which is restored by swapping two vertices.
+        let mirrored = linear.determinant() < 0.;
+        // Normals stay perpendicular to the surface under the inverse transpose.
+        let normal_transform = linear
+            .try_inverse()
+            .ok_or_else(|| invalid("a mesh is scaled to zero".to_string()))?
+            .transpose();
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                return Err(invalid(format!(
+                    "mesh {index} contains primitives other than triangles"
+                )));
+            }
+
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+            let positions: Vec<Vector3<f32>> = reader
+                .read_positions()
+                .ok_or_else(|| invalid(format!("mesh {index} has a primitive without positions")))?
+                .map(|position| transform.transform_point(&Point3::from(position)).coords)
+                .collect();
+            let normals: Option<Vec<Vector3<f32>>> = reader.read_normals().map(|normals| {
+                normals
+                    .map(|normal| normal_transform * Vector3::from(normal))
+                    .collect()
+            });
+            if normals
+                .as_ref()
+                .is_some_and(|normals| normals.len() != positions.len())
+            {
+                return Err(invalid(format!(
+                    "mesh {index} has a different number of normals and positions"
+                )));
+            }
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+            let material = Gltf::material(&primitive.material());
+
+            for triangle in indices.chunks_exact(3) {
+                if let Some(vertex) = triangle.iter().find(|vertex| **vertex >= positions.len()) {
+                    return Err(invalid(format!(
+                        "mesh {index} references vertex {vertex} of {}",
+                        positions.len()
+                    )));
+                }
+                let corners = if mirrored {
+                    [triangle[0], triangle[2], triangle[1]]
+                } else {
+                    [triangle[0], triangle[1], triangle[2]]
+                };
+                let vertices = corners.map(|corner| positions[corner]);
+
+                match &normals {
+                    Some(normals) => triangles.push(SmoothTriangle::new(
+                        vertices,
+                        corners.map(|corner| normals[corner]),
+                        material.clone(),
+                    )),
+                    None => {
+                        let [a, b, c] = vertices;
+                        triangles.push(Triangle::new(a, b, c, material.clone()));
+                    }
+                }
+            }
+        }
+
+        Ok(triangles)
+    }
+
+    /// Create a [`Microfacet`] material from the factors of `material` (the default material of glTF for primitives without one).
+    fn material(material: &gltf::Material) -> Microfacet<SolidColor> {
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        Microfacet::solid_color(
+            color![r, g, b],
+            pbr.roughness_factor(),
+            pbr.metallic_factor(),
+        )
+    }
+
+    /// Create a [`Camera`] from `camera` placed by `transform`. glTF cameras look along their negative z axis with y pointing up.
+    fn camera(camera: &gltf::Camera, transform: &Matrix4<f32>) -> Result<Camera, GltfError> {
+        let lookfrom = transform.transform_point(&Point3::origin()).coords;
+        let lookat = lookfrom + transform.transform_vector(&-Vector3::z());
+        let vup = transform.transform_vector(&Vector3::y());
+
+        match camera.projection() {
+            Projection::Perspective(perspective) => Ok(Camera::new(
+                lookfrom,
+                lookat,
+                vup,
+                perspective.yfov(),
+                perspective.aspect_ratio().unwrap_or(16. / 9.),
+                0.,
+                1.,
+            )),
+            Projection::Orthographic(orthographic) => {
+                let (xmag, ymag) = (orthographic.xmag(), orthographic.ymag());
+                if xmag <= 0. || ymag <= 0. {
+                    return Err(invalid(format!(
+                        "camera {} has a non-positive magnification",
+                        camera.index()
+                    )));
+                }
+                Ok(Camera::orthographic(
+                    lookfrom,
+                    lookat,
+                    vup,
+                    2. * ymag,
+                    xmag / ymag,
+                ))
+            }
+        }
+    }
+}
+
+fn invalid(message: String) -> GltfError {
+    GltfError::Invalid(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    /// A cube from -1 to 1 (8 vertices, 36 `u16` indices) in front of a camera at the origin.
+    /// The cube is rotated by 90° around y, which only looks the same if the rotation is applied around its own center.
+    const CUBE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0, 1] }],
+        "nodes": [
+            { "mesh": 0, "translation": [0, 0, -5], "rotation": [0, 0.70710677, 0, 0.70710677] },
+            { "camera": 0, "translation": [0, 1, 0] }
+        ],
+        "cameras": [{ "type": "perspective", "perspective": { "yfov": 0.8, "aspectRatio": 1.5, "znear": 0.1 } }],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 }] }],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 } }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 8, "type": "VEC3", "min": [-1, -1, -1], "max": [1, 1, 1] },
+            { "bufferView": 1, "componentType": 5123, "count": 36, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 96 },
+            { "buffer": 0, "byteOffset": 96, "byteLength": 72 }
+        ],
+        "buffers": [{
+            "byteLength": 168,
+            "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAIC/AACAPwAAgL8AAIC/AACAPwAAgD8AAIC/AACAvwAAgD8AAIC/AACAvwAAgL8AAIA/AACAPwAAgL8AAIA/AACAPwAAgD8AAIA/AACAvwAAgD8AAIA/BAAFAAYABAAGAAcAAQAAAAMAAQADAAIABQABAAIABQACAAYAAAAEAAcAAAAHAAMABwAGAAIABwACAAMAAAABAAUAAAAFAAQA"
+        }]
+    }"#;
+
+    /// Check that the ray along -z from the origin hits the front of the [`CUBE`] at `z = -4`.
+    fn assert_hits_cube(world: &HittableList) {
+        let ray = Ray::new(Vector3::zeros(), vector![0., 0., -1.]);
+        let hit = world.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-4);
+        assert!((hit.normal - vector![0., 0., 1.]).norm() < 1e-4);
+    }
+
+    #[test]
+    fn cube() {
+        let gltf = Gltf::parse(CUBE.as_bytes(), None).unwrap();
+        let mesh = gltf.document.meshes().next().unwrap();
+        assert_eq!(gltf.mesh(&mesh, &Matrix4::identity()).unwrap().len(), 12);
+
+        let (world, camera) = gltf.scene().unwrap();
+        assert_eq!(world.len(), 1);
+        assert_eq!(camera.get_ray(0.5, 0.5).origin(), vector![0., 1., 0.]);
+        assert_hits_cube(&world);
+    }
+
+    #[test]
+    fn scaled_nodes_are_baked() {
+        let scaled = CUBE.replace(
+            r#""translation": [0, 0, -5], "rotation": [0, 0.70710677, 0, 0.70710677]"#,
+            r#""translation": [0, 0, -10], "scale": [2, 2, -2]"#,
+        );
+        let (world, _) = Gltf::parse(scaled.as_bytes(), None)
+            .unwrap()
+            .scene()
+            .unwrap();
+
+        let ray = Ray::new(Vector3::zeros(), vector![0., 0., -1.]);
+        let hit = world.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 8.).abs() < 1e-4);
+        // The mirrored cube still faces outwards.
+        assert!(hit.front_face);
+    }
+
+    /// Pack `json` and the binary chunk `binary` into a `.glb`.
+    fn glb(json: &str, binary: &[u8]) -> Vec<u8> {
+        let mut json = json.to_string();
+        while json.len() % 4 != 0 {
+            json.push(' ');
+        }
+
+        let mut glb = Vec::new();
+        let length = 12 + 8 + json.len() + 8 + binary.len();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(json.as_bytes());
+        glb.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(binary);
+        glb
+    }
+
+    /// The [`CUBE`] without the URI of its buffer, which is therefore the binary chunk of a `.glb`, and the contents of the buffer.
+    fn binary_cube_parts() -> (String, Vec<u8>) {
+        let uri = &CUBE[CUBE.find(",\n            \"uri\"").unwrap()..=CUBE.rfind('"').unwrap()];
+        let binary = Gltf::parse(CUBE.as_bytes(), None)
+            .unwrap()
+            .buffers
+            .remove(0)
+            .0;
+        (CUBE.replace(uri, ""), binary)
+    }
+
+    #[test]
+    fn binary_cube() {
+        let (json, binary) = binary_cube_parts();
+        let (world, _) = Gltf::parse(&glb(&json, &binary), None)
+            .unwrap()
+            .scene()
+            .unwrap();
+        assert_hits_cube(&world);
+    }
+
+    #[test]
+    fn sparse_positions() {
+        // The untransformed cube, moved to the front of the camera by a sparse accessor replacing all 8 vertices.
+        let (json, mut binary) = binary_cube_parts();
+        let json = json
+            .replace(
+                r#"{ "bufferView": 0, "componentType": 5126, "count": 8, "type": "VEC3", "min": [-1, -1, -1], "max": [1, 1, 1] }"#,
+                r#"{ "bufferView": 0, "componentType": 5126, "count": 8, "type": "VEC3", "min": [-1, -1, -6], "max": [1, 1, -4],
+                     "sparse": { "count": 8, "indices": { "bufferView": 2, "componentType": 5121 }, "values": { "bufferView": 3 } } }"#,
+            )
+            .replace(
+                r#""translation": [0, 0, -5], "rotation": [0, 0.70710677, 0, 0.70710677]"#,
+                r#""translation": [0, 0, 0]"#,
+            )
+            .replace(
+                r#"{ "buffer": 0, "byteOffset": 96, "byteLength": 72 }"#,
+                r#"{ "buffer": 0, "byteOffset": 96, "byteLength": 72 },
+                   { "buffer": 0, "byteOffset": 168, "byteLength": 8 },
+                   { "buffer": 0, "byteOffset": 176, "byteLength": 96 }"#,
+            )
+            .replace(r#""byteLength": 168"#, r#""byteLength": 272"#);
+        // The indices 0 to 7 followed by the vertices of the cube shifted by -5 along z.
+        binary.extend(0..8);
+        for index in 0..8 {
+            let vertex = [
+                if [1, 2, 5, 6].contains(&index) {
+                    1f32
+                } else {
+                    -1.
+                },
+                if [2, 3, 6, 7].contains(&index) {
+                    1.
+                } else {
+                    -1.
+                },
+                if index < 4 { -6. } else { -4. },
+            ];
+            binary.extend(vertex.iter().flat_map(|value| value.to_le_bytes()));
+        }
+
+        let (world, _) = Gltf::parse(&glb(&json, &binary), None)
+            .unwrap()
+            .scene()
+            .unwrap();
+        assert_hits_cube(&world);
+    }
+
+    #[test]
+    fn vertex_normals() {
+        // One triangle at z = -2 without indices, whose normal tilts towards +x at its second vertex.
+        let triangle = r#"{
+            "asset": { "version": "2.0" },
+            "nodes": [{ "mesh": 0, "camera": 0 }],
+            "cameras": [{ "type": "orthographic", "orthographic": { "xmag": 1, "ymag": 1, "znear": 0.1, "zfar": 10 } }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "NORMAL": 1 } }] }],
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, -2], "max": [1, 1, -2] },
+                { "bufferView": 0, "byteOffset": 36, "componentType": 5126, "count": 3, "type": "VEC3" }
+            ],
+            "bufferViews": [{ "buffer": 0, "byteLength": 72 }],
+            "buffers": [{
+                "byteLength": 72,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAADAAACAPwAAAAAAAADAAAAAAAAAgD8AAADAAAAAAAAAAAAAAIA/8wQ1PwAAAADzBDU/AAAAAAAAAAAAAIA/"
+            }]
+        }"#;
+        let (world, _) = Gltf::parse(triangle.as_bytes(), None)
+            .unwrap()
+            .scene()
+            .unwrap();
+
+        let ray = Ray::new(vector![0.5, 0.1, 0.], vector![0., 0., -1.]);
+        let hit = world.hit(ray, 0., f32::INFINITY).unwrap();
+        let up: Vector3<f32> = vector![0., 0., 1.];
+        let tilted: Vector3<f32> = vector![1., 0., 1.].normalize();
+        let expected = (0.5 * up + 0.5 * tilted).normalize();
+        assert!((hit.t - 2.).abs() < 1e-4);
+        assert!((hit.normal - expected).norm() < 1e-4);
+    }
+
+    #[test]
+    fn errors() {
+        let no_camera = CUBE.replace(r#""camera": 0, "#, "");
+        let error = Gltf::parse(no_camera.as_bytes(), None)
+            .unwrap()
+            .scene()
+            .unwrap_err();
+        assert!(matches!(error, GltfError::NoCamera));
+
+        let error = load_gltf("does/not/exist.gltf").unwrap_err();
+        assert!(matches!(error, GltfError::Io(_)));
+
+        for uri in [
+            "../cube.bin",
+            "%2E%2E/cube.bin",
+            "/etc/cube.bin",
+            "file:///cube.bin",
+        ] {
+            let outside = CUBE.replace(
+                &CUBE[CUBE.find("data:").unwrap()..CUBE.rfind('"').unwrap()],
+                uri,
+            );
+            let error = Gltf::parse(outside.as_bytes(), Some(Path::new("."))).err();
+            assert!(matches!(error, Some(GltfError::Invalid(_))), "{uri}");
+        }
+    }
+}
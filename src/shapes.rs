@@ -2,17 +2,21 @@
 
 use std::f32::consts::{FRAC_PI_2, PI};
 use std::fmt::Debug;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::Arc;
 
 use nalgebra::Rotation3;
 use rand::Rng;
 
 use crate::hitrecord::HitRecord;
 use crate::hittable::Aabb;
-use crate::materials::{Isotropic, Material};
+use crate::materials::{Isotropic, Lambertian, Material};
 use crate::ray::Ray;
-use crate::textures::{SolidColor, Texture};
+use crate::textures::{CheckerTexture, SolidColor, Texture};
 use crate::*;
 
+pub mod mesh;
+
 /// Marks an object to support movement and rotation via [`Offset`].
 pub trait Movable: Clone + Debug + Hittable {
     /// Consumes `self` and returns a rotated version.
@@ -77,34 +81,47 @@ impl Offset {
         t_min: f32,
         t_max: f32,
     ) -> Option<HitRecord<'a>> {
-        // Rotation
-        let rotated_ray = match self.rotation {
-            Some(rotation) => {
-                Ray::new(rotation * ray.origin(), rotation * ray.direction()).with_time(ray.time())
-            }
-            None => ray,
-        };
+        self.hit_with(ray, |ray| hittable.hit_origin(ray, t_min, t_max))
+    }
 
-        // Translation
-        let offset_ray = Ray::new(
-            rotated_ray.origin() - self.offset(rotated_ray.time()),
-            rotated_ray.direction(),
-        )
-        .with_time(rotated_ray.time());
+    /// Transform `ray` to the origin, find the hit there with `hit_origin`, and transform it back like [`hit`](Offset::hit).
+    pub(crate) fn hit_with<'a>(
+        &self,
+        ray: Ray,
+        hit_origin: impl FnOnce(Ray) -> Option<HitRecord<'a>>,
+    ) -> Option<HitRecord<'a>> {
+        if ray.is_degenerate() {
+            return None;
+        }
 
-        let mut hit_record_option = hittable.hit_origin(offset_ray, t_min, t_max);
+        let mut hit_record_option = hit_origin(self.transform_ray(ray));
 
         if let Some(hit_record) = &mut hit_record_option {
-            hit_record.point += self.offset(ray.time());
-            if let Some(rotation) = self.rotation {
-                hit_record.point = rotation.inverse() * hit_record.point;
-                hit_record.normal = rotation.inverse() * hit_record.normal;
-            }
+            self.transform_hit_record(hit_record, ray.time());
+            // Wrappers may have scaled the ray, so measure the distance along the ray in world space.
+            hit_record.distance = hit_record.t * ray.direction().norm();
         }
 
         hit_record_option
     }
 
+    /// Transform a [`Ray`] into the coordinate system of the object at the origin.
+    pub(crate) fn transform_ray(&self, ray: Ray) -> Ray {
+        ray.transformed(self.rotation, -self.offset(ray.time()))
+    }
+
+    /// Transform a [`HitRecord`] of the object at the origin back into world space.
+    pub(crate) fn transform_hit_record(&self, hit_record: &mut HitRecord, time: f32) {
+        hit_record.point += self.offset(time);
+        if let Some(rotation) = self.rotation {
+            hit_record.point = rotation.inverse() * hit_record.point;
+            hit_record.normal = rotation.inverse() * hit_record.normal;
+            hit_record.tangent = hit_record
+                .tangent
+                .map(|tangent| rotation.inverse() * tangent);
+        }
+    }
+
     pub(crate) fn bounding_box<'a, H: Hittable + ?Sized>(
         &'a self,
         hittable: &'a H,
@@ -125,6 +142,15 @@ impl Offset {
     }
 }
 
+/// Unit tangent running around the y axis at `point`, i.e. in the direction of decreasing azimuth `atan2(z, x)`.
+///
+/// On the y axis itself, where it is undefined, the x axis is returned.
+fn azimuthal_tangent(point: Vector3<f32>) -> Vector3<f32> {
+    vector![point.z, 0., -point.x]
+        .try_normalize(f32::EPSILON)
+        .unwrap_or_else(Vector3::x)
+}
+
 /// A sphere.
 ///
 /// # Fields
@@ -172,8 +198,36 @@ impl<M: Material> Sphere<M> {
     }
 }
 
-impl<M: Material + Clone + 'static> Hittable for Sphere<M> {
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+impl<M: Material + Clone + 'static> Sphere<M> {
+    /// Check whether a [`Ray`] passes through the sphere and return both the near and the far hit.
+    ///
+    /// Returns [`None`] unless both hits lie inside the allowed parameter range. This is useful e.g. for finding where a [`Ray`] enters and exits a volume.
+    pub fn hit_both(
+        &self,
+        ray: Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(HitRecord<'_>, HitRecord<'_>)> {
+        let offset_ray = self.center.transform_ray(ray);
+        let (near, far) = self.roots(offset_ray)?;
+        if near < t_min || far > t_max {
+            return None;
+        }
+
+        let mut near = self.hit_record(offset_ray, near);
+        let mut far = self.hit_record(offset_ray, far);
+        self.center.transform_hit_record(&mut near, ray.time());
+        self.center.transform_hit_record(&mut far, ray.time());
+
+        Some((near, far))
+    }
+
+    /// Both parameters at which a [`Ray`] intersects the sphere at the origin, ordered by size.
+    fn roots(&self, ray: Ray) -> Option<(f32, f32)> {
+        if ray.is_degenerate() {
+            return None;
+        }
+
         let oc = ray.origin();
         let a = ray.direction().norm_squared();
         let b_halves = oc.dot(&ray.direction());
@@ -184,27 +238,35 @@ impl<M: Material + Clone + 'static> Hittable for Sphere<M> {
         }
         let discriminant_sqrt = discriminant.sqrt();
 
-        let mut root = (-b_halves - discriminant_sqrt) / a;
+        Some((
+            (-b_halves - discriminant_sqrt) / a,
+            (-b_halves + discriminant_sqrt) / a,
+        ))
+    }
+
+    fn hit_record(&self, ray: Ray, root: f32) -> HitRecord<'_> {
+        let point = ray.at(root);
+        let normal = point / self.radius;
+        let (u, v) = self.get_surface_coordinates(normal);
+
+        HitRecord::from_ray(point, u, v, normal, root, self.material(), ray)
+            .with_tangent(azimuthal_tangent(point))
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Sphere<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (near, far) = self.roots(ray)?;
+
+        let mut root = near;
         if root < t_min || root > t_max {
-            root = (-b_halves + discriminant_sqrt) / a;
+            root = far;
             if root < t_min || root > t_max {
                 return None;
             }
         }
 
-        let point = ray.at(root);
-        let normal = point / self.radius;
-        let (u, v) = self.get_surface_coordinates(normal);
-
-        Some(HitRecord::from_ray(
-            point,
-            u,
-            v,
-            normal,
-            root,
-            self.material(),
-            ray,
-        ))
+        Some(self.hit_record(ray, root))
     }
 
     fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
@@ -239,12 +301,14 @@ impl<M: Material + Clone + 'static> Movable for Sphere<M> {
 /// - `center`: Center of the cylinder.
 /// - `radius`: Radius of the cylinder.
 /// - `height`: Height of the cylinder (from top to bottom, not from center to bottom).
+/// - `capped`: Whether the ends are closed (otherwise it is an open tube).
 /// - `material`: Material of the cylinder.
 #[derive(Clone, Debug)]
 pub struct Cylinder<M: Material> {
     center: Offset,
     radius: f32,
     height: f32,
+    capped: bool,
     material: M,
 }
 
@@ -255,16 +319,26 @@ impl<M: Material> Cylinder<M> {
             center: Offset::new(center),
             radius,
             height,
+            capped: true,
             material,
         }
     }
 
+    /// Create a new stationary [`Cylinder`] without caps (e.g. a pipe).
+    pub fn open(center: Vector3<f32>, radius: f32, height: f32, material: M) -> Self {
+        Self {
+            capped: false,
+            ..Self::new(center, radius, height, material)
+        }
+    }
+
     pub fn moving(self, position_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
         let center = self.center.moving(position_end, time_start, time_end);
         Self {
             center,
             radius: self.radius,
             height: self.height,
+            capped: self.capped,
             material: self.material,
         }
     }
@@ -281,13 +355,17 @@ impl<M: Material> Cylinder<M> {
         self.height
     }
 
+    pub fn capped(&self) -> bool {
+        self.capped
+    }
+
     pub fn material(&self) -> &M {
         &self.material
     }
 }
 
 impl<M: Material + Clone + 'static> Hittable for Cylinder<M> {
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let oc = vector![ray.origin().x, 0., ray.origin().z];
         let a = ray.direction().x.powi(2) + ray.direction().z.powi(2);
         let b_halves = oc.dot(&ray.direction());
@@ -313,7 +391,16 @@ impl<M: Material + Clone + 'static> Hittable for Cylinder<M> {
         let mut point: Vector3<f32>;
         let mut root: f32;
 
-        if point1.y > upper_bound {
+        if !self.capped {
+            // Without caps, only the side counts, which may also be hit from the inside.
+            (root, point) =
+                [(root1, point1), (root2, point2)]
+                    .into_iter()
+                    .find(|(root, point)| {
+                        (t_min..=t_max).contains(root)
+                            && (lower_bound..=upper_bound).contains(&point.y)
+                    })?;
+        } else if point1.y > upper_bound {
             if point2.y > upper_bound {
                 return None;
             }
@@ -356,15 +443,24 @@ impl<M: Material + Clone + 'static> Hittable for Cylinder<M> {
         let mut normal = point / self.radius;
         normal = vector!(normal.x, 0., normal.z);
 
-        Some(HitRecord::from_ray(
-            point,
-            0., // TODO: Parametrization of Cylinder
-            0.,
-            normal,
-            root,
-            &self.material,
-            ray,
-        ))
+        // The side is unrolled like the sphere's longitude, the caps are projected onto the unit square.
+        let (u, v) = if !self.capped || point.y.abs() < upper_bound.abs() - 1e-4 * self.height.abs()
+        {
+            (
+                1. - (point.z.atan2(point.x) + PI) / (2. * PI),
+                (point.y - lower_bound) / self.height,
+            )
+        } else {
+            (
+                (point.x / self.radius.abs() + 1.) / 2.,
+                (point.z / self.radius.abs() + 1.) / 2.,
+            )
+        };
+
+        Some(
+            HitRecord::from_ray(point, u, v, normal, root, &self.material, ray)
+                .with_tangent(azimuthal_tangent(point)),
+        )
     }
 
     fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
@@ -391,6 +487,120 @@ impl<M: Material + Clone + 'static> Movable for Cylinder<M> {
     }
 }
 
+/// A cone along the y axis with its apex at the top.
+///
+/// Only the slanted side is hit, the base is open (close it with a [`Disk`] if needed).
+///
+/// # Fields
+/// - `center`: Center of the cone, halfway between the base and the apex.
+/// - `radius`: Radius of the base.
+/// - `height`: Height of the cone (from the base to the apex).
+/// - `material`: Material of the cone.
+#[derive(Clone, Debug)]
+pub struct Cone<M: Material> {
+    center: Offset,
+    radius: f32,
+    height: f32,
+    material: M,
+}
+
+impl<M: Material> Cone<M> {
+    /// Create a new stationary [`Cone`].
+    pub fn new(center: Vector3<f32>, radius: f32, height: f32, material: M) -> Self {
+        Self {
+            center: Offset::new(center),
+            radius,
+            height,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Cone<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        // The side satisfies x² + z² = k² (apex - y)², with the slope k = radius / height.
+        let apex = self.height / 2.;
+        let k2 = (self.radius / self.height).powi(2);
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let w = apex - origin.y;
+
+        let a = direction.x.powi(2) + direction.z.powi(2) - k2 * direction.y.powi(2);
+        let b_halves = origin.x * direction.x + origin.z * direction.z + k2 * w * direction.y;
+        let c = origin.x.powi(2) + origin.z.powi(2) - k2 * w.powi(2);
+
+        let roots = if a.abs() < f32::EPSILON {
+            // Parallel to the slope, so there is only one intersection.
+            [-c / (2. * b_halves), f32::NAN]
+        } else {
+            let discriminant = b_halves.powi(2) - a * c;
+            if discriminant < 0. {
+                return None;
+            }
+            let discriminant_sqrt = discriminant.sqrt();
+            let root1 = (-b_halves - discriminant_sqrt) / a;
+            let root2 = (-b_halves + discriminant_sqrt) / a;
+            [root1.min(root2), root1.max(root2)]
+        };
+
+        // Clipping to the height range also discards the mirrored cone above the apex.
+        let (root, point) = roots
+            .into_iter()
+            .map(|t| (t, ray.at(t)))
+            .find(|(t, point)| (t_min..=t_max).contains(t) && (-apex..=apex).contains(&point.y))?;
+
+        let normal = vector![point.x, k2 * (apex - point.y), point.z]
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+        let u = 1. - (point.z.atan2(point.x) + PI) / (2. * PI);
+        let v = (point.y + apex) / self.height;
+
+        Some(
+            HitRecord::from_ray(point, u, v, normal, root, &self.material, ray)
+                .with_tangent(azimuthal_tangent(point)),
+        )
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(Aabb::new(
+            -vector![self.radius.abs(), self.height.abs() / 2., self.radius.abs()],
+            vector![self.radius.abs(), self.height.abs() / 2., self.radius.abs()],
+        ))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Cone<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Plane {
     XY,
@@ -510,7 +720,7 @@ impl<M: Material> Rectangle<M> {
 }
 
 impl<M: Material + Clone + 'static> Hittable for Rectangle<M> {
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let (a_index, b_index, c_index) = self.orientation.axes();
         let a_min = -self.width / 2.;
         let a_max = self.width / 2.;
@@ -533,22 +743,16 @@ impl<M: Material + Clone + 'static> Hittable for Rectangle<M> {
         let v = (b - b_min) / (b_max - b_min);
         let mut normal = vector![0., 0., 0.];
         normal[c_index] = 1.;
+        let mut tangent = vector![0., 0., 0.];
+        tangent[a_index] = 1.;
 
-        Some(HitRecord::from_ray(
-            point,
-            u,
-            v,
-            normal,
-            t,
-            &self.material,
-            ray,
-        ))
+        Some(HitRecord::from_ray(point, u, v, normal, t, &self.material, ray).with_tangent(tangent))
     }
 
     fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
-        let minimum = -vector![self.width / 2., self.height / 2., 0.0001];
-        let maximum = vector![self.width / 2., self.height / 2., 0.0001];
-        Some(Aabb::new(minimum, maximum))
+        let minimum = -vector![self.width / 2., self.height / 2., 0.];
+        let maximum = vector![self.width / 2., self.height / 2., 0.];
+        Some(Aabb::new(minimum, maximum).pad(0.0002))
     }
 
     fn center(&self) -> &Offset {
@@ -568,64 +772,44 @@ impl<M: Material + Clone + 'static> Movable for Rectangle<M> {
     }
 }
 
-/// A axis-aligned cuboid (3D rectangle).
+/// A flat parallelogram spanned by two arbitrary edges, unlike the axis-aligned [`Rectangle`].
 ///
-/// # Fields:
-/// - `center`: Its [`Offset`].
-/// - `width`: Its width (in x direction).
-/// - `height`: Its height (in y direction).
-/// - `depth`: Its depth (in z direction).
+/// # Fields
+/// - `center`: Its [`Offset`], placed at the middle of the parallelogram.
+/// - `corner`: The corner at which both edges start, relative to `center`.
+/// - `u_edge`: The edge along which `u` increases.
+/// - `v_edge`: The edge along which `v` increases.
+/// - `normal`: Its unit normal in the direction of `u_edge × v_edge`.
+/// - `w`: `n / (n · n)` for the unnormalized normal `n`, which gives the planar coordinates of a point.
 /// - `material`: Its material.
 #[derive(Clone, Debug)]
-pub struct Cuboid<M: Material> {
+pub struct Quad<M: Material> {
     center: Offset,
-    width: f32,
-    height: f32,
-    depth: f32,
-    rectangles: HittableList,
+    corner: Vector3<f32>,
+    u_edge: Vector3<f32>,
+    v_edge: Vector3<f32>,
+    normal: Vector3<f32>,
+    w: Vector3<f32>,
     material: M,
 }
 
-impl<M: Material + Clone + 'static> Cuboid<M> {
-    pub fn new(center: Vector3<f32>, width: f32, height: f32, depth: f32, material: M) -> Self {
-        let mut rectangles = HittableList::default();
-
-        let bottom = Rectangle::xz(
-            -vector![0., height / 2., 0.],
-            width,
-            depth,
-            material.clone(),
-        );
-        let top = Rectangle::xz(vector![0., height / 2., 0.], width, depth, material.clone());
-        let left = Rectangle::yz(
-            -vector![width / 2., 0., 0.],
-            height,
-            depth,
-            material.clone(),
-        );
-        let right = Rectangle::yz(vector![width / 2., 0., 0.], height, depth, material.clone());
-        let back = Rectangle::xy(
-            -vector![0., 0., depth / 2.],
-            width,
-            height,
-            material.clone(),
-        );
-        let front = Rectangle::xy(vector![0., 0., depth / 2.], width, height, material.clone());
-        rectangles.push(top);
-        rectangles.push(bottom);
-        rectangles.push(left);
-        rectangles.push(right);
-        rectangles.push(back);
-        rectangles.push(front);
-
-        let center = Offset::new(center);
+impl<M: Material> Quad<M> {
+    pub fn new(
+        corner: Vector3<f32>,
+        u_edge: Vector3<f32>,
+        v_edge: Vector3<f32>,
+        material: M,
+    ) -> Self {
+        let half_diagonal = (u_edge + v_edge) / 2.;
+        let n = u_edge.cross(&v_edge);
 
         Self {
-            center,
-            width,
-            height,
-            depth,
-            rectangles,
+            center: Offset::new(corner + half_diagonal),
+            corner: -half_diagonal,
+            u_edge,
+            v_edge,
+            normal: n.normalize(),
+            w: n / n.norm_squared(),
             material,
         }
     }
@@ -634,21 +818,51 @@ impl<M: Material + Clone + 'static> Cuboid<M> {
         self.center.offset(time)
     }
 
+    pub fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
     pub fn material(&self) -> &M {
         &self.material
     }
 }
 
-impl<M: Material + Clone + 'static> Hittable for Cuboid<M> {
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        self.rectangles.hit(ray, t_min, t_max)
+impl<M: Material + Clone + 'static> Hittable for Quad<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(&ray.direction());
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let t = self.normal.dot(&(self.corner - ray.origin())) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let planar = point - self.corner;
+        let u = self.w.dot(&planar.cross(&self.v_edge));
+        let v = self.w.dot(&self.u_edge.cross(&planar));
+        if !(0. ..=1.).contains(&u) || !(0. ..=1.).contains(&v) {
+            return None;
+        }
+
+        Some(
+            HitRecord::from_ray(point, u, v, self.normal, t, &self.material, ray)
+                .with_tangent(self.u_edge.normalize()),
+        )
     }
 
     fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
-        Some(Aabb::new(
-            -vector![self.width / 2., self.height / 2., self.depth / 2.],
-            vector![self.width / 2., self.height / 2., self.depth / 2.],
-        ))
+        let corners = [
+            self.corner,
+            self.corner + self.u_edge,
+            self.corner + self.v_edge,
+            self.corner + self.u_edge + self.v_edge,
+        ];
+        let minimum = corners.iter().fold(corners[0], |a, b| a.inf(b));
+        let maximum = corners.iter().fold(corners[0], |a, b| a.sup(b));
+        Some(Aabb::new(minimum, maximum).pad(0.0002))
     }
 
     fn center(&self) -> &Offset {
@@ -656,7 +870,7 @@ impl<M: Material + Clone + 'static> Hittable for Cuboid<M> {
     }
 }
 
-impl<M: Material + Clone + 'static> Movable for Cuboid<M> {
+impl<M: Material + Clone + 'static> Movable for Quad<M> {
     fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
         self.center = self.center.with_rotation(rotation);
         self
@@ -668,86 +882,2361 @@ impl<M: Material + Clone + 'static> Movable for Cuboid<M> {
     }
 }
 
-/// A medium of constant optical density.
+/// A triangle.
+///
+/// # Fields:
+/// - `center`: Its [`Offset`], placed at the centroid.
+/// - `vertices`: Its vertices relative to the centroid.
+/// - `material`: Its material.
 #[derive(Clone, Debug)]
-pub struct ConstantMedium<H: Hittable, T: Texture> {
-    boundary: H,
-    phase_function: Isotropic<T>,
-    negative_inverse_density: f32,
+pub struct Triangle<M: Material> {
+    center: Offset,
+    vertices: [Vector3<f32>; 3],
+    material: M,
 }
 
-impl<H: Hittable, T: Texture> ConstantMedium<H, T> {
-    pub fn new(boundary: H, texture: T, density: f32) -> Self {
+impl<M: Material> Triangle<M> {
+    /// Create a new [`Triangle`] from its vertices.
+    ///
+    /// The front face is the one from which `a`, `b`, `c` appear counterclockwise.
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>, material: M) -> Self {
+        let centroid = (a + b + c) / 3.;
         Self {
-            boundary,
-            phase_function: Isotropic::new(texture),
-            negative_inverse_density: -1. / density,
+            center: Offset::new(centroid),
+            vertices: [a - centroid, b - centroid, c - centroid],
+            material,
         }
     }
-}
 
-impl<H: Hittable> ConstantMedium<H, SolidColor> {
-    pub fn solid_color(boundary: H, color: Color, density: f32) -> Self {
-        Self {
-            boundary,
-            phase_function: Isotropic::solid_color(color),
-            negative_inverse_density: -1. / density,
-        }
+    pub fn material(&self) -> &M {
+        &self.material
     }
 }
 
-impl<H, T> Hittable for ConstantMedium<H, T>
-where
-    H: Hittable + Clone + 'static,
-    T: Texture + Clone + 'static,
-{
-    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let mut rng = rand::thread_rng();
+impl<M: Material + Clone + 'static> Hittable for Triangle<M> {
+    /// Möller–Trumbore intersection. `u` and `v` are the barycentric coordinates of `b` and `c`.
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let [a, b, c] = self.vertices;
+        let edge1 = b - a;
+        let edge2 = c - a;
 
-        let mut hit1 = self.boundary.hit(ray, -f32::INFINITY, f32::INFINITY)?;
-        let mut hit2 = self.boundary.hit(ray, hit1.t + 0.0001, f32::INFINITY)?;
+        let p = ray.direction().cross(&edge2);
+        let determinant = edge1.dot(&p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
 
-        if hit1.t < t_min {
-            hit1.t = t_min
-        };
-        if hit2.t > t_max {
-            hit2.t = t_max
-        };
+        let s = ray.origin() - a;
+        let u = s.dot(&p) / determinant;
+        if !(0. ..=1.).contains(&u) {
+            return None;
+        }
 
-        if hit1.t >= hit2.t {
+        let q = s.cross(&edge1);
+        let v = ray.direction().dot(&q) / determinant;
+        if v < 0. || u + v > 1. {
             return None;
         }
 
-        if hit1.t < 0. {
-            hit1.t = 0.
+        let t = edge2.dot(&q) / determinant;
+        if t < t_min || t > t_max {
+            return None;
         }
 
-        let ray_length = ray.direction().norm();
-        let distance_inside_boundary = (hit2.t - hit1.t) * ray_length;
-        let hit_distance = self.negative_inverse_density * rng.gen::<f32>().ln();
+        Some(HitRecord::from_ray(
+            ray.at(t),
+            u,
+            v,
+            edge1.cross(&edge2).normalize(),
+            t,
+            &self.material,
+            ray,
+        ))
+    }
 
-        if hit_distance > distance_inside_boundary {
-            return None;
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let [a, b, c] = self.vertices;
+        let minimum = a.inf(&b).inf(&c);
+        let maximum = a.sup(&b).sup(&c);
+        Some(Aabb::new(minimum, maximum).pad(0.0002))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Triangle<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A [`Triangle`] whose normal is interpolated between normals given at its vertices, so that meshes look smooth instead of faceted.
+///
+/// # Fields:
+/// - `triangle`: The underlying [`Triangle`], which determines where it is hit and its front face.
+/// - `normals`: The normals at its vertices.
+#[derive(Clone, Debug)]
+pub struct SmoothTriangle<M: Material> {
+    triangle: Triangle<M>,
+    normals: [Vector3<f32>; 3],
+}
+
+impl<M: Material> SmoothTriangle<M> {
+    /// Create a new [`SmoothTriangle`] from its vertices and the normals at them.
+    ///
+    /// The front face is the one from which `a`, `b`, `c` appear counterclockwise, the normals should point out of it.
+    pub fn new([a, b, c]: [Vector3<f32>; 3], normals: [Vector3<f32>; 3], material: M) -> Self {
+        Self {
+            triangle: Triangle::new(a, b, c, material),
+            normals: normals.map(|normal| normal.normalize()),
         }
+    }
 
-        let t = hit1.t + hit_distance / ray_length;
-        let point = ray.at(t);
-        Some(HitRecord::new(
+    pub fn material(&self) -> &M {
+        self.triangle.material()
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for SmoothTriangle<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut hit = self.triangle.hit_origin(ray, t_min, t_max)?;
+        let [a, b, c] = self.normals;
+        let normal = ((1. - hit.u - hit.v) * a + hit.u * b + hit.v * c).normalize();
+        hit.normal = if hit.front_face { normal } else { -normal };
+        Some(hit)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.triangle.bounding_box_origin(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        self.triangle.center()
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for SmoothTriangle<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.triangle = self.triangle.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.triangle = self.triangle.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A axis-aligned cuboid (3D rectangle).
+///
+/// # Fields:
+/// - `center`: Its [`Offset`].
+/// - `width`: Its width (in x direction).
+/// - `height`: Its height (in y direction).
+/// - `depth`: Its depth (in z direction).
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct Cuboid<M: Material> {
+    center: Offset,
+    width: f32,
+    height: f32,
+    depth: f32,
+    rectangles: HittableList,
+    material: M,
+}
+
+impl<M: Material + Clone + 'static> Cuboid<M> {
+    pub fn new(center: Vector3<f32>, width: f32, height: f32, depth: f32, material: M) -> Self {
+        let mut rectangles = HittableList::default();
+
+        let bottom = Rectangle::xz(
+            -vector![0., height / 2., 0.],
+            width,
+            depth,
+            material.clone(),
+        );
+        let top = Rectangle::xz(vector![0., height / 2., 0.], width, depth, material.clone());
+        let left = Rectangle::yz(
+            -vector![width / 2., 0., 0.],
+            height,
+            depth,
+            material.clone(),
+        );
+        let right = Rectangle::yz(vector![width / 2., 0., 0.], height, depth, material.clone());
+        let back = Rectangle::xy(
+            -vector![0., 0., depth / 2.],
+            width,
+            height,
+            material.clone(),
+        );
+        let front = Rectangle::xy(vector![0., 0., depth / 2.], width, height, material.clone());
+        rectangles.push(top);
+        rectangles.push(bottom);
+        rectangles.push(left);
+        rectangles.push(right);
+        rectangles.push(back);
+        rectangles.push(front);
+
+        let center = Offset::new(center);
+
+        Self {
+            center,
+            width,
+            height,
+            depth,
+            rectangles,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Cuboid<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.rectangles.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(Aabb::new(
+            -vector![self.width / 2., self.height / 2., self.depth / 2.],
+            vector![self.width / 2., self.height / 2., self.depth / 2.],
+        ))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Cuboid<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// An axis-aligned cuboid with rounded edges and corners.
+///
+/// It is intersected by sphere tracing its exact signed distance, so all edges and corners are a single smooth surface.
+///
+/// # Fields:
+/// - `center`: Its [`Offset`].
+/// - `width`: Its width (in x direction).
+/// - `height`: Its height (in y direction).
+/// - `depth`: Its depth (in z direction).
+/// - `corner_radius`: Radius of the rounded edges and corners, at most half of the smallest side.
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct RoundedBox<M: Material> {
+    center: Offset,
+    width: f32,
+    height: f32,
+    depth: f32,
+    corner_radius: f32,
+    material: M,
+}
+
+impl<M: Material> RoundedBox<M> {
+    pub fn new(
+        center: Vector3<f32>,
+        width: f32,
+        height: f32,
+        depth: f32,
+        corner_radius: f32,
+        material: M,
+    ) -> Self {
+        let corner_radius = corner_radius.clamp(0., width.min(height).min(depth) / 2.);
+        Self {
+            center: Offset::new(center),
+            width,
+            height,
+            depth,
+            corner_radius,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn corner_radius(&self) -> f32 {
+        self.corner_radius
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+
+    fn half_extent(&self) -> Vector3<f32> {
+        vector![self.width, self.height, self.depth] / 2.
+    }
+
+    fn distance(&self, point: Vector3<f32>) -> f32 {
+        let q = point.abs() - self.half_extent() + Vector3::repeat(self.corner_radius);
+        q.sup(&Vector3::zeros()).norm() + q.max().min(0.) - self.corner_radius
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for RoundedBox<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let aabb = self.bounding_box_origin(0., 0.)?;
+        let (t_start, t_end) = aabb.hit_interval(ray, t_min, t_max)?;
+
+        let epsilon = 1e-4 * self.half_extent().min().max(1e-3);
+        let (t, point, normal) =
+            sphere_trace(|point| self.distance(point), ray, t_start, t_end, epsilon)?;
+
+        // Like a cuboid, the faces are parametrized along the two axes perpendicular to the dominant normal direction.
+        let half_extent = self.half_extent();
+        let axis = normal.iamax();
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let u = (point[a] + half_extent[a]) / (2. * half_extent[a]);
+        let v = (point[b] + half_extent[b]) / (2. * half_extent[b]);
+
+        Some(HitRecord::from_ray(
             point,
-            0.,
-            0.,
-            Vector3::zeros(),
+            u,
+            v,
+            normal,
             t,
-            true,
-            &self.phase_function,
+            &self.material,
+            ray,
         ))
     }
 
-    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
-        self.boundary.bounding_box_origin(time0, time1)
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let half_extent = self.half_extent().abs();
+        Some(Aabb::new(-half_extent, half_extent).pad(0.0002))
     }
 
     fn center(&self) -> &Offset {
-        self.boundary.center()
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for RoundedBox<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A hollow sphere with walls of a given thickness (e.g. bubbles or glassware).
+///
+/// It consists of an outer [`Sphere`] and an inner one with negative radius, so that the normals of the inner surface point into the hollow.
+///
+/// # Fields:
+/// - `center`: Its [`Offset`].
+/// - `outer_radius`: Radius of the outer surface.
+/// - `thickness`: Thickness of the wall (clamped to `outer_radius`).
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct Shell<M: Material> {
+    center: Offset,
+    outer_radius: f32,
+    thickness: f32,
+    spheres: HittableList,
+    material: M,
+}
+
+impl<M: Material + Clone + 'static> Shell<M> {
+    pub fn new(center: Vector3<f32>, outer_radius: f32, thickness: f32, material: M) -> Self {
+        let thickness = thickness.clamp(0., outer_radius);
+
+        let mut spheres = HittableList::default();
+        spheres.push(Sphere::new(
+            Vector3::zeros(),
+            outer_radius,
+            material.clone(),
+        ));
+        spheres.push(Sphere::new(
+            Vector3::zeros(),
+            -(outer_radius - thickness),
+            material.clone(),
+        ));
+
+        Self {
+            center: Offset::new(center),
+            outer_radius,
+            thickness,
+            spheres,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn outer_radius(&self) -> f32 {
+        self.outer_radius
+    }
+
+    pub fn thickness(&self) -> f32 {
+        self.thickness
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Shell<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.spheres.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let radius = self.outer_radius.abs();
+        Some(Aabb::new(
+            -vector![radius, radius, radius],
+            vector![radius, radius, radius],
+        ))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Shell<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// An infinite plane.
+///
+/// As it is infinite, it has no bounding box, so a [`Raytracer`](crate::raytracer::Raytracer) containing it renders without a [`Bvh`](crate::hittable::Bvh).
+///
+/// # Fields:
+/// - `center`: Its [`Offset`], a point on the plane.
+/// - `normal`: Its unit normal.
+/// - `tangents`: Two unit vectors spanning the plane, along which `u` and `v` are measured.
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct InfinitePlane<M: Material> {
+    center: Offset,
+    normal: Vector3<f32>,
+    tangents: [Vector3<f32>; 2],
+    material: M,
+}
+
+impl<M: Material> InfinitePlane<M> {
+    pub fn new(point: Vector3<f32>, normal: Vector3<f32>, material: M) -> Self {
+        let normal = normal.normalize();
+
+        Self {
+            center: Offset::new(point),
+            normal,
+            tangents: vec3::orthonormal_basis(&normal),
+            material,
+        }
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for InfinitePlane<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(&ray.direction());
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let t = -self.normal.dot(&ray.origin()) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = ray.at(t);
+        Some(HitRecord::from_ray(
+            point,
+            self.tangents[0].dot(&point),
+            self.tangents[1].dot(&point),
+            self.normal,
+            t,
+            &self.material,
+            ray,
+        ))
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        None
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for InfinitePlane<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A flat disk, or an annulus if it has an inner radius (e.g. lenses or washers).
+///
+/// # Fields:
+/// - `center`: Its [`Offset`], the center of the disk.
+/// - `normal`: Its unit normal.
+/// - `tangents`: Two unit vectors spanning the plane of the disk, the polar angle `v` is measured from the first towards the second.
+/// - `radius`: Its outer radius.
+/// - `inner_radius`: Radius of the hole in the middle (zero for a full disk).
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct Disk<M: Material> {
+    center: Offset,
+    normal: Vector3<f32>,
+    tangents: [Vector3<f32>; 2],
+    radius: f32,
+    inner_radius: f32,
+    material: M,
+}
+
+impl<M: Material> Disk<M> {
+    pub fn new(center: Vector3<f32>, normal: Vector3<f32>, radius: f32, material: M) -> Self {
+        let normal = normal.normalize();
+
+        Self {
+            center: Offset::new(center),
+            normal,
+            tangents: vec3::orthonormal_basis(&normal),
+            radius,
+            inner_radius: 0.,
+            material,
+        }
+    }
+
+    /// Create an annulus, i.e. a [`Disk`] with a hole of `inner_radius`.
+    pub fn annulus(
+        center: Vector3<f32>,
+        normal: Vector3<f32>,
+        inner_radius: f32,
+        radius: f32,
+        material: M,
+    ) -> Self {
+        Self {
+            inner_radius,
+            ..Self::new(center, normal, radius, material)
+        }
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn inner_radius(&self) -> f32 {
+        self.inner_radius
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Disk<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.dot(&ray.direction());
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let t = -self.normal.dot(&ray.origin()) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let r = point.norm();
+        if r < self.inner_radius || r > self.radius {
+            return None;
+        }
+
+        let theta = self.tangents[1]
+            .dot(&point)
+            .atan2(self.tangents[0].dot(&point));
+        Some(HitRecord::from_ray(
+            point,
+            r / self.radius,
+            (theta / (2. * PI)).rem_euclid(1.),
+            self.normal,
+            t,
+            &self.material,
+            ray,
+        ))
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        // A circle of radius r perpendicular to n extends r * sqrt(1 - n_i²) along each axis i.
+        let extent = self
+            .normal
+            .map(|n| self.radius * (1. - n * n).max(0.).sqrt());
+        Some(Aabb::new(-extent, extent).pad(0.0002))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Disk<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// An infinite horizontal diffuse floor with a checkerboard of two [`Texture`]s.
+///
+/// This is an [`InfinitePlane`] with a [`Lambertian`] [`CheckerTexture`] in (u, v) space.
+/// As it is infinite, it has no bounding box, so a [`Raytracer`](crate::raytracer::Raytracer) containing it renders without a [`Bvh`](crate::hittable::Bvh).
+///
+/// # Fields:
+/// - `center`: Its [`Offset`].
+/// - `plane`: The checkered [`InfinitePlane`] at the height of the floor.
+#[derive(Clone, Debug)]
+pub struct CheckeredFloor<A: Texture, B: Texture> {
+    center: Offset,
+    plane: InfinitePlane<Lambertian<CheckerTexture<A, B>>>,
+}
+
+impl<A: Texture, B: Texture> CheckeredFloor<A, B> {
+    /// Create a floor at height `y` with checks of side length `scale`, `texture_a` on the checks containing the origin and `texture_b` on the others.
+    ///
+    /// Returns [`None`] if `scale` is not positive.
+    pub fn new(y: f32, scale: f32, texture_a: A, texture_b: B) -> Option<Self> {
+        if scale.is_nan() || scale <= 0. {
+            return None;
+        }
+
+        Some(Self {
+            center: Offset::default(),
+            plane: InfinitePlane::new(
+                vector![0., y, 0.],
+                Vector3::y(),
+                Lambertian::new(CheckerTexture::uv(texture_a, texture_b, 1. / scale)),
+            ),
+        })
+    }
+}
+
+impl CheckeredFloor<SolidColor, SolidColor> {
+    /// Create the classic diffuse checkerboard floor (see [`new`](CheckeredFloor::new)).
+    pub fn solid_colors(y: f32, scale: f32, color_a: Color, color_b: Color) -> Option<Self> {
+        Self::new(y, scale, SolidColor::new(color_a), SolidColor::new(color_b))
+    }
+}
+
+impl<A, B> Hittable for CheckeredFloor<A, B>
+where
+    A: Texture + Clone + 'static,
+    B: Texture + Clone + 'static,
+{
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.plane.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        None
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+/// A solid prism with a regular polygon as its cross-section (e.g. dice, crystals, or columns), extruded along the y axis.
+///
+/// It is built from one [`Quad`] per side and a fan of [`Triangle`]s for each cap, like a [`Cuboid`] from its [`Rectangle`]s.
+/// The outward normal of the first side points along the x axis, so a 4-sided prism is an axis-aligned square column.
+///
+/// # Fields:
+/// - `center`: Its [`Offset`].
+/// - `sides`: Number of sides of the polygon (at least 3).
+/// - `radius`: Radius of the circumscribed circle of the polygon.
+/// - `height`: Its height (in y direction).
+/// - `faces`: The side [`Quad`]s and the cap [`Triangle`]s.
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct Prism<M: Material> {
+    center: Offset,
+    sides: u8,
+    radius: f32,
+    height: f32,
+    faces: HittableList,
+    material: M,
+}
+
+impl<M: Material + Clone + 'static> Prism<M> {
+    pub fn new(center: Vector3<f32>, sides: u8, radius: f32, height: f32, material: M) -> Self {
+        let sides = sides.max(3);
+        let angle = 2. * PI / sides as f32;
+        let (bottom, top) = (-vector![0., height / 2., 0.], vector![0., height / 2., 0.]);
+        // The vertices of the bottom polygon, offset by half a side so that the first side faces along x.
+        let vertex = |index: u8| {
+            let (sin, cos) = ((index as f32 - 0.5) * angle).sin_cos();
+            bottom + radius * vector![cos, 0., sin]
+        };
+
+        let mut faces = HittableList::default();
+        for side in 0..sides {
+            let (start, end) = (vertex(side), vertex(side + 1));
+            faces.push(Quad::new(
+                start,
+                top - bottom,
+                end - start,
+                material.clone(),
+            ));
+            faces.push(Triangle::new(bottom, start, end, material.clone()));
+            faces.push(Triangle::new(
+                top,
+                end + top - bottom,
+                start + top - bottom,
+                material.clone(),
+            ));
+        }
+
+        Self {
+            center: Offset::new(center),
+            sides,
+            radius,
+            height,
+            faces,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn sides(&self) -> u8 {
+        self.sides
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Prism<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.faces.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(Aabb::new(
+            -vector![self.radius.abs(), self.height.abs() / 2., self.radius.abs()],
+            vector![self.radius.abs(), self.height.abs() / 2., self.radius.abs()],
+        ))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Prism<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// Find the first root of a signed distance function along a [`Ray`] by sphere tracing.
+///
+/// Marches from `t_start` to `t_end` in steps of the (absolute) distance, so it works both from outside and from inside the surface.
+/// Returns the parameter, the point, and the outward normal (gradient of the distance) of the hit.
+fn sphere_trace<F: Fn(Vector3<f32>) -> f32>(
+    distance: F,
+    ray: Ray,
+    t_start: f32,
+    t_end: f32,
+    epsilon: f32,
+) -> Option<(f32, Vector3<f32>, Vector3<f32>)> {
+    const MAX_STEPS: usize = 512;
+
+    let ray_length = ray.direction().norm();
+
+    let mut t = t_start;
+    for _ in 0..MAX_STEPS {
+        if t > t_end {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let step = distance(point).abs();
+
+        if step < epsilon {
+            let normal = vector![
+                distance(point + epsilon * Vector3::x()) - distance(point - epsilon * Vector3::x()),
+                distance(point + epsilon * Vector3::y()) - distance(point - epsilon * Vector3::y()),
+                distance(point + epsilon * Vector3::z()) - distance(point - epsilon * Vector3::z())
+            ]
+            .normalize();
+
+            return Some((t, point, normal));
+        }
+
+        t += step.max(epsilon) / ray_length;
+    }
+
+    None
+}
+
+/// A tube swept along a cubic Bézier curve (e.g. hair, grass, or cables).
+///
+/// The centerline is approximated by [`CURVE_SEGMENTS`] straight segments whose radii are interpolated linearly between `radius_start` and `radius_end`.
+/// Each segment forms a cone capped by spheres, and the resulting swept-sphere surface is intersected by sphere tracing its signed distance.
+///
+/// # Fields
+/// - `center`: Its [`Offset`] (the centroid of the control points).
+/// - `control_points`: The four Bézier control points relative to `center`.
+/// - `radius_start`: Radius at the first control point.
+/// - `radius_end`: Radius at the last control point.
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct Curve<M: Material> {
+    center: Offset,
+    control_points: [Vector3<f32>; 4],
+    radius_start: f32,
+    radius_end: f32,
+    segments: Vec<(Vector3<f32>, Vector3<f32>)>,
+    material: M,
+}
+
+/// Number of straight segments a [`Curve`] is divided into.
+pub const CURVE_SEGMENTS: usize = 16;
+
+impl<M: Material> Curve<M> {
+    /// Create a new stationary [`Curve`] from its control points in world space.
+    pub fn new(
+        control_points: [Vector3<f32>; 4],
+        radius_start: f32,
+        radius_end: f32,
+        material: M,
+    ) -> Self {
+        let centroid = control_points.iter().sum::<Vector3<f32>>() / 4.;
+        let control_points = control_points.map(|point| point - centroid);
+
+        let segments = (0..CURVE_SEGMENTS)
+            .map(|i| {
+                (
+                    Curve::<M>::bezier(&control_points, i as f32 / CURVE_SEGMENTS as f32),
+                    Curve::<M>::bezier(&control_points, (i + 1) as f32 / CURVE_SEGMENTS as f32),
+                )
+            })
+            .collect();
+
+        Self {
+            center: Offset::new(centroid),
+            control_points,
+            radius_start,
+            radius_end,
+            segments,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn control_points(&self) -> [Vector3<f32>; 4] {
+        self.control_points
+            .map(|point| point + self.center.offset_start)
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+
+    /// Evaluate the Bézier curve at parameter `t` in \[0,1\].
+    fn bezier(control_points: &[Vector3<f32>; 4], t: f32) -> Vector3<f32> {
+        let s = 1. - t;
+        s.powi(3) * control_points[0]
+            + 3. * s.powi(2) * t * control_points[1]
+            + 3. * s * t.powi(2) * control_points[2]
+            + t.powi(3) * control_points[3]
+    }
+
+    fn radius(&self, t: f32) -> f32 {
+        self.radius_start + t * (self.radius_end - self.radius_start)
+    }
+
+    /// Signed distance of `point` to the tube together with the curve parameter of the closest segment.
+    fn distance(&self, point: Vector3<f32>) -> (f32, f32) {
+        let mut closest = (f32::INFINITY, 0.);
+
+        for (i, (start, end)) in self.segments.iter().enumerate() {
+            let t_start = i as f32 / CURVE_SEGMENTS as f32;
+            let t_end = (i + 1) as f32 / CURVE_SEGMENTS as f32;
+            let distance = Curve::<M>::round_cone_distance(
+                point,
+                *start,
+                *end,
+                self.radius(t_start),
+                self.radius(t_end),
+            );
+
+            if distance < closest.0 {
+                let axis = end - start;
+                let fraction = ((point - start).dot(&axis) / axis.norm_squared()).clamp(0., 1.);
+                closest = (distance, t_start + fraction * (t_end - t_start));
+            }
+        }
+
+        closest
+    }
+
+    /// Exact signed distance to a cone between `a` and `b` with radii `ra` and `rb` capped by spheres.
+    fn round_cone_distance(
+        point: Vector3<f32>,
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        ra: f32,
+        rb: f32,
+    ) -> f32 {
+        let ba = b - a;
+        let l2 = ba.norm_squared();
+        if l2 < f32::EPSILON {
+            return (point - a).norm() - ra.max(rb);
+        }
+
+        let rr = ra - rb;
+        let a2 = l2 - rr * rr;
+        let pa = point - a;
+        let y = pa.dot(&ba);
+        let z = y - l2;
+        let x2 = (pa * l2 - ba * y).norm_squared();
+        let y2 = y * y * l2;
+        let z2 = z * z * l2;
+        let k = rr.signum() * rr * rr * x2;
+
+        if z.signum() * a2 * z2 > k {
+            return (x2 + z2).sqrt() / l2 - rb;
+        }
+        if y.signum() * a2 * y2 < k {
+            return (x2 + y2).sqrt() / l2 - ra;
+        }
+        ((x2 * a2 / l2).sqrt() + y * rr) / l2 - ra
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Curve<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let aabb = self.bounding_box_origin(0., 0.)?;
+        let (t_start, t_end) = aabb.hit_interval(ray, t_min, t_max)?;
+
+        let epsilon = 1e-4 * self.radius_start.max(self.radius_end).max(1e-3);
+        let (t, point, normal) =
+            sphere_trace(|point| self.distance(point).0, ray, t_start, t_end, epsilon)?;
+
+        Some(HitRecord::from_ray(
+            point,
+            self.distance(point).1,
+            0.,
+            normal,
+            t,
+            &self.material,
+            ray,
+        ))
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let radius = self.radius_start.abs().max(self.radius_end.abs());
+        let padding = vector![radius, radius, radius];
+
+        let mut minimum = self.control_points[0];
+        let mut maximum = self.control_points[0];
+        for point in &self.control_points[1..] {
+            minimum = minimum.inf(point);
+            maximum = maximum.sup(point);
+        }
+
+        Some(Aabb::new(minimum - padding, maximum + padding))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Curve<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A torus around the y axis (e.g. rings or donuts).
+///
+/// The ray-torus intersection is a quartic, whose first root is found by sphere tracing the exact signed distance of the torus.
+///
+/// # Fields
+/// - `center`: Its [`Offset`].
+/// - `major_radius`: Distance from the center to the middle of the tube.
+/// - `minor_radius`: Radius of the tube.
+/// - `material`: Its material.
+#[derive(Clone, Debug)]
+pub struct Torus<M: Material> {
+    center: Offset,
+    major_radius: f32,
+    minor_radius: f32,
+    material: M,
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(center: Vector3<f32>, major_radius: f32, minor_radius: f32, material: M) -> Self {
+        Self {
+            center: Offset::new(center),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    pub fn major_radius(&self) -> f32 {
+        self.major_radius
+    }
+
+    pub fn minor_radius(&self) -> f32 {
+        self.minor_radius
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+
+    /// Closest point on the circle through the middle of the tube.
+    fn ring_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        vector![point.x, 0., point.z]
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::x)
+            * self.major_radius
+    }
+}
+
+impl<M: Material + Clone + 'static> Hittable for Torus<M> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let aabb = self.bounding_box_origin(0., 0.)?;
+        let (t_start, t_end) = aabb.hit_interval(ray, t_min, t_max)?;
+
+        let epsilon = 1e-4 * self.minor_radius.max(1e-3);
+        let (t, point, _) = sphere_trace(
+            |point| (point - self.ring_point(point)).norm() - self.minor_radius,
+            ray,
+            t_start,
+            t_end,
+            epsilon,
+        )?;
+
+        // The normal points away from the middle of the tube, which is more accurate than the finite differences.
+        let normal = (point - self.ring_point(point)).normalize();
+        let u = 1. - (point.z.atan2(point.x) + PI) / (2. * PI);
+        let outward = self.ring_point(point) / self.major_radius;
+        let v = (normal.y.atan2(normal.dot(&outward)) / (2. * PI)).rem_euclid(1.);
+
+        Some(
+            HitRecord::from_ray(point, u, v, normal, t, &self.material, ray)
+                .with_tangent(azimuthal_tangent(point)),
+        )
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let outer = self.major_radius.abs() + self.minor_radius.abs();
+        let extent = vector![outer, self.minor_radius.abs(), outer];
+        Some(Aabb::new(-extent, extent))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<M: Material + Clone + 'static> Movable for Torus<M> {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A 2D shape in the xy plane given by a signed distance function and extruded along the z axis.
+///
+/// This allows rendering e.g. letters or logos without meshing them. The resulting solid is intersected by sphere tracing inside `bounds`.
+/// For correct results, `sdf` must not overestimate the distance to the shape's outline (negative inside, positive outside).
+///
+/// # Fields
+/// - `center`: Its [`Offset`].
+/// - `sdf`: Signed distance function of the 2D shape.
+/// - `depth`: Its depth (in z direction), centered around `center`.
+/// - `bounds`: [`Aabb`] relative to `center` that encloses the whole solid.
+/// - `material`: Its material.
+#[derive(Clone)]
+pub struct Extrusion<F, M>
+where
+    F: Fn(f32, f32) -> f32,
+    M: Material,
+{
+    center: Offset,
+    sdf: F,
+    depth: f32,
+    bounds: Aabb,
+    material: M,
+}
+
+impl<F, M> Extrusion<F, M>
+where
+    F: Fn(f32, f32) -> f32,
+    M: Material,
+{
+    pub fn new(center: Vector3<f32>, sdf: F, depth: f32, bounds: Aabb, material: M) -> Self {
+        Self {
+            center: Offset::new(center),
+            sdf,
+            depth,
+            bounds,
+            material,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+
+    /// Signed distance of `point` to the extruded solid.
+    fn distance(&self, point: Vector3<f32>) -> f32 {
+        let outline = (self.sdf)(point.x, point.y);
+        let caps = point.z.abs() - self.depth / 2.;
+        outline.max(caps).min(0.) + vector![outline.max(0.), caps.max(0.)].norm()
+    }
+}
+
+impl<F, M> Debug for Extrusion<F, M>
+where
+    F: Fn(f32, f32) -> f32,
+    M: Material,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extrusion")
+            .field("center", &self.center)
+            .field("depth", &self.depth)
+            .field("bounds", &self.bounds)
+            .field("material", &self.material)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, M> Hittable for Extrusion<F, M>
+where
+    F: Fn(f32, f32) -> f32 + Send + Sync,
+    M: Material + Clone + 'static,
+{
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t_start, t_end) = self.bounds.hit_interval(ray, t_min, t_max)?;
+        let (t, point, normal) =
+            sphere_trace(|point| self.distance(point), ray, t_start, t_end, 1e-4)?;
+
+        let u = (point.x - self.bounds.minimum.x) / (self.bounds.maximum.x - self.bounds.minimum.x);
+        let v = (point.y - self.bounds.minimum.y) / (self.bounds.maximum.y - self.bounds.minimum.y);
+
+        Some(HitRecord::from_ray(
+            point,
+            u,
+            v,
+            normal,
+            t,
+            &self.material,
+            ray,
+        ))
+    }
+
+    fn bounding_box_origin(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl<F, M> Movable for Extrusion<F, M>
+where
+    F: Fn(f32, f32) -> f32 + Clone + Send + Sync,
+    M: Material + Clone + 'static,
+{
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// A medium of constant optical density.
+#[derive(Clone, Debug)]
+pub struct ConstantMedium<H: Hittable, T: Texture> {
+    boundary: H,
+    phase_function: Isotropic<T>,
+    negative_inverse_density: f32,
+}
+
+impl<H: Hittable, T: Texture> ConstantMedium<H, T> {
+    pub fn new(boundary: H, texture: T, density: f32) -> Self {
+        Self {
+            boundary,
+            phase_function: Isotropic::new(texture),
+            negative_inverse_density: -1. / density,
+        }
+    }
+}
+
+impl<H: Hittable, T: Texture> ConstantMedium<H, T> {
+    /// Sample the distance a [`Ray`] travels inside the medium before scattering.
+    ///
+    /// The distance is exponentially distributed. Passing a seeded `rng` makes it deterministic.
+    pub fn scatter_distance<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        // `1 - gen()` lies in (0, 1], so the logarithm is always finite.
+        self.negative_inverse_density * (1. - rng.gen::<f32>()).ln()
+    }
+}
+
+impl<H: Hittable> ConstantMedium<H, SolidColor> {
+    pub fn solid_color(boundary: H, color: Color, density: f32) -> Self {
+        Self {
+            boundary,
+            phase_function: Isotropic::solid_color(color),
+            negative_inverse_density: -1. / density,
+        }
+    }
+}
+
+impl<H, T> Hittable for ConstantMedium<H, T>
+where
+    H: Hittable + Clone + 'static,
+    T: Texture + Clone + 'static,
+{
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut rng = random::rng();
+
+        let mut hit1 = self.boundary.hit(ray, -f32::INFINITY, f32::INFINITY)?;
+        let mut hit2 = self.boundary.hit(ray, hit1.t + 0.0001, f32::INFINITY)?;
+
+        if hit1.t < t_min {
+            hit1.t = t_min
+        };
+        if hit2.t > t_max {
+            hit2.t = t_max
+        };
+
+        if hit1.t >= hit2.t {
+            return None;
+        }
+
+        if hit1.t < 0. {
+            hit1.t = 0.
+        }
+
+        let ray_length = ray.direction().norm();
+        let distance_inside_boundary = (hit2.t - hit1.t) * ray_length;
+        let hit_distance = self.scatter_distance(&mut rng);
+
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let t = hit1.t + hit_distance / ray_length;
+        let point = ray.at(t);
+        Some(HitRecord::new(
+            point,
+            0.,
+            0.,
+            Vector3::zeros(),
+            t,
+            true,
+            &self.phase_function,
+        ))
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box_origin(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        self.boundary.center()
+    }
+}
+
+/// Remaps the time of incoming [`Ray`]s before passing them on to another [`Hittable`].
+///
+/// This retimes the motion blur of moving objects: a `factor` below 1 slows the motion down within the shutter interval, a `factor` above 1 speeds it up.
+///
+/// # Fields
+/// - `inner`: Wrapped [`Hittable`].
+/// - `factor`: Factor by which the time of each [`Ray`] is multiplied.
+#[derive(Clone, Debug)]
+pub struct TimeWarp<H: Hittable> {
+    center: Offset,
+    inner: H,
+    factor: f32,
+}
+
+impl<H: Hittable> TimeWarp<H> {
+    pub fn new(inner: H, factor: f32) -> Self {
+        Self {
+            center: Offset::default(),
+            inner,
+            factor,
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for TimeWarp<H> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let warped_ray = ray.with_time(self.factor * ray.time());
+        self.inner.hit(warped_ray, t_min, t_max)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let (time0, time1) = (self.factor * time0, self.factor * time1);
+        self.inner.bounding_box(time0.min(time1), time0.max(time1))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+/// Counts how many [`Ray`]s hit another [`Hittable`], e.g. for finding out which objects are worth optimizing.
+///
+/// Clones share the same counter, so a clone can be kept to query the count after the original was moved into a [`Raytracer`].
+///
+/// # Fields
+/// - `inner`: Wrapped [`Hittable`].
+/// - `hits`: Number of hits so far.
+#[derive(Clone, Debug)]
+pub struct Profiled<H: Hittable> {
+    center: Offset,
+    inner: H,
+    hits: Arc<AtomicU64>,
+}
+
+impl<H: Hittable> Profiled<H> {
+    pub fn new(inner: H) -> Self {
+        Self {
+            center: Offset::default(),
+            inner,
+            hits: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Number of [`Ray`]s that hit `inner` so far.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(atomic::Ordering::Relaxed)
+    }
+}
+
+impl<H: Hittable> Hittable for Profiled<H> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let hit_record = self.inner.hit(ray, t_min, t_max);
+        if hit_record.is_some() {
+            self.hits.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+        hit_record
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+/// Places shared geometry (e.g. a [`Bvh`](crate::hittable::Bvh) of a mesh) in the scene without cloning it.
+///
+/// Many instances can reference the same [`Hittable`], each with its own position, rotation, and movement.
+///
+/// # Fields
+/// - `center`: Its [`Offset`], applied on top of the one of `inner`.
+/// - `inner`: Shared [`Hittable`].
+#[derive(Clone, Debug)]
+pub struct Instance {
+    center: Offset,
+    inner: Arc<dyn Hittable>,
+}
+
+impl Instance {
+    pub fn new(inner: Arc<dyn Hittable>, position: Vector3<f32>) -> Self {
+        Self {
+            center: Offset::new(position),
+            inner,
+        }
+    }
+
+    pub fn position(&self, time: f32) -> Vector3<f32> {
+        self.center.offset(time)
+    }
+
+    pub fn inner(&self) -> &Arc<dyn Hittable> {
+        &self.inner
+    }
+}
+
+impl Hittable for Instance {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.inner.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.inner.bounding_box(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+impl Movable for Instance {
+    fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.center = self.center.with_rotation(rotation);
+        self
+    }
+
+    fn moving(mut self, offset_end: Vector3<f32>, time_start: f32, time_end: f32) -> Self {
+        self.center = self.center.moving(offset_end, time_start, time_end);
+        self
+    }
+}
+
+/// Find the first hit on the surface of a constructive solid geometry combination of the closed [`Hittable`]s `a` and `b`.
+///
+/// Walks the hits on both surfaces in order. The next hit on the other surface tells whether a hit is inside of it:
+/// a back face means the [`Ray`] is about to leave it. `keep(from_a, inside_other)` decides whether a hit is part of the combined surface.
+/// Returns the hit and whether it lies on `a`.
+fn csg_hit<'a>(
+    a: &'a dyn Hittable,
+    b: &'a dyn Hittable,
+    ray: Ray,
+    t_min: f32,
+    t_max: f32,
+    keep: impl Fn(bool, bool) -> bool,
+) -> Option<(HitRecord<'a>, bool)> {
+    let mut t = t_min;
+    loop {
+        let hit_a = a.hit(ray, t, f32::INFINITY);
+        let hit_b = b.hit(ray, t, f32::INFINITY);
+        let t_a = hit_a.as_ref().map_or(f32::INFINITY, |hit| hit.t);
+        let t_b = hit_b.as_ref().map_or(f32::INFINITY, |hit| hit.t);
+
+        if hit_a.is_none() && hit_b.is_none() || t_a.min(t_b) > t_max {
+            return None;
+        }
+
+        if t_a <= t_b {
+            let inside_b = hit_b.is_some_and(|hit| !hit.front_face);
+            if keep(true, inside_b) {
+                return Some((hit_a?, true));
+            }
+            t = t_a + 0.0001;
+        } else {
+            let inside_a = hit_a.is_some_and(|hit| !hit.front_face);
+            if keep(false, inside_a) {
+                return Some((hit_b?, false));
+            }
+            t = t_b + 0.0001;
+        }
+    }
+}
+
+/// Constructive solid geometry: the combined solid of two closed [`Hittable`]s (e.g. a capsule from a cylinder and two spheres).
+///
+/// Unlike a [`HittableList`], the parts of each surface inside of the other one are dropped, which matters for transparent materials.
+///
+/// # Fields
+/// - `a`: First [`Hittable`].
+/// - `b`: Second [`Hittable`].
+#[derive(Clone, Debug)]
+pub struct Union<A: Hittable, B: Hittable> {
+    center: Offset,
+    a: A,
+    b: B,
+}
+
+impl<A: Hittable, B: Hittable> Union<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            center: Offset::default(),
+            a,
+            b,
+        }
+    }
+}
+
+impl<A: Hittable, B: Hittable> Hittable for Union<A, B> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        csg_hit(&self.a, &self.b, ray, t_min, t_max, |_, inside_other| {
+            !inside_other
+        })
+        .map(|(hit, _)| hit)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let box_a = self.a.bounding_box(time0, time1)?;
+        let box_b = self.b.bounding_box(time0, time1)?;
+        Some(box_a.surrounding(&box_b))
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+/// Constructive solid geometry: the part shared by two closed [`Hittable`]s (e.g. a lens from two spheres).
+///
+/// # Fields
+/// - `a`: First [`Hittable`].
+/// - `b`: Second [`Hittable`].
+#[derive(Clone, Debug)]
+pub struct Intersection<A: Hittable, B: Hittable> {
+    center: Offset,
+    a: A,
+    b: B,
+}
+
+impl<A: Hittable, B: Hittable> Intersection<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            center: Offset::default(),
+            a,
+            b,
+        }
+    }
+}
+
+impl<A: Hittable, B: Hittable> Hittable for Intersection<A, B> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        csg_hit(&self.a, &self.b, ray, t_min, t_max, |_, inside_other| {
+            inside_other
+        })
+        .map(|(hit, _)| hit)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.a.bounding_box(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+/// Constructive solid geometry: the part of one closed [`Hittable`] that lies outside of another (e.g. a sphere with a bore).
+///
+/// The surface consists of the parts of `a` outside of `b` and the parts of `b` inside of `a`, the latter facing the other way.
+///
+/// # Fields
+/// - `a`: [`Hittable`] that is carved.
+/// - `b`: [`Hittable`] that is removed from `a`.
+#[derive(Clone, Debug)]
+pub struct Difference<A: Hittable, B: Hittable> {
+    center: Offset,
+    a: A,
+    b: B,
+}
+
+impl<A: Hittable, B: Hittable> Difference<A, B> {
+    pub fn new(a: A, b: B) -> Self {
+        Self {
+            center: Offset::default(),
+            a,
+            b,
+        }
+    }
+}
+
+impl<A: Hittable, B: Hittable> Hittable for Difference<A, B> {
+    fn hit_origin(&self, ray: Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (mut hit, from_a) = csg_hit(
+            &self.a,
+            &self.b,
+            ray,
+            t_min,
+            t_max,
+            |from_a, inside_other| from_a != inside_other,
+        )?;
+        if !from_a {
+            hit.front_face = !hit.front_face;
+        }
+        Some(hit)
+    }
+
+    fn bounding_box_origin(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        self.a.bounding_box(time0, time1)
+    }
+
+    fn center(&self) -> &Offset {
+        &self.center
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::mock::StepRng;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::color::WHITE;
+    use crate::materials::Lambertian;
+    use crate::vec3::random_vector_in_range;
+
+    #[test]
+    fn sphere_hit_both() {
+        let center = vector![1., 2., -5.];
+        let sphere = Sphere::new(center, 1., Lambertian::solid_color(WHITE));
+
+        let ray = Ray::new(vector![1., 2., 0.], vector![0., 0., -1.]);
+        let (near, far) = sphere.hit_both(ray, 0., f32::INFINITY).unwrap();
+
+        assert!((near.t - 4.).abs() < 1e-5);
+        assert!((far.t - 6.).abs() < 1e-5);
+        assert!((near.point + far.point - 2. * center).norm() < 1e-5);
+        assert!(near.front_face);
+        assert!(!far.front_face);
+
+        assert!(sphere.hit_both(ray, 5., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn straight_curve_is_thin_cylinder() {
+        let curve = Curve::new(
+            [
+                vector![-1., 0., 0.],
+                vector![-1. / 3., 0., 0.],
+                vector![1. / 3., 0., 0.],
+                vector![1., 0., 0.],
+            ],
+            0.1,
+            0.1,
+            Lambertian::solid_color(color![1., 1., 1.]),
+        );
+
+        let ray = Ray::new(vector![0.3, 0., 5.], vector![0., 0., -1.]);
+        let hit = curve.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.9).abs() < 1e-3);
+        assert!((hit.normal - vector![0., 0., 1.]).norm() < 1e-2);
+        assert!(hit.front_face);
+
+        let ray_offset = Ray::new(vector![0.3, 0.2, 5.], vector![0., 0., -1.]);
+        assert!(curve.hit(ray_offset, 0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn extruded_circle() {
+        let extrusion = Extrusion::new(
+            vector![0., 0., 0.],
+            |x: f32, y: f32| (x * x + y * y).sqrt() - 1.,
+            2.,
+            Aabb::new(vector![-1., -1., -1.], vector![1., 1., 1.]),
+            Lambertian::solid_color(WHITE),
+        );
+
+        let ray_side = Ray::new(vector![5., 0., 0.5], vector![-1., 0., 0.]);
+        let hit = extrusion.hit(ray_side, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-3);
+        assert!((hit.normal - vector![1., 0., 0.]).norm() < 1e-2);
+
+        let ray_cap = Ray::new(vector![0.5, 0.5, 5.], vector![0., 0., -1.]);
+        let hit = extrusion.hit(ray_cap, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-3);
+        assert!((hit.normal - vector![0., 0., 1.]).norm() < 1e-2);
+
+        let ray_miss = Ray::new(vector![0.9, 0.9, 5.], vector![0., 0., -1.]);
+        assert!(extrusion.hit(ray_miss, 0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn constant_medium_scatter_distance() {
+        let medium = ConstantMedium::solid_color(
+            Sphere::new(vector![0., 0., 0.], 1., Lambertian::solid_color(WHITE)),
+            WHITE,
+            0.5,
+        );
+
+        let distance1 = medium.scatter_distance(&mut StdRng::seed_from_u64(42));
+        let distance2 = medium.scatter_distance(&mut StdRng::seed_from_u64(42));
+        assert_eq!(distance1, distance2);
+
+        let distance_zero = medium.scatter_distance(&mut StepRng::new(0, 0));
+        assert!(distance_zero.is_finite());
+    }
+
+    #[test]
+    fn time_warp() {
+        let sphere = Sphere::new(
+            vector![0., 0., -5.],
+            0.4,
+            Lambertian::solid_color(color![1., 1., 1.]),
+        )
+        .moving(vector![2., 0., -5.], 0., 1.);
+        let warped = TimeWarp::new(sphere.clone(), 0.5);
+
+        let direction = vector![0.1, 0., -1.];
+        let warped_hit = warped
+            .hit(
+                Ray::new(Vector3::zeros(), direction).with_time(0.5),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+        let hit = sphere
+            .hit(
+                Ray::new(Vector3::zeros(), direction).with_time(0.25),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+
+        assert!((warped_hit.t - hit.t).abs() < 1e-5);
+        assert!((warped_hit.point - hit.point).norm() < 1e-5);
+        assert!((warped_hit.normal - hit.normal).norm() < 1e-5);
+        assert!(sphere
+            .hit(
+                Ray::new(Vector3::zeros(), direction).with_time(0.5),
+                0.,
+                f32::INFINITY
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn prism_matches_cuboid_and_cylinder() {
+        let material = Lambertian::solid_color(color![1., 1., 1.]);
+        let center = vector![0., 0., -5.];
+        let square = Prism::new(center, 4, 0.5f32.sqrt(), 2., material.clone());
+        let cuboid = Cuboid::new(center, 1., 2., 1., material.clone());
+        let polygon = Prism::new(center, 128, 0.5, 2., material.clone());
+        let cylinder = Cylinder::new(center, 0.5, 2., material);
+
+        for _ in 0..1000 {
+            let origin = random_vector_in_range(-0.5, 0.5);
+            let ray = Ray::new(origin, center + random_vector_in_range(-1., 1.) - origin);
+
+            let square_hit = square.hit(ray, 0., f32::INFINITY);
+            let cuboid_hit = cuboid.hit(ray, 0., f32::INFINITY);
+            assert_eq!(square_hit.is_some(), cuboid_hit.is_some());
+            if let (Some(square_hit), Some(cuboid_hit)) = (square_hit, cuboid_hit) {
+                assert!((square_hit.t - cuboid_hit.t).abs() < 1e-4);
+                assert!((square_hit.normal - cuboid_hit.normal).norm() < 1e-4);
+            }
+
+            // Only compare the side, as `Cylinder` does not have proper normals on its caps.
+            if let (Some(polygon_hit), Some(cylinder_hit)) = (
+                polygon.hit(ray, 0., f32::INFINITY),
+                cylinder.hit(ray, 0., f32::INFINITY),
+            ) {
+                if polygon_hit.normal.y == 0. {
+                    assert!((polygon_hit.t - cylinder_hit.t).abs() < 1e-2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_direction_misses() {
+        let sphere = Sphere::new(
+            vector![0., 0., 0.],
+            1.,
+            Lambertian::solid_color(color![1., 1., 1.]),
+        );
+        let ray = Ray::new(vector![0., 0., 0.], Vector3::zeros());
+
+        assert!(sphere.hit_origin(ray, 0., f32::INFINITY).is_none());
+        assert!(sphere.hit(ray, 0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn profiled_hits() {
+        let material = Lambertian::solid_color(color![0.5, 0.5, 0.5]);
+        let visible = Profiled::new(Sphere::new(vector![0., 0., -3.], 1., material.clone()));
+        let off_screen = Profiled::new(Sphere::new(vector![0., 0., 10.], 1., material));
+
+        let mut raytracer = Raytracer::new(Camera::default(), WHITE, 8, 6, 4, 10);
+        raytracer.world.push(visible.clone());
+        raytracer.world.push(off_screen.clone());
+        raytracer.render();
+
+        // The off-screen sphere can only be hit by the few bounces that happen to go there.
+        assert!(visible.hits() > 0);
+        assert!(off_screen.hits() < visible.hits() / 10);
+    }
+
+    #[test]
+    fn shell_four_surfaces() {
+        let shell = Shell::new(
+            vector![0., 0., -5.],
+            1.,
+            0.1,
+            Lambertian::solid_color(color![1., 1., 1.]),
+        );
+        let ray = Ray::new(vector![0., 0., 0.], vector![0., 0., -1.]);
+
+        let mut hits = Vec::new();
+        let mut t_min = 0.;
+        while let Some(hit) = shell.hit(ray, t_min, f32::INFINITY) {
+            t_min = hit.t + 1e-4;
+            hits.push((hit.t, hit.front_face));
+        }
+
+        let expected = [(4., true), (4.1, false), (5.9, true), (6., false)];
+        assert_eq!(hits.len(), expected.len());
+        for ((t, front_face), (expected_t, expected_front_face)) in hits.into_iter().zip(expected) {
+            assert!((t - expected_t).abs() < 1e-4);
+            assert_eq!(front_face, expected_front_face);
+        }
+    }
+
+    #[test]
+    fn checkered_floor() {
+        let (color_a, color_b) = (color![1., 1., 1.], color![0., 0., 0.]);
+        assert!(CheckeredFloor::solid_colors(-1., 0., color_a, color_b).is_none());
+        assert!(CheckeredFloor::solid_colors(-1., -2., color_a, color_b).is_none());
+        let floor = CheckeredFloor::solid_colors(-1., 2., color_a, color_b).unwrap();
+        let albedo_at = |x: f32, z: f32| {
+            let ray = Ray::new(vector![x, 5., z], vector![0., -1., 0.]);
+            let hit = floor.hit(ray, 0., f32::INFINITY).unwrap();
+            assert!((hit.t - 6.).abs() < 1e-5);
+            assert!(hit.front_face);
+            hit.material().scatter(ray, hit.clone()).unwrap().1
+        };
+
+        assert_eq!(albedo_at(1., 1.), color_a);
+        assert_eq!(albedo_at(3., 1.), color_b);
+        assert_eq!(albedo_at(3., 3.), color_a);
+        assert_eq!(albedo_at(-1., 1.), color_b);
+        assert!(floor.bounding_box(0., 0.).is_none());
+        assert!(floor
+            .hit(
+                Ray::new(vector![0., 5., 0.], vector![0., 1., 0.]),
+                0.,
+                f32::INFINITY
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn triangle_hit() {
+        let triangle = Triangle::new(
+            vector![0., 0., -2.],
+            vector![1., 0., -2.],
+            vector![0., 1., -2.],
+            Lambertian::default(),
+        );
+
+        let hit = triangle
+            .hit(
+                Ray::new(vector![0.25, 0.25, 0.], vector![0., 0., -1.]),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.t - 2.).abs() < 1e-5);
+        assert!((hit.point - vector![0.25, 0.25, -2.]).norm() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5 && (hit.v - 0.25).abs() < 1e-5);
+        assert!(hit.front_face);
+
+        let miss = Ray::new(vector![0.75, 0.75, 0.], vector![0., 0., -1.]);
+        assert!(triangle.hit(miss, 0., f32::INFINITY).is_none());
+
+        let aabb = triangle.bounding_box(0., 0.).unwrap();
+        assert!((aabb.minimum.x - 0.).abs() < 1e-5 && (aabb.maximum.y - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn smooth_triangle() {
+        let up = vector![0., 0., 1.];
+        let tilted = vector![1., 0., 1.];
+        let triangle = SmoothTriangle::new(
+            [
+                vector![0., 0., -2.],
+                vector![1., 0., -2.],
+                vector![0., 1., -2.],
+            ],
+            [up, tilted, up],
+            Lambertian::default(),
+        );
+
+        // Halfway between `a` and `b`, the normal is halfway between theirs.
+        let ray = Ray::new(vector![0.5, 0., 0.], vector![0., 0., -1.]);
+        let hit = triangle.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 2.).abs() < 1e-5);
+        assert!((hit.normal - (up + tilted.normalize()).normalize()).norm() < 1e-5);
+
+        // From behind, the normal is flipped like for a flat triangle.
+        let ray = Ray::new(vector![0., 0.01, -4.], vector![0., 0., 1.]);
+        let hit = triangle.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert!((hit.normal + up).norm() < 1e-2);
+    }
+
+    #[test]
+    fn infinite_plane() {
+        let plane = InfinitePlane::new(
+            vector![0., -1., 0.],
+            vector![0., 2., 0.],
+            Lambertian::default(),
+        );
+
+        let parallel = Ray::new(vector![0., 0., 0.], vector![1., 0., 1.]);
+        assert!(plane.hit(parallel, 0., f32::INFINITY).is_none());
+
+        let down = |x: f32, z: f32| {
+            let ray = Ray::new(vector![x, 1., z], vector![0., -1., 0.]);
+            plane.hit(ray, 0., f32::INFINITY).unwrap()
+        };
+        let hit = down(0., 0.);
+        assert!((hit.t - 2.).abs() < 1e-5);
+        assert!((hit.normal - Vector3::y()).norm() < 1e-5);
+        assert!(hit.front_face);
+        assert!(hit.u.abs() < 1e-5 && hit.v.abs() < 1e-5);
+
+        // The UV coordinates measure distances along the plane.
+        let hit = down(3., 4.);
+        assert!((hit.u.hypot(hit.v) - 5.).abs() < 1e-4);
+
+        assert!(plane.bounding_box(0., 0.).is_none());
+    }
+
+    #[test]
+    fn tangents() {
+        let ray = Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]);
+
+        let sphere = Sphere::new(vector![0., 0., 0.], 1., Lambertian::default());
+        let hit = sphere.hit(ray, 0., f32::INFINITY).unwrap();
+        let tangent = hit.tangent.unwrap();
+        assert!((tangent - Vector3::x()).norm() < 1e-6);
+        // The tangent points towards increasing `u`.
+        let nearby = sphere
+            .hit(
+                Ray::new(vector![0.01, 0., 5.], vector![0., 0., -1.]),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!(nearby.u > hit.u);
+
+        let rectangle = Rectangle::yz(vector![0., 0., 0.], 1., 1., Lambertian::default())
+            .with_rotation(Rotation3::new(FRAC_PI_2 * Vector3::y()));
+        let hit = rectangle.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!(hit.tangent.unwrap().dot(&hit.normal).abs() < 1e-6);
+        assert!((hit.tangent.unwrap().norm() - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cuboid_center_axis_hits_top() {
+        // Same construction as the boxes of the Cornell box in `book2.rs`.
+        let center = vector![30., -75., -50.];
+        let cuboid = Cuboid::new(center, 100., 150., 100., Lambertian::default())
+            .with_rotation(Rotation3::new((15f32).to_radians() * Vector3::y()));
+
+        let ray = Ray::new(center + vector![0., 500., 0.], -Vector3::y());
+        let hit = cuboid.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - (500. - 75.)).abs() < 1e-3);
+        assert!((hit.point - (center + vector![0., 75., 0.])).norm() < 1e-3);
+        assert!((hit.normal - Vector3::y()).norm() < 1e-5);
+    }
+
+    #[test]
+    fn cylinder_uv() {
+        let cylinder = Cylinder::new(vector![0., 0., 0.], 1., 2., Lambertian::default());
+
+        let hit = cylinder
+            .hit(
+                Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.v - 0.5).abs() < 1e-5);
+        assert!((hit.u - 0.25).abs() < 1e-5);
+
+        let hit = cylinder
+            .hit(
+                Ray::new(vector![0., 0.5, 5.], vector![0., 0., -1.]),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.v - 0.75).abs() < 1e-5);
+
+        let hit = cylinder
+            .hit(
+                Ray::new(vector![0.46, 5., -0.5], vector![0.01, -1., 0.]),
+                0.,
+                f32::INFINITY,
+            )
+            .unwrap();
+        assert!((hit.u - 0.75).abs() < 1e-5);
+        assert!((hit.v - 0.25).abs() < 1e-5);
+    }
+
+    #[test]
+    fn open_cylinder() {
+        let open = Cylinder::open(vector![0., 0., 0.], 1., 2., Lambertian::default());
+        let capped = Cylinder::new(vector![0., 0., 0.], 1., 2., Lambertian::default());
+
+        // Through the axis, crossing both ends but not the side.
+        let ray = Ray::new(vector![-0.1, 5., 0.], vector![0.02, -1., 0.]);
+        assert!(open.hit(ray, 0., f32::INFINITY).is_none());
+        assert!(capped.hit(ray, 0., f32::INFINITY).is_some());
+
+        // Entering through the open top, the inside of the side is hit.
+        let ray = Ray::new(vector![0., 2., 0.], vector![1., -1.5, 0.]);
+        let hit = open.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-5);
+        assert!(!hit.front_face);
+
+        // The side is the same as for the capped cylinder.
+        let ray = Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]);
+        let open_hit = open.hit(ray, 0., f32::INFINITY).unwrap();
+        let capped_hit = capped.hit(ray, 0., f32::INFINITY).unwrap();
+        assert_eq!(open_hit.t, capped_hit.t);
+    }
+
+    #[test]
+    fn disk_and_annulus() {
+        let center = vector![1., 2., 3.];
+        let normal = vector![0., 1., 1.];
+        let disk = Disk::new(center, normal, 2., Lambertian::default());
+        let annulus = Disk::annulus(center, normal, 1., 2., Lambertian::default());
+
+        // Through the hole of the annulus.
+        let ray = Ray::new(center + normal, -normal);
+        assert!(annulus.hit(ray, 0., f32::INFINITY).is_none());
+        let hit = disk.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 1.).abs() < 1e-5);
+        assert!(hit.u.abs() < 1e-5);
+
+        // Through the ring.
+        let offset = vector![1.5, 0., 0.];
+        let ray = Ray::new(center + offset + normal, -normal);
+        let hit = annulus.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.u - 0.75).abs() < 1e-5);
+        assert!((hit.normal - normal.normalize()).norm() < 1e-5);
+
+        // Outside.
+        let ray = Ray::new(center + 3. * offset + normal, -normal);
+        assert!(disk.hit(ray, 0., f32::INFINITY).is_none());
+
+        let aabb = disk.bounding_box(0., 0.).unwrap();
+        let half = 2f32.sqrt();
+        for (axis, extent) in [2., half, half].into_iter().enumerate() {
+            assert!((aabb.maximum()[axis] - center[axis] - extent).abs() < 1e-3);
+            assert!((center[axis] - aabb.minimum()[axis] - extent).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn cone() {
+        // The height is twice the radius, so the side rises by 2 per unit of radius and its normal facing +z is (0, 1, 2) normalized.
+        let cone = Cone::new(vector![0., 0., 0.], 1., 2., Lambertian::default());
+
+        // At half height, the radius is one half.
+        let ray = Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]);
+        let hit = cone.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-5);
+        assert!(hit.front_face);
+        assert!(hit.normal.y > 0.);
+        assert!((hit.normal - vector![0., 1., 2.].normalize()).norm() < 1e-5);
+        assert!((hit.v - 0.5).abs() < 1e-5);
+
+        // From above, the apex is hit.
+        let ray = Ray::new(vector![0., 5., 0.], vector![0., -1., 0.]);
+        let hit = cone.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-5);
+
+        // Neither the mirrored cone above the apex nor the open base are hit.
+        let ray = Ray::new(vector![0., 2., 5.], vector![0., 0., -1.]);
+        assert!(cone.hit(ray, 0., f32::INFINITY).is_none());
+        let ray = Ray::new(vector![0.1, -5., 0.], vector![0., 1., 0.]);
+        let hit = cone.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn torus() {
+        let torus = Torus::new(vector![0., 0., 0.], 2., 0.5, Lambertian::default());
+
+        // Straight down through the central hole.
+        let ray = Ray::new(vector![0., 5., 0.], vector![0., -1., 0.]);
+        assert!(torus.hit(ray, 0., f32::INFINITY).is_none());
+
+        // Straight down onto the top of the tube.
+        let ray = Ray::new(vector![2., 5., 0.], vector![0., -1., 0.]);
+        let hit = torus.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-3);
+        assert!((hit.normal - Vector3::y()).norm() < 1e-3);
+
+        // Horizontally through the hole, hitting the inside of the tube on the far side.
+        let ray = Ray::new(vector![0., 0., 0.], vector![1., 0., 0.]);
+        let hit = torus.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-3);
+        assert!((hit.normal + Vector3::x()).norm() < 1e-3);
+
+        // Grazing the top of the tube, just below and just above.
+        let ray = Ray::new(vector![-5., 0.499, 2.], vector![1., 0., 0.]);
+        let hit = torus.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.point.x + 0.359).abs() < 0.05);
+        assert!(hit.normal.y > 0.9);
+        let ray = Ray::new(vector![-5., 0.501, 2.], vector![1., 0., 0.]);
+        assert!(torus.hit(ray, 0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn quad() {
+        let corner = vector![1., 0., 0.];
+        let u_edge = vector![0., 2., 0.];
+        let v_edge = vector![-1., 0., 1.];
+        let quad = Quad::new(corner, u_edge, v_edge, Lambertian::default());
+        let normal = u_edge.cross(&v_edge).normalize();
+
+        let center = corner + (u_edge + v_edge) / 2.;
+        let ray = Ray::new(center + 3. * normal, -normal);
+        let hit = quad.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 3.).abs() < 1e-5);
+        assert!((hit.u - 0.5).abs() < 1e-5);
+        assert!((hit.v - 0.5).abs() < 1e-5);
+        assert!((hit.normal - normal).norm() < 1e-5);
+
+        let ray = Ray::new(corner + 0.25 * u_edge + 0.75 * v_edge + normal, -normal);
+        let hit = quad.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.u - 0.25).abs() < 1e-5);
+        assert!((hit.v - 0.75).abs() < 1e-5);
+
+        let ray = Ray::new(corner + 1.1 * u_edge + normal, -normal);
+        assert!(quad.hit(ray, 0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn difference() {
+        let material = Lambertian::default();
+        let big = Sphere::new(vector![0., 0., 0.], 2., material.clone());
+        let small = Sphere::new(vector![0., 0., 2.], 1., material);
+        let carved = Difference::new(big, small);
+
+        // Along the axis, the ray passes the removed cap and enters the cavity at the back of the small sphere.
+        let ray = Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]);
+        let hit = carved.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-4);
+        assert!(hit.front_face);
+        assert!((hit.normal - Vector3::z()).norm() < 1e-5);
+
+        // Next to the small sphere, the big one is hit as usual.
+        let ray = Ray::new(vector![0., 1.5, 5.], vector![0., 0., -1.]);
+        let hit = carved.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.point.norm() - 2.).abs() < 1e-4);
+
+        // Only touching the removed part misses.
+        let ray = Ray::new(vector![-5., 0., 2.9], vector![1., 0., 0.]);
+        assert!(carved.hit(ray, 0., f32::INFINITY).is_none());
+        assert!(carved
+            .hit(Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]), 0., 3.5)
+            .is_none());
+    }
+
+    #[test]
+    fn union_and_intersection() {
+        let material = Lambertian::default();
+        let left = Sphere::new(vector![-0.5, 0., 0.], 1., material.clone());
+        let right = Sphere::new(vector![0.5, 0., 0.], 1., material);
+        let union = Union::new(left.clone(), right.clone());
+        let intersection = Intersection::new(left, right);
+
+        // Along the x axis, the union spans [-1.5, 1.5] and the intersection [-0.5, 0.5].
+        let ray = Ray::new(vector![-5., 0., 0.], vector![1., 0., 0.]);
+        let hit = union.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 3.5).abs() < 1e-4);
+        let hit = intersection.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-4);
+        assert!(hit.front_face);
+        // From inside the overlap, the inner surfaces of the union are skipped.
+        let ray = Ray::new(vector![0., 0., 0.], vector![1., 0., 0.]);
+        let hit = union.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-4);
+        let hit = intersection.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-4);
+        assert!(!hit.front_face);
+
+        // Outside of the overlap, only the union is hit.
+        let ray = Ray::new(vector![-1., 5., 0.], vector![0., -1., 0.]);
+        assert!(union.hit(ray, 0., f32::INFINITY).is_some());
+        assert!(intersection.hit(ray, 0., f32::INFINITY).is_none());
+
+        // Inside of the overlap, both are, but on different spheres.
+        let ray = Ray::new(vector![0.1, 5., 0.], vector![0., -1., 0.]);
+        let hit = union.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - (5. - 0.84f32.sqrt())).abs() < 1e-4);
+        let hit = intersection.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - (5. - 0.64f32.sqrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rounded_box() {
+        let material = Lambertian::default();
+        let rounded = RoundedBox::new(vector![0., 0., 0.], 2., 2., 2., 0.5, material.clone());
+        let cuboid = Cuboid::new(vector![0., 0., 0.], 2., 2., 2., material);
+
+        // The faces are flat like a cuboid's.
+        let ray = Ray::new(vector![0.2, 0.3, 5.], vector![0., 0., -1.]);
+        let hit = rounded.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 4.).abs() < 1e-3);
+        assert!((hit.normal - Vector3::z()).norm() < 1e-3);
+
+        // Diagonally towards the corner, the cuboid is hit at its corner, the rounded box further inside.
+        let direction = -vector![1., 1., 1.].normalize();
+        let ray = Ray::new(vector![5., 5., 5.], direction);
+        let corner_t = 4. * 3f32.sqrt();
+        let rounded_t = 4.5 * 3f32.sqrt() - 0.5;
+        assert!((cuboid.hit(ray, 0., f32::INFINITY).unwrap().t - corner_t).abs() < 1e-3);
+        let hit = rounded.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - rounded_t).abs() < 1e-3);
+        assert!((hit.normal + direction).norm() < 1e-3);
+
+        // A ray grazing the corner of the cuboid misses the rounded box.
+        let ray = Ray::new(vector![0.95, 0.95, 5.], vector![0., 0., -1.]);
+        assert!(cuboid.hit(ray, 0., f32::INFINITY).is_some());
+        assert!(rounded.hit(ray, 0., f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn instance() {
+        let sphere: Arc<dyn Hittable> =
+            Arc::new(Sphere::new(vector![0., 1., 0.], 1., Lambertian::default()));
+        let mut world = HittableList::default();
+        for x in [-5., 0., 5.] {
+            world.push(Instance::new(sphere.clone(), vector![x, 0., 0.]));
+        }
+        assert_eq!(Arc::strong_count(&sphere), 4);
+
+        // Each instance is the shared sphere, offset on top of the sphere's own center.
+        for x in [-5., 0., 5.] {
+            let ray = Ray::new(vector![x, 1., 5.], vector![0., 0., -1.]);
+            let hit = world.hit(ray, 0., f32::INFINITY).unwrap();
+            assert!((hit.t - 4.).abs() < 1e-5);
+            assert!((hit.point - vector![x, 1., 1.]).norm() < 1e-5);
+        }
+        let ray = Ray::new(vector![2.5, 1., 5.], vector![0., 0., -1.]);
+        assert!(world.hit(ray, 0., f32::INFINITY).is_none());
+
+        let aabb = Instance::new(sphere, vector![5., 0., 0.])
+            .bounding_box(0., 0.)
+            .unwrap();
+        assert!((aabb.minimum() - vector![4., 0., -1.]).norm() < 1e-5);
+        assert!((aabb.maximum() - vector![6., 2., 1.]).norm() < 1e-5);
+
+        // Instances can be rotated independently.
+        let cuboid: Arc<dyn Hittable> = Arc::new(Cuboid::new(
+            vector![0., 0., 0.],
+            4.,
+            1.,
+            1.,
+            Lambertian::default(),
+        ));
+        let rotated = Instance::new(cuboid, vector![0., 0., 0.])
+            .with_rotation(Rotation3::new(FRAC_PI_2 * Vector3::y()));
+        let ray = Ray::new(vector![0., 0., 5.], vector![0., 0., -1.]);
+        let hit = rotated.hit(ray, 0., f32::INFINITY).unwrap();
+        assert!((hit.t - 3.).abs() < 1e-4);
     }
 }
@@ -0,0 +1,192 @@
+//! Loading of triangle meshes.
+
+use std::path::Path;
+use std::{fmt, fs, io};
+
+use super::Triangle;
+use crate::materials::Material;
+use crate::*;
+
+/// Error when loading a mesh.
+#[derive(Debug)]
+pub enum MeshError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// A line could not be parsed.
+    Parse { line: usize, message: String },
+    /// A face references a vertex that does not exist (the index is 1-based like in the file).
+    VertexOutOfRange {
+        line: usize,
+        index: i64,
+        vertices: usize,
+    },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Io(error) => write!(f, "could not read mesh: {error}"),
+            MeshError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            MeshError::VertexOutOfRange {
+                line,
+                index,
+                vertices,
+            } => write!(
+                f,
+                "line {line}: vertex index {index} is out of range for {vertices} vertices"
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for MeshError {
+    fn from(error: io::Error) -> Self {
+        MeshError::Io(error)
+    }
+}
+
+/// Load a Wavefront `.obj` file into a [`HittableList`] of [`Triangle`]s centered at the origin.
+///
+/// Only vertices (`v`) and faces (`f`) are read, everything else is ignored. Faces with more than three vertices are fan-triangulated.
+/// Vertex indices are 1-based, negative indices count back from the last vertex defined so far.
+/// As all [`Triangle`]s have a bounding box, the result can be passed to [`Bvh::new`](crate::hittable::Bvh::new).
+pub fn load_obj<M: Material + Clone + 'static, P: AsRef<Path>>(
+    path: P,
+    material: M,
+) -> Result<HittableList, MeshError> {
+    let content = fs::read_to_string(path)?;
+    parse_obj(&content, material)
+}
+
+fn parse_obj<M: Material + Clone + 'static>(
+    content: &str,
+    material: M,
+) -> Result<HittableList, MeshError> {
+    let mut vertices: Vec<Vector3<f32>> = Vec::new();
+    let mut triangles = HittableList::new(vector![0., 0., 0.]);
+
+    for (line, text) in content.lines().enumerate() {
+        let line = line + 1;
+        let parse_error = |message: &str| MeshError::Parse {
+            line,
+            message: message.to_string(),
+        };
+
+        let mut tokens = text.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let coordinates = tokens
+                    .take(3)
+                    .map(|token| token.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| parse_error("invalid vertex coordinate"))?;
+                if coordinates.len() != 3 {
+                    return Err(parse_error("expected three vertex coordinates"));
+                }
+                vertices.push(vector![coordinates[0], coordinates[1], coordinates[2]]);
+            }
+            Some("f") => {
+                let face = tokens
+                    .map(|token| {
+                        // Only the vertex index of `v/vt/vn` is used.
+                        let index: i64 = token
+                            .split('/')
+                            .next()
+                            .and_then(|index| index.parse().ok())
+                            .ok_or_else(|| parse_error("invalid face index"))?;
+                        let resolved = match index {
+                            1.. => index - 1,
+                            ..=-1 => vertices.len() as i64 + index,
+                            0 => -1,
+                        };
+                        usize::try_from(resolved)
+                            .ok()
+                            .and_then(|resolved| vertices.get(resolved))
+                            .copied()
+                            .ok_or(MeshError::VertexOutOfRange {
+                                line,
+                                index,
+                                vertices: vertices.len(),
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if face.len() < 3 {
+                    return Err(parse_error("expected at least three vertices per face"));
+                }
+
+                for window in 1..face.len() - 1 {
+                    triangles.push(Triangle::new(
+                        face[0],
+                        face[window],
+                        face[window + 1],
+                        material.clone(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Bvh;
+    use crate::materials::Lambertian;
+    use crate::ray::Ray;
+
+    const QUAD: &str = "# a unit square
+o square
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vn 0 0 1
+f 1//1 2//1 3//1 4//1
+";
+
+    #[test]
+    fn fan_triangulation() {
+        let mesh = parse_obj(QUAD, Lambertian::default()).unwrap();
+        assert_eq!(mesh.len(), 2);
+
+        let bvh = Bvh::new(mesh, 0., 0.).unwrap();
+        for (x, y) in [(0.9, 0.1), (0.1, 0.9)] {
+            let ray = Ray::new(vector![x, y, 1.], vector![0., 0., -1.]);
+            let hit = bvh.hit(ray, 0., f32::INFINITY).unwrap();
+            assert!((hit.t - 1.).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn negative_indices() {
+        let content = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n";
+        assert_eq!(parse_obj(content, Lambertian::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn vertex_out_of_range() {
+        for (index, content) in [
+            (4, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 2 3 4\n"),
+            (0, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n"),
+        ] {
+            let error = parse_obj(content, Lambertian::default()).unwrap_err();
+            assert!(matches!(
+                error,
+                MeshError::VertexOutOfRange {
+                    line: 4,
+                    index: i,
+                    vertices: 3,
+                } if i == index
+            ));
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        let error = load_obj("does/not/exist.obj", Lambertian::default()).unwrap_err();
+        assert!(matches!(error, MeshError::Io(_)));
+    }
+}
@@ -1,10 +1,17 @@
 //! A way to apply textures to shapes.
 
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::path::Path;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 
+use image::codecs::hdr::HdrDecoder;
+use image::error::{ParameterError, ParameterErrorKind};
 use image::io::Reader as ImageReader;
-use image::{ImageError, RgbImage};
+use image::{ImageError, Rgb32FImage, RgbImage};
+use nalgebra::Rotation3;
 
 use crate::color::WHITE;
 use crate::perlin::Perlin;
@@ -41,10 +48,23 @@ impl Texture for SolidColor {
 }
 
 /// A checkerboard texture.
+///
+/// By default, the checks are three-dimensional and evaluated at the `hit_point` in world space.
+/// Created with [`uv`](CheckerTexture::uv), they are two-dimensional and follow the (u, v) parametrization of the surface instead.
+///
+/// # Fields
+/// - `texture_even`: [`Texture`] of the even checks.
+/// - `texture_odd`: [`Texture`] of the odd checks.
+/// - `rotation`: Optional rotation of the checks (only in world space).
+/// - `scale`: Spatial frequency of the checks (in world space, a check is `π / scale` wide; in (u, v) space, there are `scale` checks per unit).
+/// - `uv_space`: Whether the checks are evaluated in (u, v) space.
 #[derive(Clone, Debug)]
 pub struct CheckerTexture<S: Texture, T: Texture> {
     texture_even: S,
     texture_odd: T,
+    rotation: Option<Rotation3<f32>>,
+    scale: f32,
+    uv_space: bool,
 }
 
 impl<S: Texture, T: Texture> CheckerTexture<S, T> {
@@ -52,25 +72,63 @@ impl<S: Texture, T: Texture> CheckerTexture<S, T> {
         Self {
             texture_even,
             texture_odd,
+            rotation: None,
+            scale: 10.,
+            uv_space: false,
+        }
+    }
+
+    /// Create a checkerboard with `scale` checks per unit of `u` and `v`, so that it follows the surface regardless of its position and size.
+    pub fn uv(texture_even: S, texture_odd: T, scale: f32) -> Self {
+        Self {
+            texture_even,
+            texture_odd,
+            rotation: None,
+            scale,
+            uv_space: true,
         }
     }
+
+    /// Consume `self` and rotate the checks by `rotation` around the origin.
+    pub fn with_rotation(mut self, rotation: Rotation3<f32>) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Consume `self` and change the spatial frequency of the checks (default: 10).
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
 }
 
 impl CheckerTexture<SolidColor, SolidColor> {
     pub fn solid_colors(color_even: Color, color_odd: Color) -> Self {
         let texture_even = SolidColor::new(color_even);
         let texture_odd = SolidColor::new(color_odd);
-        Self {
-            texture_even,
-            texture_odd,
-        }
+        Self::new(texture_even, texture_odd)
     }
 }
 
 impl<S: Texture, T: Texture> Texture for CheckerTexture<S, T> {
     fn color_at(&self, u: f32, v: f32, hit_point: Vector3<f32>) -> Color {
-        let sin_product =
-            (10. * hit_point.x).sin() * (10. * hit_point.y).sin() * (10. * hit_point.z).sin();
+        if self.uv_space {
+            let parity = ((u * self.scale).floor() + (v * self.scale).floor()).rem_euclid(2.);
+            return if parity == 0. {
+                self.texture_even.color_at(u, v, hit_point)
+            } else {
+                self.texture_odd.color_at(u, v, hit_point)
+            };
+        }
+
+        // Rotating the pattern is the same as rotating the point inversely.
+        let point = match self.rotation {
+            Some(rotation) => rotation.inverse() * hit_point,
+            None => hit_point,
+        };
+        let sin_product = (self.scale * point.x).sin()
+            * (self.scale * point.y).sin()
+            * (self.scale * point.z).sin();
         if sin_product < 0. {
             self.texture_odd.color_at(u, v, hit_point)
         } else {
@@ -79,6 +137,50 @@ impl<S: Texture, T: Texture> Texture for CheckerTexture<S, T> {
     }
 }
 
+/// A debug texture drawing a grid in (u, v) space over a base color.
+///
+/// Rendering a shape with it makes the stretching of its parametrization obvious.
+///
+/// # Fields
+/// - `lines_per_unit`: Number of grid lines per unit of `u` and `v`.
+/// - `line_color`: Color of the grid lines.
+/// - `base`: Color between the grid lines.
+#[derive(Clone, Debug)]
+pub struct UvGridTexture {
+    lines_per_unit: f32,
+    line_color: Color,
+    base: Color,
+}
+
+/// Width of the lines of a [`UvGridTexture`] as a fraction of the spacing between them.
+const UV_GRID_LINE_WIDTH: f32 = 0.05;
+
+impl UvGridTexture {
+    pub fn new(lines_per_unit: f32, line_color: Color, base: Color) -> Self {
+        Self {
+            lines_per_unit,
+            line_color,
+            base,
+        }
+    }
+}
+
+impl Texture for UvGridTexture {
+    fn color_at(&self, u: f32, v: f32, _hit_point: Vector3<f32>) -> Color {
+        let on_line = |coordinate: f32| {
+            let cell = coordinate * self.lines_per_unit;
+            let distance = (cell - cell.round()).abs();
+            distance < UV_GRID_LINE_WIDTH / 2.
+        };
+
+        if on_line(u) || on_line(v) {
+            self.line_color
+        } else {
+            self.base
+        }
+    }
+}
+
 /// A grayscale Perlin noise texture.
 ///
 /// # Fields
@@ -105,34 +207,442 @@ impl Texture for PerlinNoiseTexture {
     }
 }
 
+/// A marble texture with veins distorted by Perlin turbulence.
+///
+/// # Fields
+/// - `noise`: Source of the turbulence.
+/// - `scale`: Spatial frequency of the veins along the z axis.
+/// - `phase`: Strength of the distortion by the turbulence.
+/// - `depth`: Number of octaves of the turbulence.
+#[derive(Clone, Debug)]
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f32,
+    phase: f32,
+    depth: u8,
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f32, phase: f32, depth: u8) -> Self {
+        Self {
+            noise: Perlin::new(),
+            scale,
+            phase,
+            depth,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn color_at(&self, _u: f32, _v: f32, hit_point: Vector3<f32>) -> Color {
+        let turbulence = self.noise.turbulance(hit_point, self.depth);
+        WHITE * 0.5 * (1. + (self.scale * hit_point.z + self.phase * turbulence).sin())
+    }
+}
+
+/// Images decoded by [`ImageTexture::open_cached`], keyed by their canonicalized path.
+static IMAGE_CACHE: OnceLock<Mutex<HashMap<PathBuf, Arc<RgbImage>>>> = OnceLock::new();
+
+/// How an [`ImageTexture`] is sampled between the centers of its texels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// Use the texel containing the point, which looks blocky when magnified.
+    #[default]
+    Nearest,
+    /// Interpolate linearly between the four closest texels. Neighbors beyond the edges are found with the [`Wrap`] mode of the texture.
+    Bilinear,
+}
+
+/// How an [`ImageTexture`] handles coordinates outside of \[0,1\].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Wrap {
+    /// Extend the texels at the edges.
+    #[default]
+    Clamp,
+    /// Tile the image.
+    Repeat,
+    /// Tile the image, mirroring every other tile so that the edges match.
+    Mirror,
+}
+
+impl Wrap {
+    /// Map a coordinate into \[0,1\].
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Wrap::Clamp => t.clamp(0., 1.),
+            Wrap::Repeat => t.rem_euclid(1.),
+            Wrap::Mirror => {
+                let t = t.rem_euclid(2.);
+                if t > 1. {
+                    2. - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+
+    /// Map the index of a texel, possibly one beyond the edges, into `0..size`.
+    fn texel(self, i: i64, size: u32) -> u32 {
+        let size = i64::from(size);
+        let i = match self {
+            Wrap::Clamp => i.clamp(0, size - 1),
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i >= size {
+                    2 * size - 1 - i
+                } else {
+                    i
+                }
+            }
+        };
+        i as u32
+    }
+}
+
+/// Pixel data of an [`ImageTexture`], shared between clones.
+#[derive(Clone, Debug)]
+enum Pixels {
+    /// 8 bits per channel, e.g. from PNG or JPEG.
+    Ldr(Arc<RgbImage>),
+    /// Floating point channels, e.g. from Radiance `.hdr` files. Values may exceed 1.
+    Hdr(Arc<Rgb32FImage>),
+}
+
+impl Pixels {
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Pixels::Ldr(image) => image.dimensions(),
+            Pixels::Hdr(image) => image.dimensions(),
+        }
+    }
+
+    fn texel(&self, i: u32, j: u32) -> Color {
+        match self {
+            Pixels::Ldr(image) => (*image.get_pixel(i, j)).into(),
+            Pixels::Hdr(image) => (*image.get_pixel(i, j)).into(),
+        }
+    }
+}
+
 /// A image texture.
+///
+/// The pixel data is shared between clones.
+///
+/// # Fields
+/// - `image`: The pixel data, either 8-bit or floating point (see [`open_hdr`](ImageTexture::open_hdr)).
+/// - `filter`: How the image is sampled (see [`Filter`]).
+/// - `wrap_u`, `wrap_v`: How coordinates outside of the image are handled along each axis (see [`Wrap`]).
 #[derive(Clone, Debug)]
 pub struct ImageTexture {
-    image: RgbImage,
+    image: Pixels,
+    filter: Filter,
+    wrap_u: Wrap,
+    wrap_v: Wrap,
 }
 
 impl ImageTexture {
     pub fn new(image: RgbImage) -> Self {
-        Self { image }
+        Self::from_pixels(Pixels::Ldr(Arc::new(image)))
+    }
+
+    /// Create a texture from floating point pixels, whose colors are not limited to \[0,1\].
+    pub fn new_hdr(image: Rgb32FImage) -> Self {
+        Self::from_pixels(Pixels::Hdr(Arc::new(image)))
+    }
+
+    fn from_pixels(image: Pixels) -> Self {
+        Self {
+            image,
+            filter: Filter::default(),
+            wrap_u: Wrap::default(),
+            wrap_v: Wrap::default(),
+        }
+    }
+
+    /// Consume `self` and sample the image with `filter`.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Consume `self` and handle coordinates outside of \[0,1\] with `wrap`, e.g. to tile the image.
+    pub fn with_wrap(self, wrap: Wrap) -> Self {
+        self.with_wrap_uv(wrap, wrap)
+    }
+
+    /// Consume `self` and handle coordinates outside of \[0,1\] with `wrap_u` horizontally and `wrap_v` vertically.
+    pub fn with_wrap_uv(mut self, wrap_u: Wrap, wrap_v: Wrap) -> Self {
+        self.wrap_u = wrap_u;
+        self.wrap_v = wrap_v;
+        self
     }
 
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
         let image: RgbImage = ImageReader::open(path)?.decode()?.into_rgb8();
-        Ok(Self { image })
+        Ok(Self::new(image))
+    }
+
+    /// Open a high dynamic range image (e.g. a Radiance `.hdr` file) keeping the full floating point colors.
+    ///
+    /// Unlike [`open`](ImageTexture::open), colors are not quantized to 8 bits and may exceed 1, so this is suited for bright environment maps and emissive textures.
+    pub fn open_hdr<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        // The generic decoder would convert Radiance files to 8 bits.
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?;
+        let image = Rgb32FImage::from_raw(
+            metadata.width,
+            metadata.height,
+            pixels.into_iter().flat_map(|pixel| pixel.0).collect(),
+        )
+        .ok_or_else(|| {
+            ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            ))
+        })?;
+        Ok(Self::new_hdr(image))
+    }
+
+    /// Like [`open`](ImageTexture::open), but only decode each file once per process.
+    ///
+    /// All [`ImageTexture`]s opened from the same file share its pixel data.
+    pub fn open_cached<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        let path = path.as_ref().canonicalize()?;
+        let cache = IMAGE_CACHE.get_or_init(Default::default);
+
+        if let Some(image) = cache.lock().unwrap().get(&path) {
+            return Ok(Self::from_pixels(Pixels::Ldr(image.clone())));
+        }
+
+        // Decode without holding the lock, so that different files can be decoded in parallel.
+        let image: RgbImage = ImageReader::open(&path)?.decode()?.into_rgb8();
+        let image = cache
+            .lock()
+            .unwrap()
+            .entry(path)
+            .or_insert(Arc::new(image))
+            .clone();
+        Ok(Self::from_pixels(Pixels::Ldr(image)))
+    }
+
+    /// Bake any [`Texture`] into an [`ImageTexture`] of size `width`×`height` for faster lookups.
+    ///
+    /// The texture is sampled once at the center of each texel. As only (u, v) are known, the `hit_point` passed to the texture is `(u, v, 0)`.
+    /// Textures depending on the position in space (e.g. [`CheckerTexture`] or [`PerlinNoiseTexture`]) therefore lose their 3D variation and only keep a 2D slice of it.
+    pub fn bake<T: Texture>(texture: &T, width: u32, height: u32) -> Self {
+        let image = RgbImage::from_fn(width, height, |i, j| {
+            let u = (i as f32 + 0.5) / width as f32;
+            let v = 1. - (j as f32 + 0.5) / height as f32;
+            texture.color_at(u, v, vector![u, v, 0.]).into()
+        });
+        Self::new(image)
+    }
+}
+
+impl ImageTexture {
+    fn nearest(&self, u: f32, v: f32) -> Color {
+        let (width, height) = self.image.dimensions();
+        let mut i = (u.clamp(0., 1.) * width as f32) as u32;
+        let mut j = ((1. - v.clamp(0., 1.)) * height as f32) as u32;
+        if i >= width {
+            i = width - 1;
+        }
+        if j >= height {
+            j = height - 1;
+        }
+
+        self.image.texel(i, j)
+    }
+
+    fn bilinear(&self, u: f32, v: f32) -> Color {
+        let (width, height) = self.image.dimensions();
+        // Texel centers lie at half-integer positions.
+        let x = u.clamp(0., 1.) * width as f32 - 0.5;
+        let y = (1. - v.clamp(0., 1.)) * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let texel = |i: f32, j: f32| -> Color {
+            let i = self.wrap_u.texel(i as i64, width);
+            let j = self.wrap_v.texel(j as i64, height);
+            self.image.texel(i, j)
+        };
+        let top = texel(x0, y0) * (1. - fx) + texel(x0 + 1., y0) * fx;
+        let bottom = texel(x0, y0 + 1.) * (1. - fx) + texel(x0 + 1., y0 + 1.) * fx;
+        top * (1. - fy) + bottom * fy
     }
 }
 
 impl Texture for ImageTexture {
     fn color_at(&self, u: f32, v: f32, _hit_point: Vector3<f32>) -> Color {
-        let mut i = (u.clamp(0., 1.) * self.image.width() as f32) as u32;
-        let mut j = ((1. - v.clamp(0., 1.)) * self.image.height() as f32) as u32;
-        if i >= self.image.width() {
-            i = self.image.width() - 1;
+        let (u, v) = (self.wrap_u.apply(u), self.wrap_v.apply(v));
+        match self.filter {
+            Filter::Nearest => self.nearest(u, v),
+            Filter::Bilinear => self.bilinear(u, v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+    use crate::color::BLACK;
+
+    #[test]
+    fn bake_solid_color() {
+        let color = color![0.2, 0.4, 0.6];
+        let baked = ImageTexture::bake(&SolidColor::new(color), 4, 3);
+
+        let Pixels::Ldr(image) = &baked.image else {
+            panic!("baked textures are 8-bit");
+        };
+        assert_eq!(image.dimensions(), (4, 3));
+        let first = *image.get_pixel(0, 0);
+        assert!(image.pixels().all(|pixel| *pixel == first));
+        assert!((baked.color_at(0.5, 0.5, Vector3::zeros()) - color)
+            .into_iter()
+            .all(|c| c.abs() < 1. / 255.));
+    }
+
+    #[test]
+    fn open_cached_shares_image() {
+        let path = std::env::temp_dir().join("ray-tracing-open-cached.png");
+        RgbImage::from_pixel(2, 2, image::Rgb([10, 20, 30]))
+            .save(&path)
+            .unwrap();
+
+        let first = ImageTexture::open_cached(&path).unwrap();
+        let second = ImageTexture::open_cached(&path).unwrap();
+
+        let ldr = |texture: ImageTexture| match texture.image {
+            Pixels::Ldr(image) => image,
+            Pixels::Hdr(_) => panic!("PNGs are 8-bit"),
+        };
+        let first = ldr(first);
+        assert!(Arc::ptr_eq(&first, &ldr(second)));
+        assert!(!Arc::ptr_eq(
+            &first,
+            &ldr(ImageTexture::open(&path).unwrap())
+        ));
+    }
+
+    #[test]
+    fn open_hdr() {
+        let path = std::env::temp_dir().join("ray-tracing-open-hdr.hdr");
+        let pixels = [Rgb([4., 0.5, 0.25]), Rgb([0.1, 0.2, 0.3])];
+        let file = std::fs::File::create(&path).unwrap();
+        image::codecs::hdr::HdrEncoder::new(file)
+            .encode(&pixels, 2, 1)
+            .unwrap();
+
+        let texture = ImageTexture::open_hdr(&path).unwrap();
+        let bright = texture.color_at(0.25, 0.5, Vector3::zeros());
+        assert!((bright - color![4., 0.5, 0.25])
+            .into_iter()
+            .all(|c| c.abs() < 0.05));
+        let dark = texture.color_at(0.75, 0.5, Vector3::zeros());
+        assert!((dark - color![0.1, 0.2, 0.3])
+            .into_iter()
+            .all(|c| c.abs() < 0.01));
+    }
+
+    #[test]
+    fn uv_grid() {
+        let line_color = color![0.1, 0.1, 0.1];
+        let base = color![0.9, 0.9, 0.9];
+        let grid = UvGridTexture::new(10., line_color, base);
+
+        assert_eq!(grid.color_at(0.3, 0.55, Vector3::zeros()), line_color);
+        assert_eq!(grid.color_at(0.55, 0.7, Vector3::zeros()), line_color);
+        assert_eq!(grid.color_at(0.55, 0.35, Vector3::zeros()), base);
+    }
+
+    #[test]
+    fn rotated_checker() {
+        let even = color![1., 1., 1.];
+        let odd = color![0., 0., 0.];
+        let checker = CheckerTexture::solid_colors(even, odd).with_scale(1.);
+        let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_4);
+        let rotated = checker.clone().with_rotation(rotation);
+
+        // (2, 1, 1) lies in an even check, (2, 1, 1) rotated back by 45° in an odd one.
+        let point = vector![2., 1., 1.];
+        assert_eq!(checker.color_at(0., 0., point), even);
+        assert_eq!(rotated.color_at(0., 0., point), odd);
+        assert_eq!(rotated.color_at(0., 0., rotation * point), even);
+    }
+
+    #[test]
+    fn bilinear_filter() {
+        let image = RgbImage::from_fn(2, 1, |i, _| match i {
+            0 => Rgb([0, 0, 0]),
+            _ => Rgb([255, 255, 255]),
+        });
+        let nearest = ImageTexture::new(image.clone());
+        let bilinear = ImageTexture::new(image).with_filter(Filter::Bilinear);
+
+        // Between the texel centers at u = 0.25 and u = 0.75.
+        assert_eq!(nearest.color_at(0.4, 0.5, Vector3::zeros()), BLACK);
+        let gray = bilinear.color_at(0.5, 0.5, Vector3::zeros());
+        assert!((gray - color![0.5, 0.5, 0.5])
+            .into_iter()
+            .all(|c| c.abs() < 1e-6));
+
+        // The edges clamp instead of wrapping around to the other side.
+        assert_eq!(bilinear.color_at(0., 0.5, Vector3::zeros()), BLACK);
+        assert_eq!(bilinear.color_at(1., 0.5, Vector3::zeros()), WHITE);
+
+        // Unless the texture repeats, where both edges blend the first and last texel.
+        let repeat = bilinear.with_wrap(Wrap::Repeat);
+        for u in [0.001, 0.999] {
+            let seam = repeat.color_at(u, 0.5, Vector3::zeros());
+            assert!((seam - color![0.5, 0.5, 0.5])
+                .into_iter()
+                .all(|c| c.abs() < 1e-2));
         }
-        if j >= self.image.height() {
-            j = self.image.height() - 1;
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let image = RgbImage::from_fn(4, 1, |i, _| Rgb([i as u8 * 80, 0, 0]));
+        let texel = |wrap: Wrap, u: f32| {
+            let texture = ImageTexture::new(image.clone()).with_wrap(wrap);
+            (texture.color_at(u, 0.5, Vector3::zeros()).r() * 255. / 80.).round() as u8
+        };
+
+        assert_eq!(texel(Wrap::Clamp, 1.5), 3);
+        assert_eq!(texel(Wrap::Clamp, -0.25), 0);
+        assert_eq!(texel(Wrap::Repeat, 1.5), 2);
+        assert_eq!(texel(Wrap::Repeat, -0.25), 3);
+        assert_eq!(texel(Wrap::Mirror, 1.5), 2);
+        assert_eq!(texel(Wrap::Mirror, -0.25), 1);
+    }
+
+    #[test]
+    fn uv_checker() {
+        let checker = CheckerTexture::uv(SolidColor::new(WHITE), SolidColor::new(BLACK), 4.);
+
+        for hit_point in [Vector3::zeros(), vector![100., -3., 7.]] {
+            assert_eq!(checker.color_at(0.1, 0.1, hit_point), WHITE);
+            assert_eq!(checker.color_at(0.3, 0.1, hit_point), BLACK);
+            assert_eq!(checker.color_at(0.3, 0.3, hit_point), WHITE);
+            assert_eq!(checker.color_at(0.9, 0.6, hit_point), BLACK);
         }
+    }
 
-        (*self.image.get_pixel(i, j)).into()
+    #[test]
+    fn marble_in_range() {
+        let marble = MarbleTexture::new(4., 10., 7);
+
+        for _ in 0..1000 {
+            let point = crate::vec3::random_vector_in_range(-10., 10.);
+            let color = marble.color_at(0., 0., point);
+            assert!(color.into_iter().all(|c| (0. ..=1.).contains(&c)));
+            assert_eq!(color.r(), color.g());
+        }
     }
 }
@@ -51,13 +51,13 @@ pub fn near_zero(vec: &Vector3<f32>) -> bool {
 
 /// Creates a random vector with each element between 0 and 1.
 pub fn random_vector() -> Vector3<f32> {
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::random::rng();
     Vector3::new(rng.gen(), rng.gen(), rng.gen())
 }
 
 /// Creates a random vector with each element in a range.
 pub fn random_vector_in_range(min: f32, max: f32) -> Vector3<f32> {
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::random::rng();
     Vector3::new(
         min + rng.gen::<f32>() * (max - min),
         min + rng.gen::<f32>() * (max - min),
@@ -78,6 +78,21 @@ pub fn random_unit_vector_in_unit_sphere() -> Vector3<f32> {
     random_vector_in_unit_sphere().normalize()
 }
 
+/// Creates a random unit vector uniformly distributed on the unit sphere.
+///
+/// In contrast to [`random_unit_vector_in_unit_sphere`], this uses a direct spherical parametrization instead of rejection sampling.
+pub fn random_unit_vector<R: Rng + ?Sized>(rng: &mut R) -> Vector3<f32> {
+    let z = 2. * rng.gen::<f32>() - 1.;
+    let phi = 2. * std::f32::consts::PI * rng.gen::<f32>();
+    let r = (1. - z * z).max(0.).sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Creates `n` random unit vectors uniformly distributed on the unit sphere (see [`random_unit_vector`]).
+pub fn random_unit_vectors<R: Rng + ?Sized>(n: usize, rng: &mut R) -> Vec<Vector3<f32>> {
+    (0..n).map(|_| random_unit_vector(rng)).collect()
+}
+
 pub fn random_vector_in_hemisphere(normal: &Vector3<f32>) -> Vector3<f32> {
     let rand = random_vector_in_unit_sphere();
     if rand.dot(normal) > 0. {
@@ -86,8 +101,51 @@ pub fn random_vector_in_hemisphere(normal: &Vector3<f32>) -> Vector3<f32> {
     -rand
 }
 
+/// Two unit vectors that form a right-handed orthonormal basis together with the unit vector `w`.
+pub fn orthonormal_basis(w: &Vector3<f32>) -> [Vector3<f32>; 2] {
+    let helper = if w.x.abs() > 0.9 {
+        Vector3::y()
+    } else {
+        Vector3::x()
+    };
+    let u = w.cross(&helper).normalize();
+    let v = w.cross(&u);
+    [u, v]
+}
+
+/// Creates a random unit vector distributed according to a Phong lobe `cos^exponent` around `axis`.
+///
+/// An exponent of 0 samples the hemisphere around `axis` uniformly, higher exponents concentrate the samples towards `axis`.
+pub fn random_vector_in_phong_lobe(axis: &Vector3<f32>, exponent: f32) -> Vector3<f32> {
+    let mut rng = crate::random::rng();
+    let cos_theta = rng.gen::<f32>().powf(1. / (exponent + 1.));
+    let sin_theta = (1. - cos_theta.powi(2)).max(0.).sqrt();
+    let phi = 2. * std::f32::consts::PI * rng.gen::<f32>();
+
+    let w = axis.normalize();
+    let [u, v] = orthonormal_basis(&w);
+
+    sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w
+}
+
+/// Creates a random unit vector distributed according to the GGX (Trowbridge-Reitz) normal distribution with roughness `alpha` times the cosine to `axis`.
+///
+/// This is used to importance-sample the half vector of microfacet materials. Smaller values of `alpha` concentrate the samples towards `axis`.
+pub fn random_vector_in_ggx_lobe(axis: &Vector3<f32>, alpha: f32) -> Vector3<f32> {
+    let mut rng = crate::random::rng();
+    let uniform = rng.gen::<f32>();
+    let cos_theta = ((1. - uniform) / (1. + (alpha.powi(2) - 1.) * uniform)).sqrt();
+    let sin_theta = (1. - cos_theta.powi(2)).max(0.).sqrt();
+    let phi = 2. * std::f32::consts::PI * rng.gen::<f32>();
+
+    let w = axis.normalize();
+    let [u, v] = orthonormal_basis(&w);
+
+    sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w
+}
+
 pub fn random_vector_in_unit_disk() -> Vector3<f32> {
-    let mut rng = rand::thread_rng();
+    let mut rng = crate::random::rng();
     loop {
         let rand = Vector3::new(-1. + rng.gen::<f32>() * 2., -1. + rng.gen::<f32>() * 2., 0.);
         if rand.norm_squared() < 1. {
@@ -95,3 +153,34 @@ pub fn random_vector_in_unit_disk() -> Vector3<f32> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn random_unit_vectors_uniform() {
+        let n = 100_000;
+        let vectors = random_unit_vectors(n, &mut StdRng::seed_from_u64(0));
+        assert_eq!(vectors.len(), n);
+
+        for vector in &vectors {
+            assert!((vector.norm() - 1.).abs() < 1e-5);
+        }
+
+        let mean = vectors.iter().sum::<Vector3<f32>>() / n as f32;
+        assert!(mean.norm() < 0.01);
+
+        let second_moment = vectors
+            .iter()
+            .map(|vector| vector.component_mul(vector))
+            .sum::<Vector3<f32>>()
+            / n as f32;
+        for moment in second_moment.iter() {
+            assert!((moment - 1. / 3.).abs() < 0.01);
+        }
+    }
+}